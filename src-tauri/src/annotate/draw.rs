@@ -0,0 +1,265 @@
+//! Anti-aliased shape drawing. Shapes are described by signed distance functions:
+//! a pixel's coverage is how far inside the edge its center lies, clamped to one
+//! pixel, which gives smooth edges without a rasterizer dependency.
+
+use super::{Color, Point, Rect};
+
+/// Alpha-composites `color` onto the pixel at `coverage` (0..=1).
+pub(super) fn blend(img: &mut image::RgbaImage, x: i32, y: i32, color: Color, coverage: f32) {
+    if x < 0 || y < 0 || x >= img.width() as i32 || y >= img.height() as i32 {
+        return;
+    }
+    let alpha = color.0[3] as f32 / 255.0 * coverage.clamp(0.0, 1.0);
+    if alpha <= 0.0 {
+        return;
+    }
+    let px = img.get_pixel_mut(x as u32, y as u32);
+    let dst_alpha = px.0[3] as f32 / 255.0;
+    let out_alpha = alpha + dst_alpha * (1.0 - alpha);
+    for i in 0..3 {
+        let src = color.0[i] as f32 * alpha;
+        let dst = px.0[i] as f32 * dst_alpha * (1.0 - alpha);
+        px.0[i] = ((src + dst) / out_alpha.max(f32::EPSILON)).round() as u8;
+    }
+    px.0[3] = (out_alpha * 255.0).round() as u8;
+}
+
+/// Calls `sdf` for every pixel center in the box and blends where it is inside.
+fn fill_sdf(
+    img: &mut image::RgbaImage,
+    min: Point,
+    max: Point,
+    color: Color,
+    sdf: impl Fn(f32, f32) -> f32,
+) {
+    let x0 = (min.x.floor() as i32 - 1).max(0);
+    let y0 = (min.y.floor() as i32 - 1).max(0);
+    let x1 = (max.x.ceil() as i32 + 1).min(img.width() as i32 - 1);
+    let y1 = (max.y.ceil() as i32 + 1).min(img.height() as i32 - 1);
+    for y in y0..=y1 {
+        for x in x0..=x1 {
+            let d = sdf(x as f32 + 0.5, y as f32 + 0.5);
+            blend(img, x, y, color, 0.5 - d);
+        }
+    }
+}
+
+fn segment_distance(px: f32, py: f32, a: Point, b: Point) -> f32 {
+    let (dx, dy) = (b.x - a.x, b.y - a.y);
+    let len2 = dx * dx + dy * dy;
+    let t = if len2 == 0.0 {
+        0.0
+    } else {
+        (((px - a.x) * dx + (py - a.y) * dy) / len2).clamp(0.0, 1.0)
+    };
+    let (cx, cy) = (a.x + t * dx - px, a.y + t * dy - py);
+    (cx * cx + cy * cy).sqrt()
+}
+
+/// A line with round caps.
+pub(super) fn line(img: &mut image::RgbaImage, a: Point, b: Point, color: Color, width: f32) {
+    let half = width.max(1.0) / 2.0;
+    let min = Point {
+        x: a.x.min(b.x) - half,
+        y: a.y.min(b.y) - half,
+    };
+    let max = Point {
+        x: a.x.max(b.x) + half,
+        y: a.y.max(b.y) + half,
+    };
+    fill_sdf(img, min, max, color, |x, y| {
+        segment_distance(x, y, a, b) - half
+    });
+}
+
+/// A filled triangle.
+fn triangle(img: &mut image::RgbaImage, p: [Point; 3], color: Color) {
+    // Inside is on the same side of every edge; which side depends on the winding
+    let area = (p[1].x - p[0].x) * (p[2].y - p[0].y) - (p[2].x - p[0].x) * (p[1].y - p[0].y);
+    let winding = if area < 0.0 { -1.0 } else { 1.0 };
+    let min = Point {
+        x: p[0].x.min(p[1].x).min(p[2].x),
+        y: p[0].y.min(p[1].y).min(p[2].y),
+    };
+    let max = Point {
+        x: p[0].x.max(p[1].x).max(p[2].x),
+        y: p[0].y.max(p[1].y).max(p[2].y),
+    };
+    fill_sdf(img, min, max, color, |x, y| {
+        (0..3)
+            .map(|i| {
+                let (a, b) = (p[i], p[(i + 1) % 3]);
+                let (ex, ey) = (b.x - a.x, b.y - a.y);
+                let len = (ex * ex + ey * ey).sqrt().max(f32::EPSILON);
+                -(ex * (y - a.y) - ey * (x - a.x)) * winding / len
+            })
+            .fold(f32::MIN, f32::max)
+    });
+}
+
+/// A line ending in a filled head at `to`, sized from the stroke width.
+pub(super) fn arrow(img: &mut image::RgbaImage, from: Point, to: Point, color: Color, width: f32) {
+    let (dx, dy) = (to.x - from.x, to.y - from.y);
+    let len = (dx * dx + dy * dy).sqrt();
+    if len < f32::EPSILON {
+        return;
+    }
+    let (ux, uy) = (dx / len, dy / len);
+    let head_len = (width * 4.0).max(12.0).min(len);
+    let head_half = head_len * 0.6;
+    let base = Point {
+        x: to.x - ux * head_len,
+        y: to.y - uy * head_len,
+    };
+
+    // Stop the shaft inside the head so its round cap doesn't poke out of the tip
+    line(
+        img,
+        from,
+        Point {
+            x: base.x + ux * head_len * 0.3,
+            y: base.y + uy * head_len * 0.3,
+        },
+        color,
+        width,
+    );
+    triangle(
+        img,
+        [
+            to,
+            Point {
+                x: base.x - uy * head_half,
+                y: base.y + ux * head_half,
+            },
+            Point {
+                x: base.x + uy * head_half,
+                y: base.y - ux * head_half,
+            },
+        ],
+        color,
+    );
+}
+
+/// Signed distance to a rounded rectangle, negative inside.
+fn rect_sdf(x: f32, y: f32, rect: Rect, radius: f32) -> f32 {
+    let (hw, hh) = (rect.w.abs() / 2.0, rect.h.abs() / 2.0);
+    let radius = radius.clamp(0.0, hw.min(hh));
+    let cx = rect.x + rect.w / 2.0;
+    let cy = rect.y + rect.h / 2.0;
+    let qx = (x - cx).abs() - hw + radius;
+    let qy = (y - cy).abs() - hh + radius;
+    let outside = (qx.max(0.0).powi(2) + qy.max(0.0).powi(2)).sqrt();
+    outside + qx.max(qy).min(0.0) - radius
+}
+
+/// A rectangle outline, optionally filled first.
+pub(super) fn rect(
+    img: &mut image::RgbaImage,
+    rect: Rect,
+    color: Color,
+    width: f32,
+    fill: Option<Color>,
+    corner_radius: f32,
+) {
+    let half = width.max(0.0) / 2.0;
+    let min = Point {
+        x: rect.x.min(rect.x + rect.w) - half,
+        y: rect.y.min(rect.y + rect.h) - half,
+    };
+    let max = Point {
+        x: rect.x.max(rect.x + rect.w) + half,
+        y: rect.y.max(rect.y + rect.h) + half,
+    };
+    if let Some(fill) = fill {
+        fill_sdf(img, min, max, fill, |x, y| {
+            rect_sdf(x, y, rect, corner_radius)
+        });
+    }
+    if width > 0.0 {
+        fill_sdf(img, min, max, color, |x, y| {
+            rect_sdf(x, y, rect, corner_radius).abs() - half
+        });
+    }
+}
+
+pub(super) fn circle(img: &mut image::RgbaImage, center: Point, radius: f32, color: Color) {
+    let min = Point {
+        x: center.x - radius,
+        y: center.y - radius,
+    };
+    let max = Point {
+        x: center.x + radius,
+        y: center.y + radius,
+    };
+    fill_sdf(img, min, max, color, |x, y| {
+        ((x - center.x).powi(2) + (y - center.y).powi(2)).sqrt() - radius
+    });
+}
+
+/// Black or white, whichever reads better on `color`.
+pub(super) fn contrast(color: Color) -> Color {
+    let [r, g, b, _] = color.0;
+    let luma = 0.299 * r as f32 + 0.587 * g as f32 + 0.114 * b as f32;
+    if luma > 160.0 {
+        Color([0, 0, 0, 255])
+    } else {
+        Color([255, 255, 255, 255])
+    }
+}
+
+/// The rectangle in whole pixels, clamped to the image; `None` if nothing is left.
+fn pixel_bounds(img: &image::RgbaImage, rect: Rect) -> Option<(u32, u32, u32, u32)> {
+    let x0 = rect.x.min(rect.x + rect.w).floor().max(0.0) as u32;
+    let y0 = rect.y.min(rect.y + rect.h).floor().max(0.0) as u32;
+    let x1 = (rect.x.max(rect.x + rect.w).ceil() as u32).min(img.width());
+    let y1 = (rect.y.max(rect.y + rect.h).ceil() as u32).min(img.height());
+    (x1 > x0 && y1 > y0).then_some((x0, y0, x1 - x0, y1 - y0))
+}
+
+/// Gaussian blur inside `rect`. Pixels around it are sampled too, so the blurred
+/// area blends into its surroundings instead of ending in a hard edge.
+pub(super) fn blur(img: &mut image::RgbaImage, rect: Rect, radius: f32) {
+    let Some((x, y, w, h)) = pixel_bounds(img, rect) else {
+        return;
+    };
+    let margin = (radius * 3.0).ceil() as u32;
+    let mx = x.saturating_sub(margin);
+    let my = y.saturating_sub(margin);
+    let mw = (x + w + margin).min(img.width()) - mx;
+    let mh = (y + h + margin).min(img.height()) - my;
+
+    let area = image::imageops::crop_imm(img, mx, my, mw, mh).to_image();
+    let blurred = image::imageops::blur(&area, radius.max(0.5));
+    let inner = image::imageops::crop_imm(&blurred, x - mx, y - my, w, h).to_image();
+    image::imageops::replace(img, &inner, x as i64, y as i64);
+}
+
+/// Replaces `rect` with blocks of their average color. Blocks are aligned to the
+/// rectangle, so the result doesn't depend on where the image was cropped.
+pub(super) fn pixelate(img: &mut image::RgbaImage, rect: Rect, block_size: u32) {
+    let Some((x, y, w, h)) = pixel_bounds(img, rect) else {
+        return;
+    };
+    let block = block_size.max(2);
+    for by in (y..y + h).step_by(block as usize) {
+        for bx in (x..x + w).step_by(block as usize) {
+            let bw = block.min(x + w - bx);
+            let bh = block.min(y + h - by);
+            let mut sum = [0u64; 4];
+            for py in by..by + bh {
+                for px in bx..bx + bw {
+                    for (total, channel) in sum.iter_mut().zip(img.get_pixel(px, py).0) {
+                        *total += channel as u64;
+                    }
+                }
+            }
+            let count = (bw * bh) as u64;
+            let avg = image::Rgba(sum.map(|total| (total / count) as u8));
+            for py in by..by + bh {
+                for px in bx..bx + bw {
+                    img.put_pixel(px, py, avg);
+                }
+            }
+        }
+    }
+}
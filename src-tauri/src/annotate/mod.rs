@@ -0,0 +1,331 @@
+//! Screenshot annotations.
+//!
+//! The frontend edits a list of vector annotations (arrows, boxes, blur and
+//! pixelate regions, text, numbered steps) over a capture and sends it as JSON; it
+//! is rendered here onto the original pixels in list order, so later annotations
+//! sit on top. A project file keeps the untouched capture and the annotations
+//! together so the editing can be picked up again later.
+
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+mod draw;
+mod text;
+
+pub const PROJECT_EXTENSION: &str = "tgproj";
+const PROJECT_VERSION: u32 = 1;
+
+/// `#rrggbb` or `#rrggbbaa`.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(try_from = "String", into = "String")]
+pub struct Color(pub [u8; 4]);
+
+impl TryFrom<String> for Color {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        let hex = value.trim().trim_start_matches('#');
+        let channel = |i: usize| {
+            hex.get(i..i + 2)
+                .and_then(|c| u8::from_str_radix(c, 16).ok())
+                .ok_or_else(|| format!("Invalid color: {}", value))
+        };
+        match hex.len() {
+            6 => Ok(Color([channel(0)?, channel(2)?, channel(4)?, 255])),
+            8 => Ok(Color([channel(0)?, channel(2)?, channel(4)?, channel(6)?])),
+            _ => Err(format!("Invalid color: {}", value)),
+        }
+    }
+}
+
+impl From<Color> for String {
+    fn from(color: Color) -> Self {
+        let [r, g, b, a] = color.0;
+        if a == 255 {
+            format!("#{:02x}{:02x}{:02x}", r, g, b)
+        } else {
+            format!("#{:02x}{:02x}{:02x}{:02x}", r, g, b, a)
+        }
+    }
+}
+
+/// A point in image pixels.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct Point {
+    pub x: f32,
+    pub y: f32,
+}
+
+/// A rectangle in image pixels.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct Rect {
+    pub x: f32,
+    pub y: f32,
+    pub w: f32,
+    pub h: f32,
+}
+
+fn default_stroke() -> f32 {
+    4.0
+}
+
+fn default_text_size() -> f32 {
+    24.0
+}
+
+fn default_step_radius() -> f32 {
+    16.0
+}
+
+fn default_blur_radius() -> f32 {
+    8.0
+}
+
+fn default_block_size() -> u32 {
+    12
+}
+
+/// One layer, tagged by `type`, e.g.
+/// `{"type": "arrow", "from": {"x": 10, "y": 10}, "to": {"x": 80, "y": 40}, "color": "#ff3b30"}`.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Annotation {
+    Arrow {
+        from: Point,
+        to: Point,
+        color: Color,
+        #[serde(default = "default_stroke")]
+        width: f32,
+    },
+    #[serde(rename = "box")]
+    Rect {
+        rect: Rect,
+        color: Color,
+        #[serde(default = "default_stroke")]
+        width: f32,
+        #[serde(default)]
+        fill: Option<Color>,
+        #[serde(default)]
+        corner_radius: f32,
+    },
+    Blur {
+        rect: Rect,
+        #[serde(default = "default_blur_radius")]
+        radius: f32,
+    },
+    Pixelate {
+        rect: Rect,
+        #[serde(default = "default_block_size")]
+        block_size: u32,
+    },
+    /// `at` is the top-left of the first line; `\n` starts a new line.
+    Text {
+        at: Point,
+        text: String,
+        color: Color,
+        #[serde(default = "default_text_size")]
+        size: f32,
+        #[serde(default)]
+        background: Option<Color>,
+    },
+    /// A numbered badge centered on `at`.
+    Step {
+        at: Point,
+        number: u32,
+        color: Color,
+        #[serde(default = "default_step_radius")]
+        radius: f32,
+    },
+}
+
+/// Draws `annotations` over a copy of `base`.
+pub fn render(
+    base: &image::RgbaImage,
+    annotations: &[Annotation],
+) -> Result<image::RgbaImage, String> {
+    let mut img = base.clone();
+    for annotation in annotations {
+        match annotation {
+            Annotation::Arrow {
+                from,
+                to,
+                color,
+                width,
+            } => draw::arrow(&mut img, *from, *to, *color, *width),
+            Annotation::Rect {
+                rect,
+                color,
+                width,
+                fill,
+                corner_radius,
+            } => draw::rect(&mut img, *rect, *color, *width, *fill, *corner_radius),
+            Annotation::Blur { rect, radius } => draw::blur(&mut img, *rect, *radius),
+            Annotation::Pixelate { rect, block_size } => {
+                draw::pixelate(&mut img, *rect, *block_size)
+            }
+            Annotation::Text {
+                at,
+                text,
+                color,
+                size,
+                background,
+            } => text::draw_text(
+                &mut img,
+                text::font()?,
+                *at,
+                text,
+                *color,
+                *size,
+                *background,
+            ),
+            Annotation::Step {
+                at,
+                number,
+                color,
+                radius,
+            } => {
+                draw::circle(&mut img, *at, *radius, *color);
+                text::draw_centered(
+                    &mut img,
+                    *at,
+                    &number.to_string(),
+                    draw::contrast(*color),
+                    *radius * 1.2,
+                )?;
+            }
+        }
+    }
+    Ok(img)
+}
+
+/// `text` in the bundled font on a transparent image just large enough to hold it;
+/// the same pixels on every machine.
+pub fn render_text(text: &str, color: Color, size: f32) -> Result<image::RgbaImage, String> {
+    let font = text::bundled_font()?;
+    let (width, height) = text::text_size(&font, text, size);
+    let mut img =
+        image::RgbaImage::new((width.ceil() as u32).max(1), (height.ceil() as u32).max(1));
+    let origin = Point { x: 0.0, y: 0.0 };
+    text::draw_text(&mut img, &font, origin, text, color, size, None);
+    Ok(img)
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum ExportFormat {
+    #[default]
+    Png,
+    Jpeg,
+    Webp,
+}
+
+impl ExportFormat {
+    pub fn extension(self) -> &'static str {
+        match self {
+            ExportFormat::Png => "png",
+            ExportFormat::Jpeg => "jpg",
+            ExportFormat::Webp => "webp",
+        }
+    }
+}
+
+/// Encodes `img`; `quality` (1-100, default 90) applies to JPEG and WebP.
+pub fn encode(
+    img: &image::RgbaImage,
+    format: ExportFormat,
+    quality: Option<u8>,
+) -> Result<Vec<u8>, String> {
+    use image::ImageEncoder;
+
+    let quality = quality.unwrap_or(90).clamp(1, 100);
+    let mut bytes = Vec::new();
+    match format {
+        ExportFormat::Png => image::codecs::png::PngEncoder::new(&mut bytes)
+            .write_image(
+                img.as_raw(),
+                img.width(),
+                img.height(),
+                image::ColorType::Rgba8,
+            )
+            .map_err(|e| format!("Failed to encode PNG: {}", e))?,
+        ExportFormat::Jpeg => {
+            // JPEG has no alpha
+            let rgb = image::DynamicImage::ImageRgba8(img.clone()).to_rgb8();
+            image::codecs::jpeg::JpegEncoder::new_with_quality(&mut bytes, quality)
+                .encode_image(&rgb)
+                .map_err(|e| format!("Failed to encode JPEG: {}", e))?
+        }
+        ExportFormat::Webp => {
+            use image::codecs::webp::{WebPEncoder, WebPQuality};
+
+            #[allow(deprecated)]
+            let encoder = WebPEncoder::new_with_quality(&mut bytes, WebPQuality::lossy(quality));
+            encoder
+                .encode(
+                    img.as_raw(),
+                    img.width(),
+                    img.height(),
+                    image::ColorType::Rgba8,
+                )
+                .map_err(|e| format!("Failed to encode WebP: {}", e))?
+        }
+    }
+    Ok(bytes)
+}
+
+/// Editable annotation project: the original capture (PNG, base64) plus the layers.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Project {
+    pub version: u32,
+    pub width: u32,
+    pub height: u32,
+    pub image: String,
+    pub annotations: Vec<Annotation>,
+}
+
+impl Project {
+    pub fn new(base: &image::RgbaImage, annotations: Vec<Annotation>) -> Result<Self, String> {
+        use base64::{engine::general_purpose, Engine as _};
+
+        Ok(Self {
+            version: PROJECT_VERSION,
+            width: base.width(),
+            height: base.height(),
+            image: general_purpose::STANDARD.encode(encode(base, ExportFormat::Png, None)?),
+            annotations,
+        })
+    }
+
+    pub fn base_image(&self) -> Result<image::RgbaImage, String> {
+        use base64::{engine::general_purpose, Engine as _};
+
+        let bytes = general_purpose::STANDARD
+            .decode(&self.image)
+            .map_err(|e| format!("Failed to decode project image: {}", e))?;
+        Ok(image::load_from_memory(&bytes)
+            .map_err(|e| format!("Failed to read project image: {}", e))?
+            .to_rgba8())
+    }
+
+    /// Written to a temp file and renamed, so a crash never leaves half a project.
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        let json =
+            serde_json::to_vec(self).map_err(|e| format!("Failed to serialize project: {}", e))?;
+        let tmp = path.with_extension(format!("{}.tmp", PROJECT_EXTENSION));
+        std::fs::write(&tmp, json).map_err(|e| format!("Failed to write project: {}", e))?;
+        std::fs::rename(&tmp, path).map_err(|e| format!("Failed to write project: {}", e))
+    }
+
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let bytes = std::fs::read(path).map_err(|e| format!("Failed to read project: {}", e))?;
+        let project: Self = serde_json::from_slice(&bytes)
+            .map_err(|e| format!("Invalid annotation project: {}", e))?;
+        if project.version > PROJECT_VERSION {
+            return Err(format!(
+                "This project was saved by a newer version (format {})",
+                project.version
+            ));
+        }
+        Ok(project)
+    }
+}
@@ -0,0 +1,196 @@
+//! Text for annotations, rasterized with ab_glyph from a system font, and for
+//! watermarks in a bundled one.
+
+use super::{draw, Color, Point, Rect};
+use ab_glyph::{point, Font, FontRef, FontVec, Glyph, PxScale, ScaleFont};
+use std::sync::OnceLock;
+
+/// Regular sans-serif fonts that ship with each OS, tried in order.
+#[cfg(target_os = "macos")]
+const FONT_CANDIDATES: &[&str] = &[
+    "/System/Library/Fonts/Supplemental/Arial.ttf",
+    "/Library/Fonts/Arial.ttf",
+    "/System/Library/Fonts/Helvetica.ttc",
+];
+#[cfg(target_os = "windows")]
+const FONT_CANDIDATES: &[&str] = &[
+    "C:\\Windows\\Fonts\\segoeui.ttf",
+    "C:\\Windows\\Fonts\\arial.ttf",
+];
+#[cfg(not(any(target_os = "macos", target_os = "windows")))]
+const FONT_CANDIDATES: &[&str] = &[
+    "/usr/share/fonts/truetype/dejavu/DejaVuSans.ttf",
+    "/usr/share/fonts/TTF/DejaVuSans.ttf",
+    "/usr/share/fonts/dejavu/DejaVuSans.ttf",
+    "/usr/share/fonts/truetype/liberation/LiberationSans-Regular.ttf",
+    "/usr/share/fonts/liberation/LiberationSans-Regular.ttf",
+    "/usr/share/fonts/truetype/noto/NotoSans-Regular.ttf",
+    "/usr/share/fonts/noto/NotoSans-Regular.ttf",
+];
+
+static FONT: OnceLock<Option<FontVec>> = OnceLock::new();
+
+/// `TASKGOBLIN_ANNOTATION_FONT` points at a specific TTF/OTF file; otherwise the
+/// first candidate that exists is loaded once and reused.
+pub(super) fn font() -> Result<&'static FontVec, String> {
+    FONT.get_or_init(|| {
+        let custom = std::env::var("TASKGOBLIN_ANNOTATION_FONT").ok();
+        custom
+            .iter()
+            .map(String::as_str)
+            .chain(FONT_CANDIDATES.iter().copied())
+            .find_map(|path| {
+                let data = std::fs::read(path).ok()?;
+                FontVec::try_from_vec_and_index(data, 0).ok()
+            })
+    })
+    .as_ref()
+    .ok_or_else(|| "No font found for text annotations".to_string())
+}
+
+/// DejaVu Sans (see `fonts/LICENSE`), compiled in so text renders the same on
+/// every machine whatever fonts are installed.
+pub(super) fn bundled_font() -> Result<FontRef<'static>, String> {
+    FontRef::try_from_slice(include_bytes!("../../fonts/DejaVuSans.ttf"))
+        .map_err(|e| format!("Failed to load the bundled font: {}", e))
+}
+
+/// Glyphs for one line with its baseline starting at `origin`, and the line width.
+fn layout_line(
+    font: &impl Font,
+    scale: PxScale,
+    text: &str,
+    origin: ab_glyph::Point,
+) -> (Vec<Glyph>, f32) {
+    let scaled = font.as_scaled(scale);
+    let mut caret = origin.x;
+    let mut previous = None;
+    let glyphs = text
+        .chars()
+        .filter(|c| !c.is_control())
+        .map(|c| {
+            let id = font.glyph_id(c);
+            if let Some(previous) = previous {
+                caret += scaled.kern(previous, id);
+            }
+            let glyph = id.with_scale_and_position(scale, point(caret, origin.y));
+            caret += scaled.h_advance(id);
+            previous = Some(id);
+            glyph
+        })
+        .collect();
+    (glyphs, caret - origin.x)
+}
+
+fn draw_glyphs(img: &mut image::RgbaImage, font: &impl Font, glyphs: Vec<Glyph>, color: Color) {
+    for glyph in glyphs {
+        if let Some(outlined) = font.outline_glyph(glyph) {
+            let bounds = outlined.px_bounds();
+            outlined.draw(|gx, gy, coverage| {
+                draw::blend(
+                    img,
+                    bounds.min.x as i32 + gx as i32,
+                    bounds.min.y as i32 + gy as i32,
+                    color,
+                    coverage,
+                );
+            });
+        }
+    }
+}
+
+/// Width and height `draw_text` covers for `text` at `size`, without background.
+pub(super) fn text_size(font: &impl Font, text: &str, size: f32) -> (f32, f32) {
+    let scale = PxScale::from(size.max(1.0));
+    let scaled = font.as_scaled(scale);
+    let line_height = scaled.height() + scaled.line_gap();
+    let lines: Vec<&str> = text.lines().collect();
+    let width = lines
+        .iter()
+        .map(|line| layout_line(font, scale, line, point(0.0, 0.0)).1)
+        .fold(0.0, f32::max);
+    let height = lines.len() as f32 * line_height - scaled.line_gap();
+    (width, height.max(0.0))
+}
+
+/// Multi-line text with its top-left at `at`, over an optional rounded background.
+pub(super) fn draw_text(
+    img: &mut image::RgbaImage,
+    font: &impl Font,
+    at: Point,
+    text: &str,
+    color: Color,
+    size: f32,
+    background: Option<Color>,
+) {
+    if text.trim().is_empty() {
+        return;
+    }
+    let scale = PxScale::from(size.max(1.0));
+    let scaled = font.as_scaled(scale);
+    let line_height = scaled.height() + scaled.line_gap();
+
+    let lines: Vec<(Vec<Glyph>, f32)> = text
+        .lines()
+        .enumerate()
+        .map(|(i, line)| {
+            let baseline = at.y + scaled.ascent() + i as f32 * line_height;
+            layout_line(font, scale, line, point(at.x, baseline))
+        })
+        .collect();
+
+    if let Some(background) = background {
+        let padding = size * 0.3;
+        let width = lines.iter().map(|(_, w)| *w).fold(0.0, f32::max);
+        let height = lines.len() as f32 * line_height - scaled.line_gap();
+        draw::rect(
+            img,
+            Rect {
+                x: at.x - padding,
+                y: at.y - padding,
+                w: width + padding * 2.0,
+                h: height + padding * 2.0,
+            },
+            background,
+            0.0,
+            Some(background),
+            padding,
+        );
+    }
+
+    for (glyphs, _) in lines {
+        draw_glyphs(img, font, glyphs, color);
+    }
+}
+
+/// A single line centered on `center` by its inked bounds, so digits sit in the
+/// middle of step badges regardless of the font's ascent and descent.
+pub(super) fn draw_centered(
+    img: &mut image::RgbaImage,
+    center: Point,
+    text: &str,
+    color: Color,
+    size: f32,
+) -> Result<(), String> {
+    let font = font()?;
+    let scale = PxScale::from(size.max(1.0));
+    let (glyphs, _) = layout_line(font, scale, text, point(0.0, 0.0));
+
+    let bounds = glyphs
+        .iter()
+        .filter_map(|g| font.outline_glyph(g.clone()))
+        .map(|o| o.px_bounds())
+        .reduce(|a, b| ab_glyph::Rect {
+            min: point(a.min.x.min(b.min.x), a.min.y.min(b.min.y)),
+            max: point(a.max.x.max(b.max.x), a.max.y.max(b.max.y)),
+        });
+    let Some(bounds) = bounds else {
+        return Ok(());
+    };
+
+    let dx = center.x - (bounds.min.x + bounds.max.x) / 2.0;
+    let dy = center.y - (bounds.min.y + bounds.max.y) / 2.0;
+    let (glyphs, _) = layout_line(font, scale, text, point(dx, dy));
+    draw_glyphs(img, font, glyphs, color);
+    Ok(())
+}
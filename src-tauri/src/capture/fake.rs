@@ -0,0 +1,93 @@
+use super::{MonitorInfo, ScreenCapturer, WindowInfo};
+
+/// In-memory screens and windows. Captures return copies of the stored images, so
+/// capture consumers can be exercised without a display.
+pub struct FakeCapturer {
+    screens: Result<Vec<image::RgbaImage>, String>,
+    windows: Vec<(WindowInfo, image::RgbaImage)>,
+}
+
+impl FakeCapturer {
+    /// Monitors laid out left to right, the first one primary.
+    pub fn with_screens(screens: Vec<image::RgbaImage>) -> Self {
+        Self {
+            screens: Ok(screens),
+            windows: Vec::new(),
+        }
+    }
+
+    pub fn failing(message: &str) -> Self {
+        Self {
+            screens: Err(message.to_string()),
+            windows: Vec::new(),
+        }
+    }
+
+    /// `TASKGOBLIN_CAPTURE_FAKE_ERROR` makes every capture fail with that message,
+    /// otherwise a single gradient monitor is served.
+    pub fn from_env() -> Self {
+        match std::env::var("TASKGOBLIN_CAPTURE_FAKE_ERROR") {
+            Ok(err) => Self::failing(&err),
+            Err(_) => Self::default(),
+        }
+    }
+}
+
+impl Default for FakeCapturer {
+    /// One 1920x1080 monitor filled with a gradient, so crops are distinguishable.
+    fn default() -> Self {
+        let screen = image::RgbaImage::from_fn(1920, 1080, |x, y| {
+            image::Rgba([(x % 256) as u8, (y % 256) as u8, 128, 255])
+        });
+        Self::with_screens(vec![screen])
+    }
+}
+
+impl ScreenCapturer for FakeCapturer {
+    fn name(&self) -> &'static str {
+        "fake"
+    }
+
+    fn monitors(&self) -> Result<Vec<MonitorInfo>, String> {
+        let screens = self.screens.as_ref().map_err(|e| e.clone())?;
+        let mut x = 0;
+        Ok(screens
+            .iter()
+            .enumerate()
+            .map(|(index, img)| {
+                let info = MonitorInfo {
+                    index,
+                    name: format!("Fake {}", index + 1),
+                    x,
+                    y: 0,
+                    width: img.width(),
+                    height: img.height(),
+                    scale_factor: 1.0,
+                    is_primary: index == 0,
+                };
+                x += img.width() as i32;
+                info
+            })
+            .collect())
+    }
+
+    fn windows(&self) -> Result<Vec<WindowInfo>, String> {
+        Ok(self.windows.iter().map(|(info, _)| info.clone()).collect())
+    }
+
+    fn capture_monitor(&self, index: usize) -> Result<image::RgbaImage, String> {
+        let screens = self.screens.as_ref().map_err(|e| e.clone())?;
+        screens
+            .get(index)
+            .cloned()
+            .ok_or_else(|| format!("Monitor {} not found", index))
+    }
+
+    fn capture_window(&self, id: u32) -> Result<image::RgbaImage, String> {
+        self.windows
+            .iter()
+            .find(|(info, _)| info.id == id)
+            .map(|(_, img)| img.clone())
+            .ok_or_else(|| format!("Window {} not found", id))
+    }
+}
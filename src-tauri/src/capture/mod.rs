@@ -0,0 +1,246 @@
+//! Screen capture.
+//!
+//! Every grab (paint captures, OCR regions, color picking) goes through one
+//! [`ScreenCapturer`] and comes back as an `image::RgbaImage` in physical pixels,
+//! instead of each feature shelling out to `screencapture` or PowerShell.
+
+use serde::{Deserialize, Serialize};
+
+mod fake;
+#[cfg(any(target_os = "macos", target_os = "windows", target_os = "linux"))]
+mod native;
+mod stitch;
+
+pub use fake::FakeCapturer;
+#[cfg(any(target_os = "macos", target_os = "windows", target_os = "linux"))]
+pub use native::NativeCapturer;
+pub use stitch::{StitchStep, Stitcher};
+
+/// A display, with its bounds in physical pixels on the virtual desktop.
+#[derive(Serialize, Debug, Clone)]
+pub struct MonitorInfo {
+    /// Position in [`ScreenCapturer::monitors`]; what [`CaptureTarget::Monitor`] takes
+    pub index: usize,
+    pub name: String,
+    pub x: i32,
+    pub y: i32,
+    pub width: u32,
+    pub height: u32,
+    pub scale_factor: f32,
+    pub is_primary: bool,
+}
+
+impl MonitorInfo {
+    pub fn contains(&self, x: i32, y: i32) -> bool {
+        x >= self.x
+            && y >= self.y
+            && x < self.x + self.width as i32
+            && y < self.y + self.height as i32
+    }
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct WindowInfo {
+    pub id: u32,
+    pub title: String,
+    pub app_name: String,
+}
+
+/// Serialized externally tagged: `"full_screen"`, `{"monitor": 1}`, `{"window": 42}`,
+/// `{"region": {"monitor": 0, "x": 10, "y": 10, "width": 200, "height": 100}}`.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum CaptureTarget {
+    /// The primary monitor
+    FullScreen,
+    /// A monitor by index
+    Monitor(usize),
+    /// A rectangle in physical pixels relative to the top-left of monitor `monitor`
+    Region {
+        monitor: usize,
+        x: i32,
+        y: i32,
+        width: u32,
+        height: u32,
+    },
+    /// A top-level window by [`WindowInfo::id`]
+    Window(u32),
+}
+
+pub trait ScreenCapturer: Send + Sync {
+    fn name(&self) -> &'static str;
+    fn monitors(&self) -> Result<Vec<MonitorInfo>, String>;
+    fn windows(&self) -> Result<Vec<WindowInfo>, String>;
+    /// Grabs a whole monitor.
+    fn capture_monitor(&self, index: usize) -> Result<image::RgbaImage, String>;
+    fn capture_window(&self, id: u32) -> Result<image::RgbaImage, String>;
+
+    fn capture(&self, target: CaptureTarget) -> Result<image::RgbaImage, String> {
+        match target {
+            CaptureTarget::FullScreen => {
+                let monitors = self.monitors()?;
+                let primary = monitors
+                    .iter()
+                    .find(|m| m.is_primary)
+                    .or(monitors.first())
+                    .ok_or("No monitor found")?;
+                self.capture_monitor(primary.index)
+            }
+            CaptureTarget::Monitor(index) => self.capture_monitor(index),
+            CaptureTarget::Region {
+                monitor,
+                x,
+                y,
+                width,
+                height,
+            } => crop(&self.capture_monitor(monitor)?, x, y, width, height),
+            CaptureTarget::Window(id) => self.capture_window(id),
+        }
+    }
+}
+
+/// Capturer for the current OS. `TASKGOBLIN_CAPTURE_BACKEND=fake` swaps in
+/// [`FakeCapturer`] so capture flows run without a display or screen permissions.
+pub fn default_capturer() -> Box<dyn ScreenCapturer> {
+    if std::env::var("TASKGOBLIN_CAPTURE_BACKEND").as_deref() == Ok("fake") {
+        return Box::new(FakeCapturer::from_env());
+    }
+
+    #[cfg(any(target_os = "macos", target_os = "windows", target_os = "linux"))]
+    {
+        Box::new(NativeCapturer)
+    }
+    #[cfg(not(any(target_os = "macos", target_os = "windows", target_os = "linux")))]
+    {
+        Box::new(FakeCapturer::failing(
+            "Screen capture is only supported on macOS, Windows and Linux",
+        ))
+    }
+}
+
+/// Crops `img` to the rectangle, clamped to its bounds.
+fn crop(
+    img: &image::RgbaImage,
+    x: i32,
+    y: i32,
+    width: u32,
+    height: u32,
+) -> Result<image::RgbaImage, String> {
+    // Edges in i64 so a region hanging off either side loses only that part
+    let (left, top) = (x.max(0) as i64, y.max(0) as i64);
+    let right = (x as i64 + width as i64).min(img.width() as i64);
+    let bottom = (y as i64 + height as i64).min(img.height() as i64);
+    if right <= left || bottom <= top {
+        return Err("Selected region is outside the screen".to_string());
+    }
+    Ok(image::imageops::crop_imm(
+        img,
+        left as u32,
+        top as u32,
+        (right - left) as u32,
+        (bottom - top) as u32,
+    )
+    .to_image())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A screen whose pixels encode their own coordinates and the screen number.
+    fn screen(number: u8, width: u32, height: u32) -> image::RgbaImage {
+        image::RgbaImage::from_fn(width, height, |x, y| {
+            image::Rgba([x as u8, y as u8, number, 255])
+        })
+    }
+
+    fn capturer() -> FakeCapturer {
+        FakeCapturer::with_screens(vec![
+            screen(0, 200, 100),
+            screen(1, 120, 80),
+            screen(2, 60, 40),
+        ])
+    }
+
+    fn region(monitor: usize, x: i32, y: i32, width: u32, height: u32) -> CaptureTarget {
+        CaptureTarget::Region {
+            monitor,
+            x,
+            y,
+            width,
+            height,
+        }
+    }
+
+    #[test]
+    fn finds_monitors() {
+        let capturer = capturer();
+        let monitors = capturer.monitors().unwrap();
+        let layout: Vec<_> = monitors.iter().map(|m| (m.index, m.x, m.width)).collect();
+        assert_eq!(layout, [(0, 0, 200), (1, 200, 120), (2, 320, 60)]);
+
+        // The monitor under a point on the virtual desktop
+        let at = |x, y| monitors.iter().find(|m| m.contains(x, y)).map(|m| m.index);
+        assert_eq!(at(0, 0), Some(0));
+        assert_eq!(at(199, 99), Some(0));
+        assert_eq!(at(200, 0), Some(1));
+        assert_eq!(at(350, 39), Some(2));
+        assert_eq!(at(350, 40), None);
+        assert_eq!(at(-1, 10), None);
+
+        let full = capturer.capture(CaptureTarget::FullScreen).unwrap();
+        assert_eq!(
+            (full.dimensions(), full.get_pixel(0, 0)[2]),
+            ((200, 100), 0)
+        );
+        let second = capturer.capture(CaptureTarget::Monitor(2)).unwrap();
+        assert_eq!(
+            (second.dimensions(), second.get_pixel(0, 0)[2]),
+            ((60, 40), 2)
+        );
+        assert!(capturer.capture(CaptureTarget::Monitor(3)).is_err());
+        assert!(capturer.capture(region(3, 0, 0, 10, 10)).is_err());
+    }
+
+    #[test]
+    fn crops_regions_of_any_monitor() {
+        let img = capturer().capture(region(1, 10, 20, 30, 15)).unwrap();
+        assert_eq!(img.dimensions(), (30, 15));
+        assert_eq!(img.get_pixel(0, 0).0, [10, 20, 1, 255]);
+        assert_eq!(img.get_pixel(29, 14).0, [39, 34, 1, 255]);
+    }
+
+    #[test]
+    fn clamps_regions_hanging_off_each_edge() {
+        let capturer = capturer();
+        // Left and top: only the part on screen is kept, starting at 0,0
+        let img = capturer.capture(region(1, -10, 5, 30, 20)).unwrap();
+        assert_eq!(img.dimensions(), (20, 20));
+        assert_eq!(img.get_pixel(0, 0).0, [0, 5, 1, 255]);
+        let img = capturer.capture(region(1, 5, -15, 20, 25)).unwrap();
+        assert_eq!(img.dimensions(), (20, 10));
+        assert_eq!(img.get_pixel(0, 0).0, [5, 0, 1, 255]);
+        // Right and bottom
+        let img = capturer.capture(region(1, 100, 70, 50, 50)).unwrap();
+        assert_eq!(img.dimensions(), (20, 10));
+        assert_eq!(img.get_pixel(19, 9).0, [119, 79, 1, 255]);
+        // Every side at once is the whole screen
+        let img = capturer.capture(region(1, -5, -5, 200, 200)).unwrap();
+        assert_eq!(img.dimensions(), (120, 80));
+    }
+
+    #[test]
+    fn rejects_regions_off_screen() {
+        let capturer = capturer();
+        for target in [
+            region(1, -30, 0, 30, 10),
+            region(1, 0, -10, 10, 10),
+            region(1, 120, 0, 10, 10),
+            region(1, 0, 80, 10, 10),
+            region(1, 10, 10, 0, 10),
+            region(1, i32::MIN, 0, i32::MAX as u32, 10),
+        ] {
+            assert!(capturer.capture(target).is_err(), "{:?}", target);
+        }
+    }
+}
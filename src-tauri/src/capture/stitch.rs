@@ -0,0 +1,310 @@
+//! Stitching for scrolling captures: successive grabs of the same region, taken
+//! while the content underneath scrolls, are joined into one tall image.
+//!
+//! Frames are compared row by row. Rows that stay put between two frames at the top
+//! and bottom (sticky headers, toolbars, status bars) are treated as fixed chrome and
+//! kept once; the scroll distance is the row offset at which the moving part of the
+//! new frame lines up with the previous one.
+
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+
+/// Fixed chrome may take at most this fraction of the frame at each edge.
+const MAX_FIXED_FRACTION: f32 = 0.35;
+/// Share of distinctive overlapping rows that must match for an offset to count.
+const MIN_MATCH_RATIO: f32 = 0.97;
+/// Overlaps with fewer distinctive rows than this are too ambiguous to trust.
+const MIN_DISTINCT_ROWS: usize = 8;
+
+/// What [`Stitcher::push`] did with a frame.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StitchStep {
+    /// The content scrolled by this many pixels; that many new rows were added.
+    Scrolled(u32),
+    /// The frame matches the previous one: the end of the content was reached.
+    Unchanged,
+    /// No overlap with the previous frame was found, so nothing was added. The
+    /// scroll step was probably larger than the visible content.
+    NoOverlap,
+}
+
+/// Where a new frame lines up with the previous one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Alignment {
+    /// Rows fixed at the bottom of both frames
+    bottom: u32,
+    /// Scroll distance in pixels, 0 when nothing moved
+    offset: u32,
+}
+
+/// Per-row fingerprint plus whether the row has any detail (a row of one flat color
+/// matches everywhere, so it can't vouch for an alignment).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct RowSig {
+    hash: u64,
+    distinct: bool,
+}
+
+fn row_signatures(img: &image::RgbaImage) -> Vec<RowSig> {
+    let stride = img.width() as usize * 4;
+    img.as_raw()
+        .chunks_exact(stride.max(4))
+        .map(|row| {
+            let mut hasher = DefaultHasher::new();
+            row.hash(&mut hasher);
+            RowSig {
+                hash: hasher.finish(),
+                distinct: row.chunks_exact(4).any(|px| px != &row[..4]),
+            }
+        })
+        .collect()
+}
+
+/// Finds how `next` continues `prev`: the fixed rows at either edge and the scroll
+/// distance. Returns `None` if no offset lines the moving parts up.
+fn align(prev: &[RowSig], next: &[RowSig]) -> Option<Alignment> {
+    let height = prev.len().min(next.len());
+    let max_fixed = (height as f32 * MAX_FIXED_FRACTION) as usize;
+
+    if prev == next {
+        return Some(Alignment {
+            bottom: 0,
+            offset: 0,
+        });
+    }
+    let top = prev
+        .iter()
+        .zip(next)
+        .take(max_fixed)
+        .take_while(|(a, b)| a == b)
+        .count();
+    let bottom = prev
+        .iter()
+        .rev()
+        .zip(next.iter().rev())
+        .take(max_fixed)
+        .take_while(|(a, b)| a == b)
+        .count();
+
+    let prev = &prev[top..height - bottom];
+    let next = &next[top..height - bottom];
+    let moving = prev.len();
+
+    // The smallest offset that lines up wins: it has the largest overlap, so it is
+    // the least likely to be a coincidence in repetitive content. Offset 0 means only
+    // something small changed in place (a blinking caret, a hover effect).
+    (0..moving).find_map(|offset| {
+        let overlap = moving - offset;
+        let (mut distinct, mut matched) = (0, 0);
+        for (a, b) in prev[offset..].iter().zip(&next[..overlap]) {
+            if a.distinct || b.distinct {
+                distinct += 1;
+                if a == b {
+                    matched += 1;
+                }
+            }
+        }
+        (distinct >= MIN_DISTINCT_ROWS && matched as f32 >= distinct as f32 * MIN_MATCH_RATIO)
+            .then_some(Alignment {
+                bottom: bottom as u32,
+                offset: offset as u32,
+            })
+    })
+}
+
+/// Builds the tall image one frame at a time. All frames must be the same size.
+pub struct Stitcher {
+    width: u32,
+    /// Stitched rows so far, RGBA. Always ends with the whole latest frame's bottom.
+    rows: Vec<u8>,
+    frame_size: (u32, u32),
+    last: Vec<RowSig>,
+    frames: u32,
+}
+
+impl Stitcher {
+    pub fn new(first: image::RgbaImage) -> Self {
+        Self {
+            width: first.width(),
+            frame_size: first.dimensions(),
+            last: row_signatures(&first),
+            rows: first.into_raw(),
+            frames: 1,
+        }
+    }
+
+    /// Adds the next frame, appending whatever scrolled into view.
+    pub fn push(&mut self, frame: image::RgbaImage) -> Result<StitchStep, String> {
+        if frame.dimensions() != self.frame_size {
+            return Err("Scrolling capture frames must all be the same size".to_string());
+        }
+
+        let sigs = row_signatures(&frame);
+        let Some(alignment) = align(&self.last, &sigs) else {
+            return Ok(StitchStep::NoOverlap);
+        };
+        if alignment.offset == 0 {
+            return Ok(StitchStep::Unchanged);
+        }
+
+        let stride = self.width as usize * 4;
+        let height = frame.height();
+        // Cut the fixed footer, add the rows that scrolled in, then put the footer back
+        self.rows
+            .truncate(self.rows.len() - alignment.bottom as usize * stride);
+        let new_from = (height - alignment.bottom - alignment.offset) as usize * stride;
+        self.rows.extend_from_slice(&frame.as_raw()[new_from..]);
+
+        self.last = sigs;
+        self.frames += 1;
+        Ok(StitchStep::Scrolled(alignment.offset))
+    }
+
+    pub fn frames(&self) -> u32 {
+        self.frames
+    }
+
+    pub fn height(&self) -> u32 {
+        (self.rows.len() / (self.width as usize * 4).max(1)) as u32
+    }
+
+    pub fn finish(self) -> Result<image::RgbaImage, String> {
+        let height = self.height();
+        image::RgbaImage::from_raw(self.width, height, self.rows)
+            .ok_or_else(|| "Failed to assemble the scrolling capture".to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{Rgba, RgbaImage};
+
+    const WIDTH: u32 = 64;
+
+    /// Tall page with a different pattern on every row and blank bands, like the gaps
+    /// between paragraphs, that can't anchor an alignment on their own.
+    fn page(height: u32) -> RgbaImage {
+        RgbaImage::from_fn(WIDTH, height, |x, y| {
+            if y % 50 < 10 {
+                Rgba([255, 255, 255, 255])
+            } else {
+                Rgba([(x * 7 + y * 13) as u8, (y * 31) as u8, (x * y) as u8, 255])
+            }
+        })
+    }
+
+    /// The view of `page` scrolled down by `scroll`, under a flat `header` and over a
+    /// patterned `footer` that both stay put.
+    fn view(page: &RgbaImage, scroll: u32, height: u32, header: u32, footer: u32) -> RgbaImage {
+        RgbaImage::from_fn(WIDTH, height, |x, y| {
+            if y < header {
+                Rgba([20, 40, 200, 255])
+            } else if y >= height - footer {
+                Rgba([200, (x % 2) as u8 * 90, 20, 255])
+            } else {
+                *page.get_pixel(x, scroll + y - header)
+            }
+        })
+    }
+
+    fn rows(img: &RgbaImage, from: u32, to: u32) -> &[u8] {
+        let stride = img.width() as usize * 4;
+        &img.as_raw()[from as usize * stride..to as usize * stride]
+    }
+
+    #[test]
+    fn stitches_between_sticky_header_and_footer() {
+        let (height, header, footer) = (120, 16, 12);
+        let content = height - header - footer;
+        let page = page(400);
+        let mut stitcher = Stitcher::new(view(&page, 0, height, header, footer));
+
+        let mut scroll = 0;
+        for step in [30, 55, 20] {
+            scroll += step;
+            let step_result = stitcher.push(view(&page, scroll, height, header, footer));
+            assert_eq!(step_result, Ok(StitchStep::Scrolled(step)));
+        }
+        assert_eq!(stitcher.frames(), 4);
+
+        let img = stitcher.finish().unwrap();
+        assert_eq!(img.dimensions(), (WIDTH, height + scroll));
+        let first = view(&page, 0, height, header, footer);
+        let last = view(&page, scroll, height, header, footer);
+        // Header once at the top, the page without gaps or repeats, footer once at the end
+        assert_eq!(rows(&img, 0, header), rows(&first, 0, header));
+        assert_eq!(
+            rows(&img, header, header + content + scroll),
+            rows(&page, 0, content + scroll)
+        );
+        assert_eq!(
+            rows(&img, header + content + scroll, img.height()),
+            rows(&last, height - footer, height)
+        );
+    }
+
+    #[test]
+    fn same_frame_is_unchanged() {
+        let page = page(400);
+        let frame = view(&page, 0, 400, 0, 0);
+        let mut stitcher = Stitcher::new(frame.clone());
+        assert_eq!(stitcher.push(frame.clone()), Ok(StitchStep::Unchanged));
+
+        // A caret blinking in place isn't a scroll either
+        let mut caret = frame.clone();
+        caret.put_pixel(5, 200, Rgba([0, 0, 0, 255]));
+        assert_eq!(stitcher.push(caret), Ok(StitchStep::Unchanged));
+
+        assert_eq!(stitcher.frames(), 1);
+        assert_eq!(stitcher.finish().unwrap(), frame);
+    }
+
+    #[test]
+    fn scroll_past_the_view_has_no_overlap() {
+        let page = page(400);
+        let frame = view(&page, 0, 100, 0, 0);
+        let mut stitcher = Stitcher::new(frame.clone());
+
+        assert_eq!(
+            stitcher.push(view(&page, 150, 100, 0, 0)),
+            Ok(StitchStep::NoOverlap)
+        );
+        assert_eq!(stitcher.height(), 100);
+        assert_eq!(stitcher.finish().unwrap(), frame);
+    }
+
+    #[test]
+    fn frame_that_does_not_align_is_rejected() {
+        let page = page(200);
+        let mut stitcher = Stitcher::new(view(&page, 0, 100, 0, 0));
+
+        // A flat frame matches every blank row, which proves nothing
+        let blank = RgbaImage::from_pixel(WIDTH, 100, Rgba([255, 255, 255, 255]));
+        assert_eq!(stitcher.push(blank), Ok(StitchStep::NoOverlap));
+
+        assert!(stitcher.push(view(&page, 10, 90, 0, 0)).is_err());
+        assert_eq!(stitcher.height(), 100);
+    }
+
+    #[test]
+    fn align_finds_offset_and_footer() {
+        let page = page(300);
+        let prev = row_signatures(&view(&page, 0, 100, 8, 6));
+        let next = row_signatures(&view(&page, 24, 100, 8, 6));
+        assert_eq!(
+            align(&prev, &next),
+            Some(Alignment {
+                bottom: 6,
+                offset: 24
+            })
+        );
+        assert_eq!(
+            align(&prev, &prev),
+            Some(Alignment {
+                bottom: 0,
+                offset: 0
+            })
+        );
+    }
+}
@@ -0,0 +1,512 @@
+//! Clipboard history: a background watcher polls the system clipboard (arboard on
+//! every OS) and records text and images into a bounded, deduplicated list.
+//! Content marked as concealed by password managers is never recorded, and nothing
+//! is while that can't be checked (Linux without `wl-paste` or `xclip`).
+
+use serde::Serialize;
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// Unpinned entries kept; the oldest are dropped first.
+pub const DEFAULT_CAPACITY: usize = 50;
+/// Images are large, so only the newest few unpinned ones are kept.
+const MAX_IMAGES: usize = 10;
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+/// Longest side of the preview sent to the frontend, in pixels
+const PREVIEW_SIZE: u32 = 160;
+
+pub enum ClipboardContent {
+    Text(String),
+    Image(image::RgbaImage),
+}
+
+pub struct ClipboardEntry {
+    pub id: u64,
+    pub content: ClipboardContent,
+    pub pinned: bool,
+    /// Unix timestamp (seconds) of the last time this content was copied
+    pub copied_at: u64,
+    /// Hash of the text or pixels, used for deduplication
+    fingerprint: u64,
+    preview: Option<String>,
+}
+
+/// What the frontend sees: images are reduced to a PNG data URL preview.
+#[derive(Serialize, Debug, Clone)]
+pub struct ClipboardEntryView {
+    pub id: u64,
+    /// "text" or "image"
+    pub kind: &'static str,
+    pub text: Option<String>,
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+    pub preview: Option<String>,
+    pub pinned: bool,
+    pub copied_at: u64,
+}
+
+pub struct ClipboardHistory {
+    /// Newest first
+    entries: Vec<ClipboardEntry>,
+    next_id: u64,
+    capacity: usize,
+}
+
+impl ClipboardHistory {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            entries: Vec::new(),
+            next_id: 1,
+            capacity,
+        }
+    }
+
+    /// Records `content`. Copying something already in the history moves it to the
+    /// top (keeping its pin) instead of adding a duplicate.
+    pub fn push(&mut self, content: ClipboardContent) {
+        let fingerprint = fingerprint(&content);
+        let now = unix_now();
+
+        if let Some(pos) = self
+            .entries
+            .iter()
+            .position(|e| e.fingerprint == fingerprint)
+        {
+            let mut entry = self.entries.remove(pos);
+            entry.copied_at = now;
+            self.entries.insert(0, entry);
+            return;
+        }
+
+        let preview = match &content {
+            ClipboardContent::Image(img) => preview_data_url(img),
+            ClipboardContent::Text(_) => None,
+        };
+        self.entries.insert(
+            0,
+            ClipboardEntry {
+                id: self.next_id,
+                content,
+                pinned: false,
+                copied_at: now,
+                fingerprint,
+                preview,
+            },
+        );
+        self.next_id += 1;
+        self.trim();
+    }
+
+    /// Drops the oldest unpinned entries beyond the capacity and image limits.
+    fn trim(&mut self) {
+        let mut unpinned = 0;
+        let mut images = 0;
+        let capacity = self.capacity;
+        self.entries.retain(|e| {
+            if e.pinned {
+                return true;
+            }
+            unpinned += 1;
+            if matches!(e.content, ClipboardContent::Image(_)) {
+                images += 1;
+                if images > MAX_IMAGES {
+                    return false;
+                }
+            }
+            unpinned <= capacity
+        });
+    }
+
+    pub fn list(&self) -> Vec<ClipboardEntryView> {
+        self.entries
+            .iter()
+            .map(|e| match &e.content {
+                ClipboardContent::Text(text) => ClipboardEntryView {
+                    id: e.id,
+                    kind: "text",
+                    text: Some(text.clone()),
+                    width: None,
+                    height: None,
+                    preview: None,
+                    pinned: e.pinned,
+                    copied_at: e.copied_at,
+                },
+                ClipboardContent::Image(img) => ClipboardEntryView {
+                    id: e.id,
+                    kind: "image",
+                    text: None,
+                    width: Some(img.width()),
+                    height: Some(img.height()),
+                    preview: e.preview.clone(),
+                    pinned: e.pinned,
+                    copied_at: e.copied_at,
+                },
+            })
+            .collect()
+    }
+
+    pub fn get(&self, id: u64) -> Option<&ClipboardEntry> {
+        self.entries.iter().find(|e| e.id == id)
+    }
+
+    pub fn set_pinned(&mut self, id: u64, pinned: bool) -> Result<(), String> {
+        let entry = self
+            .entries
+            .iter_mut()
+            .find(|e| e.id == id)
+            .ok_or_else(|| format!("Clipboard history item {} not found", id))?;
+        entry.pinned = pinned;
+        self.trim();
+        Ok(())
+    }
+
+    pub fn clear(&mut self, keep_pinned: bool) {
+        self.entries.retain(|e| keep_pinned && e.pinned);
+    }
+}
+
+/// Polls the clipboard on its own thread and records every change into `history`.
+/// Where the OS counts clipboard changes the content is only read after a change.
+pub fn spawn_watcher(history: Arc<Mutex<ClipboardHistory>>) {
+    std::thread::spawn(move || {
+        let mut clipboard: Option<arboard::Clipboard> = None;
+        let mut last_seen: Option<u64> = None;
+        let mut last_count: Option<u64> = None;
+        let mut warned = false;
+
+        loop {
+            std::thread::sleep(POLL_INTERVAL);
+
+            let count = change_count();
+            if count.is_some() && count == last_count {
+                continue;
+            }
+
+            if clipboard.is_none() {
+                clipboard = arboard::Clipboard::new().ok();
+            }
+            let Some(cb) = clipboard.as_mut() else {
+                continue;
+            };
+
+            let content = match cb.get_text() {
+                Ok(text) if !text.trim().is_empty() => Some(ClipboardContent::Text(text)),
+                _ => cb.get_image().ok().and_then(|data| {
+                    image::RgbaImage::from_raw(
+                        data.width as u32,
+                        data.height as u32,
+                        data.bytes.into_owned(),
+                    )
+                    .map(ClipboardContent::Image)
+                }),
+            };
+            // Unreadable or unsupported content is retried on the next poll
+            let Some(content) = content else {
+                continue;
+            };
+            last_count = count;
+
+            let current = fingerprint(&content);
+            if last_seen == Some(current) {
+                continue;
+            }
+            last_seen = Some(current);
+
+            // Fails closed: content that can't be checked might be a password
+            match is_concealed() {
+                Ok(false) => {}
+                Ok(true) => continue,
+                Err(e) => {
+                    if !warned {
+                        eprintln!("Clipboard history is not recording: {}", e);
+                        warned = true;
+                    }
+                    continue;
+                }
+            }
+            if let Ok(mut history) = history.lock() {
+                history.push(content);
+            }
+        }
+    });
+}
+
+/// Counter the OS bumps on every clipboard change (NSPasteboard `changeCount`,
+/// `GetClipboardSequenceNumber`); `None` where there is none and the content has to
+/// be read and hashed on every poll.
+#[cfg(target_os = "macos")]
+fn change_count() -> Option<u64> {
+    use std::ffi::{c_char, c_void};
+
+    #[link(name = "AppKit", kind = "framework")]
+    extern "C" {}
+    #[link(name = "objc")]
+    extern "C" {
+        fn objc_getClass(name: *const c_char) -> *mut c_void;
+        fn sel_registerName(name: *const c_char) -> *mut c_void;
+        fn objc_msgSend();
+    }
+
+    unsafe {
+        // objc_msgSend has to be called through the signature of the method it dispatches to
+        type Msg = unsafe extern "C" fn();
+        let send_id = std::mem::transmute::<
+            Msg,
+            unsafe extern "C" fn(*mut c_void, *mut c_void) -> *mut c_void,
+        >(objc_msgSend);
+        let send_isize = std::mem::transmute::<
+            Msg,
+            unsafe extern "C" fn(*mut c_void, *mut c_void) -> isize,
+        >(objc_msgSend);
+
+        let class = objc_getClass(c"NSPasteboard".as_ptr());
+        if class.is_null() {
+            return None;
+        }
+        let pasteboard = send_id(class, sel_registerName(c"generalPasteboard".as_ptr()));
+        if pasteboard.is_null() {
+            return None;
+        }
+        Some(send_isize(pasteboard, sel_registerName(c"changeCount".as_ptr())) as u64)
+    }
+}
+
+#[cfg(target_os = "windows")]
+fn change_count() -> Option<u64> {
+    clipboard_win::seq_num().map(|n| n.get() as u64)
+}
+
+#[cfg(not(any(target_os = "macos", target_os = "windows")))]
+fn change_count() -> Option<u64> {
+    None
+}
+
+/// Whether the current clipboard content carries a password-manager marker
+/// (nspasteboard.org types on macOS, clipboard exclusion formats on Windows,
+/// the KDE password hint on Linux). An error when the types can't be listed.
+#[cfg(target_os = "macos")]
+fn is_concealed() -> Result<bool, String> {
+    use std::process::Command;
+
+    const CONCEALED_TYPES: [&str; 3] = [
+        "org.nspasteboard.ConcealedType",
+        "org.nspasteboard.TransientType",
+        "org.nspasteboard.AutoGeneratedType",
+    ];
+    let script = "ObjC.import('AppKit'); \
+                  ObjC.deepUnwrap($.NSPasteboard.generalPasteboard.types).join('\\n')";
+
+    Command::new("osascript")
+        .args(["-l", "JavaScript", "-e", script])
+        .output()
+        .map(|out| {
+            String::from_utf8_lossy(&out.stdout)
+                .lines()
+                .any(|t| CONCEALED_TYPES.contains(&t.trim()))
+        })
+        .map_err(|e| format!("Failed to read pasteboard types: {}", e))
+}
+
+#[cfg(target_os = "windows")]
+fn is_concealed() -> Result<bool, String> {
+    Ok([
+        "ExcludeClipboardContentFromMonitorProcessing",
+        "Clipboard Viewer Ignore",
+    ]
+    .iter()
+    .filter_map(|name| clipboard_win::register_format(name))
+    .any(|format| clipboard_win::is_format_avail(format.get())))
+}
+
+#[cfg(target_os = "linux")]
+fn is_concealed() -> Result<bool, String> {
+    use std::process::Command;
+
+    let (tool, output) = if std::env::var_os("WAYLAND_DISPLAY").is_some() {
+        let output = Command::new("wl-paste").arg("--list-types").output();
+        ("wl-paste (wl-clipboard)", output)
+    } else {
+        let output = Command::new("xclip")
+            .args(["-selection", "clipboard", "-t", "TARGETS", "-o"])
+            .output();
+        ("xclip", output)
+    };
+    let output = output.map_err(|e| {
+        format!(
+            "Failed to run {} to check for concealed passwords: {}",
+            tool, e
+        )
+    })?;
+    if !output.status.success() {
+        return Err(format!(
+            "{} couldn't list the clipboard types: {}",
+            tool,
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .any(|t| t.trim() == "x-kde-passwordManagerHint"))
+}
+
+#[cfg(not(any(target_os = "macos", target_os = "windows", target_os = "linux")))]
+fn is_concealed() -> Result<bool, String> {
+    Ok(false)
+}
+
+fn fingerprint(content: &ClipboardContent) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    match content {
+        ClipboardContent::Text(text) => {
+            0u8.hash(&mut hasher);
+            text.hash(&mut hasher);
+        }
+        ClipboardContent::Image(img) => {
+            1u8.hash(&mut hasher);
+            img.dimensions().hash(&mut hasher);
+            img.as_raw().hash(&mut hasher);
+        }
+    }
+    hasher.finish()
+}
+
+fn preview_data_url(img: &image::RgbaImage) -> Option<String> {
+    use base64::{engine::general_purpose, Engine as _};
+
+    let longest = img.width().max(img.height()).max(1);
+    let scale = (PREVIEW_SIZE as f32 / longest as f32).min(1.0);
+    let thumb = image::imageops::thumbnail(
+        img,
+        ((img.width() as f32 * scale) as u32).max(1),
+        ((img.height() as f32 * scale) as u32).max(1),
+    );
+
+    let mut bytes: Vec<u8> = Vec::new();
+    image::DynamicImage::ImageRgba8(thumb)
+        .write_to(
+            &mut std::io::Cursor::new(&mut bytes),
+            image::ImageOutputFormat::Png,
+        )
+        .ok()?;
+    Some(format!(
+        "data:image/png;base64,{}",
+        general_purpose::STANDARD.encode(bytes)
+    ))
+}
+
+fn unix_now() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn text(s: &str) -> ClipboardContent {
+        ClipboardContent::Text(s.to_string())
+    }
+
+    fn image(shade: u8) -> ClipboardContent {
+        ClipboardContent::Image(image::RgbaImage::from_pixel(
+            4,
+            2,
+            image::Rgba([shade, 0, 0, 255]),
+        ))
+    }
+
+    fn texts(history: &ClipboardHistory) -> Vec<String> {
+        history
+            .list()
+            .into_iter()
+            .map(|e| e.text.unwrap_or_else(|| e.kind.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn copying_again_moves_to_the_top() {
+        let mut history = ClipboardHistory::new(10);
+        history.push(text("a"));
+        history.push(text("b"));
+        history.push(image(1));
+        history.push(text("a"));
+        history.push(image(1));
+        assert_eq!(texts(&history), ["image", "a", "b"]);
+        // The same entry, not a new one
+        let ids: Vec<u64> = history.list().iter().map(|e| e.id).collect();
+        assert_eq!(ids, [3, 1, 2]);
+
+        let view = &history.list()[0];
+        assert_eq!((view.width, view.height), (Some(4), Some(2)));
+        assert!(view
+            .preview
+            .as_deref()
+            .is_some_and(|p| p.starts_with("data:image/png;base64,")));
+    }
+
+    #[test]
+    fn trims_the_oldest_unpinned() {
+        let mut history = ClipboardHistory::new(3);
+        for s in ["a", "b", "c", "d"] {
+            history.push(text(s));
+        }
+        assert_eq!(texts(&history), ["d", "c", "b"]);
+
+        // Pinned entries don't count against the capacity
+        let b = history.list()[2].id;
+        history.set_pinned(b, true).unwrap();
+        history.push(text("e"));
+        history.push(text("f"));
+        assert_eq!(texts(&history), ["f", "e", "d", "b"]);
+        assert!(history.get(b).is_some_and(|e| e.pinned));
+
+        // Unpinning trims it again
+        history.set_pinned(b, false).unwrap();
+        assert_eq!(texts(&history), ["f", "e", "d"]);
+        assert!(history.set_pinned(99, true).is_err());
+    }
+
+    #[test]
+    fn keeps_pins_when_copied_again() {
+        let mut history = ClipboardHistory::new(3);
+        history.push(text("a"));
+        history.push(text("b"));
+        let a = history.list()[1].id;
+        history.set_pinned(a, true).unwrap();
+        history.push(text("a"));
+        assert_eq!(texts(&history), ["a", "b"]);
+        assert!(history.list()[0].pinned);
+    }
+
+    #[test]
+    fn keeps_only_the_newest_images() {
+        let mut history = ClipboardHistory::new(DEFAULT_CAPACITY);
+        for shade in 0..=MAX_IMAGES as u8 {
+            history.push(image(shade));
+        }
+        history.push(text("a"));
+        let views = history.list();
+        assert_eq!(views.len(), MAX_IMAGES + 1);
+        // The first image went
+        assert_eq!(views.last().unwrap().id, 2);
+    }
+
+    #[test]
+    fn clear_can_keep_pinned() {
+        let mut history = ClipboardHistory::new(10);
+        for s in ["a", "b", "c"] {
+            history.push(text(s));
+        }
+        let b = history.list()[1].id;
+        history.set_pinned(b, true).unwrap();
+
+        history.clear(true);
+        assert_eq!(texts(&history), ["b"]);
+        history.clear(false);
+        assert!(history.list().is_empty());
+    }
+}
@@ -0,0 +1,178 @@
+//! QR code and 1D barcode decoding for captured screen regions (pure Rust, via rxing).
+
+use crate::ocr::BoundingBox;
+use serde::Serialize;
+
+#[derive(Serialize, Debug, Clone)]
+pub struct DecodedCode {
+    /// Symbology as reported by the decoder, e.g. "qrcode", "ean 13", "code 128"
+    pub format: String,
+    pub text: String,
+    pub is_url: bool,
+    /// Where the code was found, in pixels of the captured region
+    pub bbox: Option<BoundingBox>,
+}
+
+/// Decodes every code in `img`. Codes shown small on screen often fail at 1:1, so
+/// a 2x upscaled copy is tried when nothing is found.
+pub fn decode(img: &image::RgbaImage) -> Vec<DecodedCode> {
+    decode_with(img, decode_luma)
+}
+
+/// [`decode`] with the detector passed in; it gets the grayscale image and the factor
+/// that maps its coordinates back onto `img`.
+fn decode_with(
+    img: &image::RgbaImage,
+    detect: impl Fn(&image::GrayImage, f32) -> Vec<DecodedCode>,
+) -> Vec<DecodedCode> {
+    let codes = detect(
+        &image::DynamicImage::ImageRgba8(img.clone()).to_luma8(),
+        1.0,
+    );
+    if !codes.is_empty() {
+        return codes;
+    }
+
+    let upscaled = image::imageops::resize(
+        img,
+        img.width() * 2,
+        img.height() * 2,
+        image::imageops::FilterType::Nearest,
+    );
+    detect(&image::DynamicImage::ImageRgba8(upscaled).to_luma8(), 0.5)
+}
+
+fn decode_luma(luma: &image::GrayImage, scale: f32) -> Vec<DecodedCode> {
+    let results =
+        rxing::helpers::detect_multiple_in_luma(luma.as_raw().clone(), luma.width(), luma.height())
+            .unwrap_or_default();
+
+    let mut codes: Vec<DecodedCode> = Vec::new();
+    for result in results {
+        let text = result.getText().to_string();
+        if text.is_empty() || codes.iter().any(|c| c.text == text) {
+            continue;
+        }
+
+        let points = result.getPoints();
+        let bbox = if points.is_empty() {
+            None
+        } else {
+            let min_x = points.iter().map(|p| p.x).fold(f32::MAX, f32::min);
+            let min_y = points.iter().map(|p| p.y).fold(f32::MAX, f32::min);
+            let max_x = points.iter().map(|p| p.x).fold(f32::MIN, f32::max);
+            let max_y = points.iter().map(|p| p.y).fold(f32::MIN, f32::max);
+            Some(BoundingBox::new(
+                min_x * scale,
+                min_y * scale,
+                (max_x - min_x) * scale,
+                (max_y - min_y) * scale,
+            ))
+        };
+
+        codes.push(DecodedCode {
+            format: result.getBarcodeFormat().to_string(),
+            is_url: is_url(&text),
+            text,
+            bbox,
+        });
+    }
+    codes
+}
+
+fn is_url(text: &str) -> bool {
+    let lower = text.trim().to_lowercase();
+    (lower.starts_with("http://") || lower.starts_with("https://"))
+        && !lower.contains(char::is_whitespace)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A URL QR code, an EAN-13 and a Code 128 on one white canvas
+    const MULTI: &str = concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/tests/fixtures/codes_multi.png"
+    );
+
+    fn found(text: &str) -> DecodedCode {
+        DecodedCode {
+            format: "qrcode".to_string(),
+            text: text.to_string(),
+            is_url: is_url(text),
+            bbox: None,
+        }
+    }
+
+    #[test]
+    fn decodes_every_code_in_the_image() {
+        let img = image::open(MULTI).unwrap().to_rgba8();
+        let mut codes = decode(&img);
+        codes.sort_by(|a, b| a.text.cmp(&b.text));
+
+        let found: Vec<(&str, &str, bool)> = codes
+            .iter()
+            .map(|c| (c.format.as_str(), c.text.as_str(), c.is_url))
+            .collect();
+        assert_eq!(
+            found,
+            [
+                ("ean 13", "4006381333931", false),
+                ("code 128", "TG-2024-42", false),
+                ("qrcode", "https://example.com/taskgoblin", true),
+            ]
+        );
+        for code in &codes {
+            let bbox = code.bbox.unwrap();
+            assert!(bbox.x >= 0.0 && bbox.right() <= img.width() as f32);
+            assert!(bbox.y >= 0.0 && bbox.bottom() <= img.height() as f32);
+        }
+    }
+
+    #[test]
+    fn blank_image_has_no_codes() {
+        let img = image::RgbaImage::from_pixel(64, 64, image::Rgba([255, 255, 255, 255]));
+        assert!(decode(&img).is_empty());
+    }
+
+    #[test]
+    fn retries_at_twice_the_size() {
+        let img = image::RgbaImage::new(30, 20);
+        let calls = std::cell::RefCell::new(Vec::new());
+        let codes = decode_with(&img, |luma, scale| {
+            calls.borrow_mut().push((luma.dimensions(), scale));
+            // Only readable once upscaled
+            if luma.width() < 60 {
+                Vec::new()
+            } else {
+                vec![found("WIFI:S:office;;")]
+            }
+        });
+        assert_eq!(calls.into_inner(), [((30, 20), 1.0), ((60, 40), 0.5)]);
+        assert_eq!(codes.len(), 1);
+        assert!(!codes[0].is_url);
+    }
+
+    #[test]
+    fn skips_the_retry_when_the_original_decodes() {
+        let img = image::RgbaImage::new(30, 20);
+        let calls = std::cell::Cell::new(0);
+        let codes = decode_with(&img, |_, _| {
+            calls.set(calls.get() + 1);
+            vec![found("https://example.com")]
+        });
+        assert_eq!(calls.get(), 1);
+        assert!(codes[0].is_url);
+    }
+
+    #[test]
+    fn is_url_needs_an_http_scheme_and_no_spaces() {
+        assert!(is_url("https://example.com/a?b=c"));
+        assert!(is_url("  HTTP://EXAMPLE.COM  "));
+        assert!(!is_url("example.com"));
+        assert!(!is_url("ftp://example.com"));
+        assert!(!is_url("https://example.com and more"));
+        assert!(!is_url("4006381333931"));
+    }
+}
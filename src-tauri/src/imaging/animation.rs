@@ -0,0 +1,334 @@
+//! Multi-frame images: animated GIF, WebP and APNG are decoded into full-canvas
+//! frames, transformed frame by frame and re-encoded as any of the three.
+
+use super::metadata::{webp_chunk, webp_chunks};
+use image::codecs::gif::{GifEncoder, Repeat};
+use image::{AnimationDecoder, Delay, DynamicImage, Frame, ImageFormat};
+use serde::Serialize;
+use std::io::Cursor;
+use std::path::Path;
+
+/// Output formats that can hold an animation.
+pub fn can_encode(format: &str) -> bool {
+    matches!(format, "gif" | "webp" | "png")
+}
+
+/// Every frame of an animated GIF, WebP or APNG, composited onto the full canvas;
+/// `None` for still images and single-frame animations.
+pub fn decode(data: &[u8], format: &str) -> Result<Option<Vec<Frame>>, String> {
+    let err = |e: image::ImageError| format!("Failed to decode animation: {}", e);
+    let frames = match format {
+        "gif" => image::codecs::gif::GifDecoder::new(Cursor::new(data))
+            .map_err(err)?
+            .into_frames(),
+        "webp" => {
+            let decoder = image::codecs::webp::WebPDecoder::new(Cursor::new(data)).map_err(err)?;
+            if !decoder.has_animation() {
+                return Ok(None);
+            }
+            decoder.into_frames()
+        }
+        "png" => {
+            let decoder = image::codecs::png::PngDecoder::new(Cursor::new(data)).map_err(err)?;
+            if !decoder.is_apng() {
+                return Ok(None);
+            }
+            decoder.apng().into_frames()
+        }
+        _ => return Ok(None),
+    };
+    let frames = frames.collect_frames().map_err(err)?;
+    Ok((frames.len() > 1).then_some(frames))
+}
+
+fn delay_ms(delay: Delay) -> u32 {
+    let (numer, denom) = delay.numer_denom_ms();
+    (numer as f64 / denom.max(1) as f64).round() as u32
+}
+
+/// Runs `f` over every frame, keeping the timings.
+pub fn map_frames(
+    frames: Vec<Frame>,
+    mut f: impl FnMut(DynamicImage) -> Result<DynamicImage, String>,
+) -> Result<Vec<Frame>, String> {
+    frames
+        .into_iter()
+        .map(|frame| {
+            let delay = frame.delay();
+            let img = f(DynamicImage::ImageRgba8(frame.into_buffer()))?;
+            Ok(Frame::from_parts(img.into_rgba8(), 0, 0, delay))
+        })
+        .collect()
+}
+
+/// Encodes same-sized `frames` as an animated GIF, WebP or APNG that loops forever.
+/// `quality` applies to WebP, `optimize` picks the best PNG compression.
+pub fn encode(
+    frames: Vec<Frame>,
+    format: &str,
+    quality: u8,
+    optimize: bool,
+) -> Result<Vec<u8>, String> {
+    match format {
+        "gif" => {
+            let mut bytes = Vec::new();
+            {
+                let mut encoder = GifEncoder::new_with_speed(&mut bytes, 10);
+                encoder
+                    .set_repeat(Repeat::Infinite)
+                    .and_then(|_| encoder.encode_frames(frames))
+                    .map_err(|e| format!("Failed to encode GIF: {}", e))?;
+            }
+            Ok(bytes)
+        }
+        "webp" => encode_webp(&frames, quality),
+        "png" => encode_apng(&frames, optimize),
+        _ => Err(format!("Animations can't be written as {}", format)),
+    }
+}
+
+fn first_frame(frames: &[Frame]) -> Result<&Frame, String> {
+    frames
+        .first()
+        .ok_or_else(|| "An animation needs at least one frame".to_string())
+}
+
+fn encode_apng(frames: &[Frame], optimize: bool) -> Result<Vec<u8>, String> {
+    let err = |e: png::EncodingError| format!("Failed to encode APNG: {}", e);
+    let (width, height) = first_frame(frames)?.buffer().dimensions();
+    let mut bytes = Vec::new();
+    {
+        let mut encoder = png::Encoder::new(&mut bytes, width, height);
+        encoder.set_color(png::ColorType::Rgba);
+        encoder.set_depth(png::BitDepth::Eight);
+        if optimize {
+            encoder.set_compression(png::Compression::Best);
+        }
+        // 0 plays is an endless loop
+        encoder.set_animated(frames.len() as u32, 0).map_err(err)?;
+        let mut writer = encoder.write_header().map_err(err)?;
+        for frame in frames {
+            let delay = delay_ms(frame.delay()).min(u16::MAX as u32) as u16;
+            writer.set_frame_delay(delay, 1000).map_err(err)?;
+            writer.write_image_data(frame.buffer()).map_err(err)?;
+        }
+        writer.finish().map_err(err)?;
+    }
+    Ok(bytes)
+}
+
+/// Extended-format WebP with one full-canvas `ANMF` chunk per frame, each frame
+/// compressed on its own like a still image.
+fn encode_webp(frames: &[Frame], quality: u8) -> Result<Vec<u8>, String> {
+    let (width, height) = first_frame(frames)?.buffer().dimensions();
+    let canvas = [
+        &(width - 1).to_le_bytes()[..3],
+        &(height - 1).to_le_bytes()[..3],
+    ]
+    .concat();
+
+    let mut alpha = false;
+    let mut anmf = Vec::new();
+    for frame in frames {
+        alpha |= frame.buffer().pixels().any(|p| p[3] < 255);
+        let still = DynamicImage::ImageRgba8(frame.buffer().clone());
+        let still = super::encode_lossy(&still, "webp", quality)?;
+
+        // At 0,0 and the canvas size
+        let mut payload = vec![0; 6];
+        payload.extend_from_slice(&canvas);
+        payload.extend_from_slice(&delay_ms(frame.delay()).min(0xFF_FFFF).to_le_bytes()[..3]);
+        // Replace the previous frame instead of blending over it; no disposal
+        payload.push(0x02);
+        for (kind, range) in webp_chunks(&still) {
+            if matches!(&kind, b"ALPH" | b"VP8 " | b"VP8L") {
+                payload.extend(webp_chunk(&kind, &still[range]));
+            }
+        }
+        anmf.extend(webp_chunk(b"ANMF", &payload));
+    }
+
+    let mut header = vec![if alpha { 0x12 } else { 0x02 }, 0, 0, 0];
+    header.extend_from_slice(&canvas);
+    // Transparent background, endless loop
+    let anim = [0u8; 6];
+
+    let mut chunks = webp_chunk(b"VP8X", &header);
+    chunks.extend(webp_chunk(b"ANIM", &anim));
+    chunks.extend(anmf);
+    let mut out = b"RIFF".to_vec();
+    out.extend_from_slice(&(4 + chunks.len() as u32).to_le_bytes());
+    out.extend_from_slice(b"WEBP");
+    out.extend(chunks);
+    Ok(out)
+}
+
+/// A frame written by [`extract`].
+#[derive(Serialize, Debug, Clone)]
+pub struct ExtractedFrame {
+    pub path: String,
+    pub delay_ms: u32,
+}
+
+/// Writes every frame of the animation at `input` into `output_dir` as
+/// `<name>_001.png`, `<name>_002.png`, ..., replacing files of the same name.
+pub fn extract(input: &Path, output_dir: &Path) -> Result<Vec<ExtractedFrame>, String> {
+    let data = std::fs::read(input).map_err(|e| format!("Failed to read image: {}", e))?;
+    let format = super::detect_format(&data)
+        .ok_or_else(|| format!("Not a recognized image: {}", input.display()))?;
+    let frames = decode(&data, &format)?
+        .ok_or_else(|| format!("{} is not an animation", input.display()))?;
+
+    std::fs::create_dir_all(output_dir)
+        .map_err(|e| format!("Failed to create output folder: {}", e))?;
+    let stem = input
+        .file_stem()
+        .map_or("frame".into(), |s| s.to_string_lossy());
+    let digits = frames.len().to_string().len().max(3);
+
+    let mut extracted = Vec::with_capacity(frames.len());
+    for (index, frame) in frames.iter().enumerate() {
+        let path = output_dir.join(format!("{}_{:0digits$}.png", stem, index + 1));
+        let mut bytes = Vec::new();
+        frame
+            .buffer()
+            .write_to(&mut Cursor::new(&mut bytes), ImageFormat::Png)
+            .map_err(|e| format!("Failed to encode frame {}: {}", index + 1, e))?;
+        super::write_atomic(&path, &bytes)?;
+        extracted.push(ExtractedFrame {
+            path: path.to_string_lossy().to_string(),
+            delay_ms: delay_ms(frame.delay()),
+        });
+    }
+    Ok(extracted)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{Rgba, RgbaImage};
+
+    const DELAYS: [u32; 3] = [40, 100, 250];
+    const COLORS: [[u8; 3]; 4] = [[255, 0, 0], [0, 255, 0], [0, 0, 255], [255, 255, 0]];
+
+    /// Frame `i` is split in two flat colors, different from the frames around it.
+    fn color(i: usize, x: u32) -> [u8; 3] {
+        COLORS[(i + (x >= 16) as usize) % COLORS.len()]
+    }
+
+    fn frames() -> Vec<Frame> {
+        DELAYS
+            .iter()
+            .enumerate()
+            .map(|(i, &ms)| {
+                let img = RgbaImage::from_fn(32, 16, |x, _| {
+                    let [r, g, b] = color(i, x);
+                    Rgba([r, g, b, 255])
+                });
+                Frame::from_parts(img, 0, 0, Delay::from_numer_denom_ms(ms, 1))
+            })
+            .collect()
+    }
+
+    /// Same frame count and delays, and the middle frame's colors away from the
+    /// edge between them; lossy WebP may shift them a little.
+    fn assert_frames(frames: &[Frame]) {
+        let delays: Vec<u32> = frames.iter().map(|f| delay_ms(f.delay())).collect();
+        assert_eq!(delays, DELAYS);
+        let buffer = frames[1].buffer();
+        assert_eq!(buffer.dimensions(), (32, 16));
+        for x in [4, 27] {
+            let pixel = buffer.get_pixel(x, 8);
+            for (channel, expected) in color(1, x).into_iter().enumerate() {
+                let diff = (pixel[channel] as i32 - expected as i32).abs();
+                assert!(
+                    diff <= 8,
+                    "{:?} at x = {} isn't {:?}",
+                    pixel,
+                    x,
+                    color(1, x)
+                );
+            }
+            assert_eq!(pixel[3], 255);
+        }
+    }
+
+    /// Decodes `data` as `from` and encodes it as `to`, checking the frames on the way.
+    fn convert(data: &[u8], from: &str, to: &str) -> Vec<u8> {
+        let frames = decode(data, from).unwrap().unwrap();
+        assert_frames(&frames);
+        encode(frames, to, 90, false).unwrap()
+    }
+
+    #[test]
+    fn gif_to_webp() {
+        let gif = encode(frames(), "gif", 90, false).unwrap();
+        let webp = convert(&gif, "gif", "webp");
+        assert_eq!(super::super::detect_format(&webp).as_deref(), Some("webp"));
+        assert_frames(&decode(&webp, "webp").unwrap().unwrap());
+    }
+
+    #[test]
+    fn webp_to_apng() {
+        let webp = encode(frames(), "webp", 90, false).unwrap();
+        let apng = convert(&webp, "webp", "png");
+        assert_frames(&decode(&apng, "png").unwrap().unwrap());
+    }
+
+    #[test]
+    fn apng_to_gif() {
+        let apng = encode(frames(), "png", 90, true).unwrap();
+        let gif = convert(&apng, "png", "gif");
+        assert_frames(&decode(&gif, "gif").unwrap().unwrap());
+    }
+
+    #[test]
+    fn gif_to_webp_to_apng_to_gif() {
+        let gif = encode(frames(), "gif", 90, false).unwrap();
+        let webp = convert(&gif, "gif", "webp");
+        let apng = convert(&webp, "webp", "png");
+        let gif = convert(&apng, "png", "gif");
+        assert_frames(&decode(&gif, "gif").unwrap().unwrap());
+    }
+
+    #[test]
+    fn still_images_are_not_animations() {
+        let mut frames = frames();
+        frames.truncate(1);
+        let apng = encode(frames, "png", 90, false).unwrap();
+        assert!(decode(&apng, "png").unwrap().is_none());
+    }
+
+    #[test]
+    fn no_frames_is_an_error() {
+        assert!(encode(Vec::new(), "webp", 90, false).is_err());
+        assert!(encode(Vec::new(), "png", 90, false).is_err());
+    }
+
+    #[test]
+    fn extracts_numbered_frames() {
+        let dir = std::env::temp_dir().join(format!("taskgoblin-frames-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let input = dir.join("spin.gif");
+        std::fs::write(&input, encode(frames(), "gif", 90, false).unwrap()).unwrap();
+
+        let extracted = extract(&input, &dir.join("out")).unwrap();
+        let names: Vec<String> = extracted
+            .iter()
+            .map(|f| {
+                Path::new(&f.path)
+                    .file_name()
+                    .unwrap()
+                    .to_string_lossy()
+                    .to_string()
+            })
+            .collect();
+        assert_eq!(names, ["spin_001.png", "spin_002.png", "spin_003.png"]);
+        let delays: Vec<u32> = extracted.iter().map(|f| f.delay_ms).collect();
+        assert_eq!(delays, DELAYS);
+        let second = image::open(&extracted[1].path).unwrap().to_rgb8();
+        assert_eq!(second.get_pixel(4, 8).0, color(1, 4));
+        assert_eq!(second.get_pixel(27, 8).0, color(1, 27));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,387 @@
+//! Batch conversion: many inputs through [`super::process`] on a bounded pool of
+//! worker threads, with per-file progress and cancellation between files.
+
+use super::{ImageOptions, ProcessReport};
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+
+/// Decoding full-size images is memory hungry, so never run more at once than this.
+pub const MAX_WORKERS: usize = 8;
+
+/// Extensions picked up when a directory is given as input.
+const IMAGE_EXTENSIONS: &[&str] = &[
+    "png", "jpg", "jpeg", "webp", "gif", "bmp", "tif", "tiff", "ico", "heic", "heif",
+];
+
+pub struct BatchJob {
+    pub input: PathBuf,
+    pub output: PathBuf,
+}
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum FileStatus {
+    Done,
+    Failed,
+    /// Not started because the batch was cancelled
+    Skipped,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct BatchFileResult {
+    pub input: String,
+    pub output: String,
+    pub status: FileStatus,
+    pub error: Option<String>,
+    pub report: Option<ProcessReport>,
+}
+
+/// Emitted after every file as `image-batch-progress`.
+#[derive(Serialize, Debug, Clone)]
+pub struct BatchProgress {
+    pub completed: usize,
+    pub total: usize,
+    /// 0.0..=1.0
+    pub progress: f32,
+    pub file: BatchFileResult,
+}
+
+#[derive(Serialize, Debug)]
+pub struct BatchSummary {
+    pub total: usize,
+    pub succeeded: usize,
+    pub failed: usize,
+    pub skipped: usize,
+    pub cancelled: bool,
+    pub duration_ms: u64,
+    /// In input order
+    pub files: Vec<BatchFileResult>,
+}
+
+/// Resolves files, directories (their images, not recursive) and glob patterns such
+/// as `~/assets/**/*.png` into a list of files, without duplicates. A leading `~` is
+/// the home folder. Missing files are kept so they show up as failures in the summary.
+pub fn expand_inputs(inputs: &[String]) -> Result<Vec<PathBuf>, String> {
+    let mut files: Vec<PathBuf> = Vec::new();
+    let mut push = |path: PathBuf| {
+        if !files.contains(&path) {
+            files.push(path);
+        }
+    };
+
+    for input in inputs {
+        let input = &expand_home(input);
+        if input.contains(['*', '?', '[']) {
+            let matches =
+                glob::glob(input).map_err(|e| format!("Invalid pattern {}: {}", input, e))?;
+            for path in matches.flatten().filter(|p| p.is_file()) {
+                push(path);
+            }
+            continue;
+        }
+
+        let path = PathBuf::from(input);
+        if path.is_dir() {
+            let entries = std::fs::read_dir(&path)
+                .map_err(|e| format!("Failed to read folder {}: {}", input, e))?;
+            let mut images: Vec<PathBuf> = entries
+                .flatten()
+                .map(|e| e.path())
+                .filter(|p| p.is_file() && is_image(p))
+                .collect();
+            images.sort();
+            images.into_iter().for_each(&mut push);
+        } else {
+            push(path);
+        }
+    }
+
+    Ok(files)
+}
+
+/// `~` and `~/...` with the home folder in place of the `~`, as a shell would
+/// expand them; anything else as it is.
+fn expand_home(input: &str) -> String {
+    let Some(rest) = input.strip_prefix('~') else {
+        return input.to_string();
+    };
+    if !(rest.is_empty() || rest.starts_with(['/', '\\'])) {
+        return input.to_string();
+    }
+    match std::env::home_dir() {
+        Some(home) => format!("{}{}", home.to_string_lossy(), rest),
+        None => input.to_string(),
+    }
+}
+
+fn is_image(path: &Path) -> bool {
+    path.extension()
+        .and_then(|e| e.to_str())
+        .is_some_and(|e| IMAGE_EXTENSIONS.contains(&e.to_lowercase().as_str()))
+}
+
+/// Output path for every input: `<stem>.<extension>` in `output_dir` or next to the
+/// input. Avoids inputs and existing files, and never gives two inputs the same
+/// output; a `_1`, `_2`... suffix is added instead. [`run`] claims each path again
+/// when its file starts, in case something was written there in the meantime.
+pub fn plan(inputs: Vec<PathBuf>, output_dir: Option<&Path>, extension: &str) -> Vec<BatchJob> {
+    let mut taken: Vec<PathBuf> = Vec::new();
+    inputs
+        .into_iter()
+        .map(|input| {
+            let dir = output_dir
+                .map(Path::to_path_buf)
+                .or_else(|| input.parent().map(Path::to_path_buf))
+                .unwrap_or_default();
+            let stem = input
+                .file_stem()
+                .map(|s| s.to_string_lossy().to_string())
+                .unwrap_or_else(|| "image".to_string());
+
+            let mut output = dir.join(format!("{}.{}", stem, extension));
+            let mut n = 1;
+            while output == input || output.exists() || taken.contains(&output) {
+                output = dir.join(format!("{}_{}.{}", stem, n, extension));
+                n += 1;
+            }
+            taken.push(output.clone());
+            BatchJob { input, output }
+        })
+        .collect()
+}
+
+/// Creates `preferred` empty, or the first free `_1`, `_2`... variant of it if
+/// something already exists there, and returns the path it now owns.
+fn claim(preferred: &Path) -> Result<PathBuf, String> {
+    let stem = preferred
+        .file_stem()
+        .map(|s| s.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let extension = preferred
+        .extension()
+        .map(|e| e.to_string_lossy().to_string())
+        .unwrap_or_default();
+
+    let mut output = preferred.to_path_buf();
+    let mut n = 1;
+    loop {
+        match std::fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&output)
+        {
+            Ok(_) => return Ok(output),
+            Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => {
+                output = preferred.with_file_name(format!("{}_{}.{}", stem, n, extension));
+                n += 1;
+            }
+            Err(e) => return Err(format!("Failed to create output file: {}", e)),
+        }
+    }
+}
+
+/// Converts every job on up to `workers` threads. `cancel` is checked before each
+/// file starts; files already running are finished. Outputs are claimed with
+/// [`claim`] right before converting, so nothing is ever overwritten and the
+/// results carry the paths actually written. `on_progress` is called from the
+/// worker threads after each file.
+pub fn run(
+    jobs: &[BatchJob],
+    options: &ImageOptions,
+    workers: usize,
+    cancel: &Mutex<bool>,
+    on_progress: impl Fn(BatchProgress) + Sync,
+) -> BatchSummary {
+    let started = std::time::Instant::now();
+    let total = jobs.len();
+    let next = AtomicUsize::new(0);
+    let completed = AtomicUsize::new(0);
+    let results: Mutex<Vec<Option<BatchFileResult>>> = Mutex::new(vec![None; total]);
+    let workers = workers.clamp(1, MAX_WORKERS).min(total.max(1));
+
+    std::thread::scope(|scope| {
+        for _ in 0..workers {
+            scope.spawn(|| loop {
+                if cancel.lock().map(|c| *c).unwrap_or(false) {
+                    break;
+                }
+                let index = next.fetch_add(1, Ordering::SeqCst);
+                let Some(job) = jobs.get(index) else {
+                    break;
+                };
+
+                let mut output = job.output.clone();
+                let outcome = claim(&job.output).and_then(|claimed| {
+                    output = claimed;
+                    super::process(&job.input, &output, options).inspect_err(|_| {
+                        let _ = std::fs::remove_file(&output);
+                    })
+                });
+                let file = BatchFileResult {
+                    input: job.input.to_string_lossy().to_string(),
+                    output: output.to_string_lossy().to_string(),
+                    status: if outcome.is_ok() {
+                        FileStatus::Done
+                    } else {
+                        FileStatus::Failed
+                    },
+                    error: outcome.as_ref().err().cloned(),
+                    report: outcome.ok(),
+                };
+                if let Ok(mut results) = results.lock() {
+                    results[index] = Some(file.clone());
+                }
+
+                let done = completed.fetch_add(1, Ordering::SeqCst) + 1;
+                on_progress(BatchProgress {
+                    completed: done,
+                    total,
+                    progress: done as f32 / total as f32,
+                    file,
+                });
+            });
+        }
+    });
+
+    let files: Vec<BatchFileResult> = results
+        .into_inner()
+        .unwrap_or_else(|e| e.into_inner())
+        .into_iter()
+        .zip(jobs)
+        .map(|(result, job)| {
+            result.unwrap_or_else(|| BatchFileResult {
+                input: job.input.to_string_lossy().to_string(),
+                output: job.output.to_string_lossy().to_string(),
+                status: FileStatus::Skipped,
+                error: None,
+                report: None,
+            })
+        })
+        .collect();
+    let count = |status: FileStatus| files.iter().filter(|f| f.status == status).count();
+
+    BatchSummary {
+        total,
+        succeeded: count(FileStatus::Done),
+        failed: count(FileStatus::Failed),
+        skipped: count(FileStatus::Skipped),
+        cancelled: cancel.lock().map(|c| *c).unwrap_or(false),
+        duration_ms: started.elapsed().as_millis() as u64,
+        files,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Empty scratch folder for one test
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("taskgoblin-batch-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn png_options() -> ImageOptions {
+        ImageOptions {
+            format: "png".to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn expands_the_home_folder() {
+        let home = std::env::home_dir().unwrap().to_string_lossy().to_string();
+        assert_eq!(expand_home("~"), home);
+        assert_eq!(
+            expand_home("~/assets/**/*.png"),
+            format!("{}/assets/**/*.png", home)
+        );
+        assert_eq!(expand_home("~other/a.png"), "~other/a.png");
+        assert_eq!(expand_home("/tmp/~/a.png"), "/tmp/~/a.png");
+    }
+
+    #[test]
+    fn expands_folders_and_patterns() {
+        let dir = temp_dir("expand");
+        for name in ["b.png", "a.jpg", "notes.txt"] {
+            std::fs::write(dir.join(name), b"").unwrap();
+        }
+        let folder = dir.to_string_lossy().to_string();
+        let pattern = dir.join("*.png").to_string_lossy().to_string();
+        let missing = dir.join("missing.png").to_string_lossy().to_string();
+
+        let files = expand_inputs(&[folder, pattern, missing]).unwrap();
+        assert_eq!(
+            files,
+            [
+                dir.join("a.jpg"),
+                dir.join("b.png"),
+                dir.join("missing.png")
+            ]
+        );
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn claim_picks_the_next_free_name() {
+        let dir = temp_dir("claim");
+        std::fs::write(dir.join("a.png"), b"taken").unwrap();
+        std::fs::write(dir.join("a_1.png"), b"taken").unwrap();
+
+        let claimed = claim(&dir.join("a.png")).unwrap();
+        assert_eq!(claimed, dir.join("a_2.png"));
+        assert!(claimed.is_file());
+        assert_eq!(claim(&dir.join("b.png")).unwrap(), dir.join("b.png"));
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn run_keeps_files_written_after_planning() {
+        let dir = temp_dir("run");
+        let input = dir.join("photo.jpg");
+        image::RgbImage::from_pixel(8, 8, image::Rgb([200, 10, 10]))
+            .save(&input)
+            .unwrap();
+        let out = dir.join("out");
+        std::fs::create_dir_all(&out).unwrap();
+
+        let jobs = plan(vec![input], Some(&out), "png");
+        assert_eq!(jobs[0].output, out.join("photo.png"));
+        // Shows up between planning and converting
+        std::fs::write(out.join("photo.png"), b"keep me").unwrap();
+
+        let summary = run(&jobs, &png_options(), 2, &Mutex::new(false), |_| {});
+        assert_eq!(summary.succeeded, 1);
+        assert!(!summary.cancelled);
+        assert_eq!(std::fs::read(out.join("photo.png")).unwrap(), b"keep me");
+        let written = out.join("photo_1.png");
+        assert_eq!(summary.files[0].output, written.to_string_lossy());
+        assert_eq!(image::open(&written).unwrap().width(), 8);
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn failed_file_leaves_no_output() {
+        let dir = temp_dir("failed");
+        let jobs = plan(vec![dir.join("missing.png")], Some(&dir), "png");
+        let summary = run(&jobs, &png_options(), 1, &Mutex::new(false), |_| {});
+        assert_eq!(summary.failed, 1);
+        assert!(!dir.join("missing_1.png").exists() && !dir.join("missing.png").exists());
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn cancelled_batch_reports_the_flag() {
+        let dir = temp_dir("cancel");
+        let jobs = plan(vec![dir.join("a.png"), dir.join("b.png")], None, "jpg");
+        let summary = run(&jobs, &png_options(), 2, &Mutex::new(true), |_| {});
+        assert!(summary.cancelled);
+        assert_eq!(summary.skipped, 2);
+        let _ = std::fs::remove_dir_all(dir);
+    }
+}
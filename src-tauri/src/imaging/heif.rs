@@ -0,0 +1,240 @@
+//! HEIC/HEIF decoding and encoding. On Linux, and elsewhere with the `heif`
+//! feature, this goes through libheif in memory; other macOS builds fall back to
+//! `sips`, staging files in the system temp dir.
+
+use super::metadata::Metadata;
+use image::DynamicImage;
+use std::path::Path;
+
+/// `ftyp` brands of HEIF images. `mif1`/`msf1` are shared with AVIF, so files
+/// that also list an AVIF brand are left to the AVIF decoder.
+const HEIF_BRANDS: &[&[u8; 4]] = &[
+    b"heic", b"heix", b"hevc", b"hevx", b"heim", b"heis", b"mif1", b"msf1",
+];
+
+const AVIF_BRANDS: &[&[u8; 4]] = &[b"avif", b"avis"];
+
+/// Whether `data` starts like a HEIC/HEIF file (and not an AVIF one).
+pub fn is_heif(data: &[u8]) -> bool {
+    if data.len() < 12 || &data[4..8] != b"ftyp" {
+        return false;
+    }
+    // Major brand, minor version, then compatible brands to the end of the box
+    let size = u32::from_be_bytes([data[0], data[1], data[2], data[3]]) as usize;
+    let ftyp = &data[..size.clamp(12, data.len())];
+    let brands: Vec<&[u8]> = std::iter::once(&ftyp[8..12])
+        .chain(ftyp.get(16..).unwrap_or_default().chunks_exact(4))
+        .collect();
+    let has = |list: &[&[u8; 4]]| brands.iter().any(|b| list.iter().any(|l| *b == &l[..]));
+    !has(AVIF_BRANDS) && has(HEIF_BRANDS)
+}
+
+/// Decodes the primary image. libheif applies the container's rotation and mirror,
+/// so the result is already upright and EXIF orientation must not be applied again.
+#[cfg(any(feature = "heif", target_os = "linux"))]
+pub fn decode(path: &Path) -> Result<DynamicImage, String> {
+    use libheif_rs::{ColorSpace, HeifContext, LibHeif, RgbChroma};
+
+    let bytes = std::fs::read(path).map_err(|e| format!("Failed to read image: {}", e))?;
+    let ctx = HeifContext::read_from_bytes(&bytes)
+        .map_err(|e| format!("Failed to open HEIF image: {}", e))?;
+    let handle = ctx
+        .primary_image_handle()
+        .map_err(|e| format!("Failed to open HEIF image: {}", e))?;
+    let alpha = handle.has_alpha_channel();
+    let chroma = if alpha {
+        RgbChroma::Rgba
+    } else {
+        RgbChroma::Rgb
+    };
+    let decoded = LibHeif::new()
+        .decode(&handle, ColorSpace::Rgb(chroma), None)
+        .map_err(|e| format!("Failed to decode HEIF image: {}", e))?;
+
+    let planes = decoded.planes();
+    let plane = planes
+        .interleaved
+        .ok_or_else(|| "HEIF decoder returned no pixels".to_string())?;
+    let (width, height) = (plane.width, plane.height);
+    let row_len = width as usize * if alpha { 4 } else { 3 };
+    let mut pixels = Vec::with_capacity(row_len * height as usize);
+    for row in plane.data.chunks(plane.stride).take(height as usize) {
+        pixels.extend_from_slice(&row[..row_len]);
+    }
+
+    let img = if alpha {
+        image::RgbaImage::from_raw(width, height, pixels).map(DynamicImage::ImageRgba8)
+    } else {
+        image::RgbImage::from_raw(width, height, pixels).map(DynamicImage::ImageRgb8)
+    };
+    img.ok_or_else(|| "HEIF decoder returned a truncated image".to_string())
+}
+
+/// Encodes `img` as HEVC-compressed HEIC at `quality` (1-100), with `metadata`.
+#[cfg(any(feature = "heif", target_os = "linux"))]
+pub fn encode(img: &DynamicImage, quality: u8, metadata: &Metadata) -> Result<Vec<u8>, String> {
+    use libheif_rs::{
+        Channel, ColorSpace, CompressionFormat, EncoderQuality, HeifContext, Image, LibHeif,
+        RgbChroma,
+    };
+
+    let err = |e: libheif_rs::HeifError| format!("Failed to encode HEIC: {}", e);
+    let (width, height) = (img.width(), img.height());
+    let alpha = img.color().has_alpha();
+    let (chroma, pixels) = if alpha {
+        (RgbChroma::Rgba, img.to_rgba8().into_raw())
+    } else {
+        (RgbChroma::Rgb, img.to_rgb8().into_raw())
+    };
+    let row_len = pixels.len() / height as usize;
+
+    let mut image = Image::new(width, height, ColorSpace::Rgb(chroma)).map_err(err)?;
+    image
+        .create_plane(Channel::Interleaved, width, height, 8)
+        .map_err(err)?;
+    {
+        let planes = image.planes_mut();
+        let plane = planes
+            .interleaved
+            .ok_or_else(|| "Failed to encode HEIC: no pixel plane".to_string())?;
+        for (y, row) in pixels.chunks_exact(row_len).enumerate() {
+            plane.data[y * plane.stride..][..row_len].copy_from_slice(row);
+        }
+    }
+
+    let lib = LibHeif::new();
+    let mut encoder = lib
+        .encoder_for_format(CompressionFormat::Hevc)
+        .map_err(err)?;
+    encoder
+        .set_quality(EncoderQuality::Lossy(quality))
+        .map_err(err)?;
+    let mut ctx = HeifContext::new().map_err(err)?;
+    let handle = ctx.encode_image(&image, &mut encoder, None).map_err(err)?;
+    if let Some(exif) = &metadata.exif {
+        ctx.add_exif_metadata(&handle, exif).map_err(err)?;
+    }
+    if let Some(xmp) = &metadata.xmp {
+        ctx.add_xmp_metadata(&handle, xmp).map_err(err)?;
+    }
+    ctx.write_to_bytes().map_err(err)
+}
+
+/// A file in the system temp dir, removed when dropped.
+#[cfg(all(not(feature = "heif"), target_os = "macos"))]
+struct TempFile(std::path::PathBuf);
+
+#[cfg(all(not(feature = "heif"), target_os = "macos"))]
+impl TempFile {
+    fn new(extension: &str) -> Self {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        static COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+        Self(std::env::temp_dir().join(format!(
+            "taskgoblin-heif-{}-{}.{}",
+            std::process::id(),
+            COUNTER.fetch_add(1, Ordering::SeqCst),
+            extension
+        )))
+    }
+}
+
+#[cfg(all(not(feature = "heif"), target_os = "macos"))]
+impl Drop for TempFile {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.0);
+    }
+}
+
+/// Runs `sips` to convert `input` into `output` in `format`.
+#[cfg(all(not(feature = "heif"), target_os = "macos"))]
+fn sips(format: &str, quality: Option<u8>, input: &Path, output: &Path) -> Result<(), String> {
+    let mut command = std::process::Command::new("sips");
+    command.args(["-s", "format", format]);
+    if let Some(quality) = quality {
+        command.args(["-s", "formatOptions", &quality.to_string()]);
+    }
+    let result = command
+        .arg(input)
+        .arg("--out")
+        .arg(output)
+        .output()
+        .map_err(|e| format!("Sips command failed: {}", e))?;
+    if !result.status.success() {
+        return Err(format!(
+            "sips failed: {}",
+            String::from_utf8_lossy(&result.stderr)
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(all(not(feature = "heif"), target_os = "macos"))]
+pub fn decode(path: &Path) -> Result<DynamicImage, String> {
+    let png = TempFile::new("png");
+    sips("png", None, path, &png.0)?;
+    image::open(&png.0).map_err(|e| format!("Failed to open image: {}", e))
+}
+
+/// `sips` writes no metadata we hand it, so `metadata` is dropped here.
+#[cfg(all(not(feature = "heif"), target_os = "macos"))]
+pub fn encode(img: &DynamicImage, quality: u8, _metadata: &Metadata) -> Result<Vec<u8>, String> {
+    let png = TempFile::new("png");
+    let heic = TempFile::new("heic");
+    img.save_with_format(&png.0, image::ImageFormat::Png)
+        .map_err(|e| format!("Failed to write temp PNG for HEIC conversion: {}", e))?;
+    sips("heic", Some(quality), &png.0, &heic.0)?;
+    std::fs::read(&heic.0).map_err(|e| format!("Failed to read converted HEIC: {}", e))
+}
+
+#[cfg(not(any(feature = "heif", target_os = "macos", target_os = "linux")))]
+pub fn decode(_path: &Path) -> Result<DynamicImage, String> {
+    Err("HEIC/HEIF images need a build with the `heif` feature".to_string())
+}
+
+#[cfg(not(any(feature = "heif", target_os = "macos", target_os = "linux")))]
+pub fn encode(_img: &DynamicImage, _quality: u8, _metadata: &Metadata) -> Result<Vec<u8>, String> {
+    Err("HEIC/HEIF images need a build with the `heif` feature".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ftyp(major: &[u8; 4], compatible: &[&[u8; 4]]) -> Vec<u8> {
+        let size = 16 + 4 * compatible.len() as u32;
+        let mut data = size.to_be_bytes().to_vec();
+        data.extend_from_slice(b"ftyp");
+        data.extend_from_slice(major);
+        data.extend_from_slice(&[0; 4]);
+        for brand in compatible {
+            data.extend_from_slice(*brand);
+        }
+        // The next box, which must not be read as a brand
+        data.extend_from_slice(b"\0\0\0\x08avif");
+        data
+    }
+
+    #[test]
+    fn sniffs_heif_brands() {
+        assert!(is_heif(&ftyp(b"heic", &[b"mif1", b"heic"])));
+        assert!(is_heif(&ftyp(b"mif1", &[b"heic"])));
+        assert!(is_heif(&ftyp(b"msf1", &[])));
+        // Short files with only the major brand
+        assert!(is_heif(&ftyp(b"heix", &[])[..12]));
+    }
+
+    #[test]
+    fn avif_is_not_heif() {
+        assert!(!is_heif(&ftyp(b"avif", &[b"mif1", b"miaf"])));
+        assert!(!is_heif(&ftyp(b"mif1", &[b"avif", b"miaf"])));
+        assert!(!is_heif(&ftyp(b"msf1", &[b"avis", b"msf1"])));
+    }
+
+    #[test]
+    fn other_files_are_not_heif() {
+        assert!(!is_heif(&ftyp(b"isom", &[b"mp41"])));
+        assert!(!is_heif(b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR"));
+        assert!(!is_heif(b"ftyp"));
+    }
+}
@@ -0,0 +1,651 @@
+//! Lossless JPEG optimization, as `jpegtran -optimize` does it: the entropy-coded
+//! data is decoded down to its Huffman symbols and written again with tables built
+//! for this image. The DCT coefficients, and so the pixels, are left as they were.
+
+/// A Huffman table as stored in a DHT segment.
+#[derive(Clone)]
+struct Table {
+    /// Number of codes of each length, 1 to 16 bits
+    counts: [u8; 16],
+    /// Symbols in code order
+    symbols: Vec<u8>,
+}
+
+impl Table {
+    /// Canonical (code, length) of every symbol, as in Annex C of T.81.
+    fn codes(&self) -> [(u16, u8); 256] {
+        let mut codes = [(0, 0); 256];
+        let mut symbols = self.symbols.iter();
+        let mut code = 0u32;
+        for (len, &count) in (1..).zip(&self.counts) {
+            for symbol in symbols.by_ref().take(count as usize) {
+                codes[*symbol as usize] = (code as u16, len);
+                code += 1;
+            }
+            code <<= 1;
+        }
+        codes
+    }
+
+    /// The shortest codes for symbols seen `frequencies` times, limited to 16 bits
+    /// and never all ones (Annex K.2 of T.81).
+    fn optimal(frequencies: &[u32; 256]) -> Self {
+        // A reserved extra symbol takes the all-ones code and is dropped at the end
+        let mut freq = [0u64; 257];
+        for (f, &count) in freq.iter_mut().zip(frequencies) {
+            *f = count as u64;
+        }
+        freq[256] = 1;
+        let mut sizes = [0usize; 257];
+        let mut others = [usize::MAX; 257];
+
+        loop {
+            // The two least frequent; on ties the higher symbol, as libjpeg does
+            let mut c1 = None;
+            let mut c2 = None;
+            for i in 0..257 {
+                if freq[i] == 0 {
+                    continue;
+                }
+                if c1.is_none_or(|c: usize| freq[i] <= freq[c]) {
+                    c2 = c1;
+                    c1 = Some(i);
+                } else if c2.is_none_or(|c: usize| freq[i] <= freq[c]) {
+                    c2 = Some(i);
+                }
+            }
+            let (Some(mut c1), Some(mut c2)) = (c1, c2) else {
+                break;
+            };
+            freq[c1] += freq[c2];
+            freq[c2] = 0;
+            sizes[c1] += 1;
+            while others[c1] != usize::MAX {
+                c1 = others[c1];
+                sizes[c1] += 1;
+            }
+            others[c1] = c2;
+            sizes[c2] += 1;
+            while others[c2] != usize::MAX {
+                c2 = others[c2];
+                sizes[c2] += 1;
+            }
+        }
+
+        let mut bits = [0u32; 33];
+        for &size in sizes.iter().filter(|&&s| s > 0) {
+            bits[size] += 1;
+        }
+        // Shorten codes past 16 bits, keeping the tree full
+        for i in (17..=32).rev() {
+            while bits[i] > 0 {
+                let mut j = i - 2;
+                while bits[j] == 0 {
+                    j -= 1;
+                }
+                bits[i] -= 2;
+                bits[i - 1] += 1;
+                bits[j + 1] += 2;
+                bits[j] -= 1;
+            }
+        }
+        let longest = (1..=16).rev().find(|&i| bits[i] > 0).unwrap_or(1);
+        bits[longest] -= 1;
+
+        let mut counts = [0u8; 16];
+        for (count, &n) in counts.iter_mut().zip(&bits[1..=16]) {
+            *count = n as u8;
+        }
+        let mut symbols = Vec::new();
+        for size in 1..=32 {
+            symbols.extend((0..256).filter(|&s| sizes[s] == size).map(|s| s as u8));
+        }
+        Self { counts, symbols }
+    }
+}
+
+/// Decoding side of a [`Table`] (Annex F.2.2.3 of T.81).
+struct Decoder {
+    table: Table,
+    min_code: [i32; 17],
+    max_code: [i32; 17],
+    first: [usize; 17],
+}
+
+impl Decoder {
+    fn new(table: Table) -> Option<Self> {
+        let mut decoder = Self {
+            table,
+            min_code: [0; 17],
+            max_code: [-1; 17],
+            first: [0; 17],
+        };
+        let (mut code, mut k) = (0i32, 0usize);
+        for len in 1..=16 {
+            let count = decoder.table.counts[len - 1] as usize;
+            if count > 0 {
+                decoder.first[len] = k;
+                decoder.min_code[len] = code;
+                code += count as i32;
+                k += count;
+                decoder.max_code[len] = code - 1;
+            }
+            if code > 1 << len {
+                return None;
+            }
+            code <<= 1;
+        }
+        (k <= decoder.table.symbols.len()).then_some(decoder)
+    }
+
+    fn decode(&self, reader: &mut BitReader) -> Option<u8> {
+        let mut code = 0i32;
+        for len in 1..=16 {
+            code = (code << 1) | reader.bits(1)? as i32;
+            if code <= self.max_code[len] {
+                let index = self.first[len] + (code - self.min_code[len]) as usize;
+                return Some(self.table.symbols[index]);
+            }
+        }
+        None
+    }
+}
+
+/// Reads the entropy-coded data of one scan, undoing the 0xFF00 byte stuffing.
+struct BitReader<'a> {
+    data: &'a [u8],
+    pos: usize,
+    acc: u32,
+    count: u8,
+}
+
+impl BitReader<'_> {
+    fn bits(&mut self, n: u8) -> Option<u16> {
+        let mut value = 0u16;
+        for _ in 0..n {
+            if self.count == 0 {
+                let byte = *self.data.get(self.pos)?;
+                if byte == 0xFF {
+                    // A stuffed zero; anything else is a restart marker in the way
+                    if self.data.get(self.pos + 1) != Some(&0) {
+                        return None;
+                    }
+                    self.pos += 1;
+                }
+                self.pos += 1;
+                self.acc = byte as u32;
+                self.count = 8;
+            }
+            self.count -= 1;
+            value = (value << 1) | ((self.acc >> self.count) & 1) as u16;
+        }
+        Some(value)
+    }
+
+    /// Drops the padding bits and steps over the next RSTn marker.
+    fn restart(&mut self) -> Option<()> {
+        self.count = 0;
+        while self.data.get(self.pos..self.pos + 2) == Some(&[0xFF, 0xFF]) {
+            self.pos += 1;
+        }
+        match self.data.get(self.pos..self.pos + 2)? {
+            [0xFF, 0xD0..=0xD7] => {
+                self.pos += 2;
+                Some(())
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Writes entropy-coded data, stuffing a zero after every 0xFF.
+#[derive(Default)]
+struct BitWriter {
+    out: Vec<u8>,
+    acc: u32,
+    count: u8,
+}
+
+impl BitWriter {
+    fn write(&mut self, value: u16, len: u8) {
+        self.acc = (self.acc << len) | (value as u32 & ((1 << len) - 1));
+        self.count += len;
+        while self.count >= 8 {
+            self.count -= 8;
+            let byte = (self.acc >> self.count) as u8;
+            self.out.push(byte);
+            if byte == 0xFF {
+                self.out.push(0);
+            }
+        }
+        self.acc &= (1 << self.count) - 1;
+    }
+
+    /// Pads the last byte with ones.
+    fn flush(&mut self) {
+        if self.count > 0 {
+            self.write(0xFF, 8 - self.count);
+        }
+    }
+}
+
+/// One Huffman-coded symbol and the raw bits after it; `table` 0-3 are the DC
+/// tables and 4-7 the AC ones, [`RESTART`] marks a restart interval boundary.
+#[derive(Clone, Copy)]
+struct Token {
+    table: u8,
+    symbol: u8,
+    extra: u16,
+}
+
+const RESTART: u8 = 0xFF;
+
+impl Token {
+    fn extra_len(self) -> u8 {
+        if self.table < 4 {
+            self.symbol
+        } else {
+            self.symbol & 0x0F
+        }
+    }
+}
+
+struct Component {
+    id: u8,
+    h: usize,
+    v: usize,
+}
+
+struct Frame {
+    width: usize,
+    height: usize,
+    components: Vec<Component>,
+}
+
+/// Decodes the tokens of one sequential scan; `scan` is the SOS payload.
+fn read_scan(
+    data: &[u8],
+    frame: &Frame,
+    scan: &[u8],
+    tables: &[Option<Decoder>; 8],
+    restart_interval: usize,
+) -> Option<Vec<Token>> {
+    let count = *scan.first()? as usize;
+    let selectors = scan.get(1..1 + 2 * count)?;
+    // Sequential scans always cover all 64 coefficients at full precision
+    if scan.get(1 + 2 * count..)? != [0, 63, 0] {
+        return None;
+    }
+    let mut components = Vec::with_capacity(count);
+    for pair in selectors.chunks_exact(2) {
+        let index = frame.components.iter().position(|c| c.id == pair[0])?;
+        let (dc, ac) = ((pair[1] >> 4) as usize, (pair[1] & 0x0F) as usize);
+        if dc > 3 || ac > 3 || tables[dc].is_none() || tables[4 + ac].is_none() {
+            return None;
+        }
+        components.push((&frame.components[index], dc, 4 + ac));
+    }
+
+    let h_max = frame.components.iter().map(|c| c.h).max()?;
+    let v_max = frame.components.iter().map(|c| c.v).max()?;
+    // One block per MCU when the scan has a single component, else h x v of each
+    let (mcus_across, mcus_down) = match components[..] {
+        [(c, ..)] => (
+            (frame.width * c.h).div_ceil(h_max).div_ceil(8),
+            (frame.height * c.v).div_ceil(v_max).div_ceil(8),
+        ),
+        _ => (
+            frame.width.div_ceil(8 * h_max),
+            frame.height.div_ceil(8 * v_max),
+        ),
+    };
+    let mcus = mcus_across * mcus_down;
+
+    let mut reader = BitReader {
+        data,
+        pos: 0,
+        acc: 0,
+        count: 0,
+    };
+    let mut tokens = Vec::new();
+    let read = |reader: &mut BitReader, table: usize, tokens: &mut Vec<Token>| {
+        let symbol = tables[table].as_ref()?.decode(reader)?;
+        let mut token = Token {
+            table: table as u8,
+            symbol,
+            extra: 0,
+        };
+        // DC differences take at most 15 bits
+        if token.extra_len() > 15 {
+            return None;
+        }
+        token.extra = reader.bits(token.extra_len())?;
+        tokens.push(token);
+        Some(symbol)
+    };
+    for mcu in 0..mcus {
+        for &(component, dc, ac) in &components {
+            let blocks = if components.len() == 1 {
+                1
+            } else {
+                component.h * component.v
+            };
+            for _ in 0..blocks {
+                read(&mut reader, dc, &mut tokens)?;
+                let mut k = 1;
+                while k < 64 {
+                    let rs = read(&mut reader, ac, &mut tokens)?;
+                    match (rs >> 4, rs & 0x0F) {
+                        (15, 0) => k += 16,
+                        // End of block
+                        (_, 0) => break,
+                        (run, _) => k += run as usize + 1,
+                    }
+                }
+                if k > 64 {
+                    return None;
+                }
+            }
+        }
+        if restart_interval > 0 && (mcu + 1) % restart_interval == 0 && mcu + 1 < mcus {
+            reader.restart()?;
+            tokens.push(Token {
+                table: RESTART,
+                symbol: 0,
+                extra: 0,
+            });
+        }
+    }
+    Some(tokens)
+}
+
+/// A DHT segment holding `tables` (index as in [`Token::table`]).
+fn dht(tables: &[(usize, Table)]) -> Vec<u8> {
+    let mut payload = Vec::new();
+    for (index, table) in tables {
+        // Class 0 is DC, 1 is AC
+        payload.push((((index / 4) << 4) | (index % 4)) as u8);
+        payload.extend_from_slice(&table.counts);
+        payload.extend_from_slice(&table.symbols);
+    }
+    let mut segment = vec![0xFF, 0xC4];
+    segment.extend_from_slice(&(payload.len() as u16 + 2).to_be_bytes());
+    segment.extend(payload);
+    segment
+}
+
+/// Rewrites a baseline (or extended sequential) Huffman-coded JPEG with Huffman
+/// tables optimized for its data. EXIF, XMP, comments and other application data
+/// are dropped; JFIF, ICC profiles and Adobe color transforms are kept. `None` for
+/// progressive, lossless, arithmetic-coded and damaged files.
+pub fn optimize(data: &[u8]) -> Option<Vec<u8>> {
+    if !data.starts_with(&[0xFF, 0xD8]) {
+        return None;
+    }
+    let mut out = vec![0xFF, 0xD8];
+    let mut tables: [Option<Decoder>; 8] = Default::default();
+    let mut frame = None;
+    let mut restart_interval = 0;
+    let mut pos = 2;
+
+    loop {
+        while data.get(pos..pos + 2) == Some(&[0xFF, 0xFF]) {
+            pos += 1;
+        }
+        let marker = match data.get(pos..pos + 2)? {
+            [0xFF, marker] => *marker,
+            _ => return None,
+        };
+        match marker {
+            0xD9 => {
+                out.extend_from_slice(&[0xFF, 0xD9]);
+                return Some(out);
+            }
+            // Markers without a length have no place outside the scan data
+            0x01 | 0xD0..=0xD8 => return None,
+            _ => {}
+        }
+        let len = u16::from_be_bytes([*data.get(pos + 2)?, *data.get(pos + 3)?]) as usize;
+        let segment = data.get(pos..pos + 2 + len)?;
+        let payload = segment.get(4..)?;
+        pos += 2 + len;
+
+        match marker {
+            // Baseline and extended sequential, Huffman-coded
+            0xC0 | 0xC1 => {
+                let count = *payload.get(5)? as usize;
+                let components = payload
+                    .get(6..6 + 3 * count)?
+                    .chunks_exact(3)
+                    .map(|c| Component {
+                        id: c[0],
+                        h: (c[1] >> 4) as usize,
+                        v: (c[1] & 0x0F) as usize,
+                    })
+                    .collect::<Vec<_>>();
+                let height = u16::from_be_bytes([payload[1], payload[2]]) as usize;
+                let width = u16::from_be_bytes([payload[3], payload[4]]) as usize;
+                // A zero height comes later in a DNL marker, which isn't handled
+                if count == 0 || width == 0 || height == 0 {
+                    return None;
+                }
+                if components
+                    .iter()
+                    .any(|c| !(1..=4).contains(&c.h) || !(1..=4).contains(&c.v))
+                {
+                    return None;
+                }
+                frame = Some(Frame {
+                    width,
+                    height,
+                    components,
+                });
+                out.extend_from_slice(segment);
+            }
+            // Every other frame type
+            0xC2 | 0xC3 | 0xC5..=0xC7 | 0xC8..=0xCB | 0xCD..=0xCF => return None,
+            0xC4 => {
+                let mut rest = payload;
+                while let [class_id, counts @ ..] = rest {
+                    let counts: [u8; 16] = counts.get(..16)?.try_into().ok()?;
+                    let total = counts.iter().map(|&c| c as usize).sum::<usize>();
+                    let symbols = rest.get(17..17 + total)?.to_vec();
+                    let (class, id) = ((class_id >> 4) as usize, (class_id & 0x0F) as usize);
+                    if class > 1 || id > 3 {
+                        return None;
+                    }
+                    tables[class * 4 + id] = Some(Decoder::new(Table { counts, symbols })?);
+                    rest = &rest[17 + total..];
+                }
+            }
+            0xDD => {
+                restart_interval =
+                    u16::from_be_bytes([*payload.first()?, *payload.get(1)?]) as usize;
+                out.extend_from_slice(segment);
+            }
+            0xDA => {
+                let frame = frame.as_ref()?;
+                // The scan data runs to the first marker that isn't a restart
+                let start = pos;
+                let mut end = start;
+                loop {
+                    match data.get(end..end + 2)? {
+                        [0xFF, 0x00 | 0xD0..=0xD7] => end += 2,
+                        [0xFF, _] => break,
+                        _ => end += 1,
+                    }
+                }
+                let tokens =
+                    read_scan(&data[start..end], frame, payload, &tables, restart_interval)?;
+
+                let mut frequencies = [[0u32; 256]; 8];
+                for token in tokens.iter().filter(|t| t.table != RESTART) {
+                    frequencies[token.table as usize][token.symbol as usize] += 1;
+                }
+                let optimized = (0..8)
+                    .filter(|&i| frequencies[i].iter().any(|&f| f > 0))
+                    .map(|i| (i, Table::optimal(&frequencies[i])))
+                    .collect::<Vec<_>>();
+                let mut codes = [[(0, 0); 256]; 8];
+                for (index, table) in &optimized {
+                    codes[*index] = table.codes();
+                }
+
+                out.extend(dht(&optimized));
+                out.extend_from_slice(segment);
+                let mut writer = BitWriter::default();
+                let mut restarts = 0u8;
+                for token in tokens {
+                    if token.table == RESTART {
+                        writer.flush();
+                        writer.out.extend_from_slice(&[0xFF, 0xD0 + restarts % 8]);
+                        restarts = restarts.wrapping_add(1);
+                        continue;
+                    }
+                    let (code, len) = codes[token.table as usize][token.symbol as usize];
+                    writer.write(code, len);
+                    writer.write(token.extra, token.extra_len());
+                }
+                writer.flush();
+                out.extend(writer.out);
+                pos = end;
+            }
+            // JFIF, ICC profile, Adobe (which says how the colors are stored)
+            0xE0 | 0xE2 | 0xEE => out.extend_from_slice(segment),
+            // Other application data and comments
+            0xE1 | 0xE3..=0xED | 0xEF | 0xFE => {}
+            // Quantization tables and anything else
+            _ => out.extend_from_slice(segment),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn photo(width: u32, height: u32) -> image::RgbImage {
+        image::RgbImage::from_fn(width, height, |x, y| {
+            let n = (x * 7) ^ (y * 13);
+            image::Rgb([
+                (x * 255 / width) as u8,
+                (n % 97) as u8 + (y * 120 / height) as u8,
+                ((x + y) * 3 % 256) as u8,
+            ])
+        })
+    }
+
+    fn encode(
+        img: &image::RgbImage,
+        f: impl FnOnce(&mut jpeg_encoder::Encoder<&mut Vec<u8>>),
+    ) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        let mut encoder = jpeg_encoder::Encoder::new(&mut bytes, 85);
+        f(&mut encoder);
+        encoder
+            .encode(
+                img.as_raw(),
+                img.width() as u16,
+                img.height() as u16,
+                jpeg_encoder::ColorType::Rgb,
+            )
+            .unwrap();
+        bytes
+    }
+
+    fn assert_same_pixels(original: &[u8], optimized: &[u8]) {
+        let original = image::load_from_memory(original).unwrap();
+        let optimized = image::load_from_memory(optimized).unwrap();
+        assert_eq!(original.to_rgb8(), optimized.to_rgb8());
+    }
+
+    #[test]
+    fn keeps_the_pixels_and_shrinks() {
+        // Odd sizes leave partial MCUs at the edges
+        let img = photo(101, 67);
+        for sampling in [
+            jpeg_encoder::SamplingFactor::F_1_1,
+            jpeg_encoder::SamplingFactor::F_2_2,
+            jpeg_encoder::SamplingFactor::F_2_1,
+        ] {
+            let original = encode(&img, |e| e.set_sampling_factor(sampling));
+            let optimized = optimize(&original).unwrap();
+            assert!(optimized.len() < original.len(), "{:?}", sampling);
+            assert_same_pixels(&original, &optimized);
+            // Already optimal
+            assert_eq!(optimize(&optimized).unwrap(), optimized);
+        }
+
+        let mut bytes = Vec::new();
+        image::codecs::jpeg::JpegEncoder::new_with_quality(&mut bytes, 90)
+            .encode_image(&image::DynamicImage::ImageRgb8(img))
+            .unwrap();
+        let optimized = optimize(&bytes).unwrap();
+        assert!(optimized.len() < bytes.len());
+        assert_same_pixels(&bytes, &optimized);
+    }
+
+    #[test]
+    fn keeps_restart_markers() {
+        let original = encode(&photo(64, 48), |e| {
+            e.set_sampling_factor(jpeg_encoder::SamplingFactor::F_2_2);
+            e.set_restart_interval(3);
+        });
+        let optimized = optimize(&original).unwrap();
+        let restarts = |data: &[u8]| {
+            data.windows(2)
+                .filter(|w| w[0] == 0xFF && (0xD0..=0xD7).contains(&w[1]))
+                .count()
+        };
+        assert_eq!(restarts(&optimized), restarts(&original));
+        assert!(restarts(&original) > 0);
+        assert_same_pixels(&original, &optimized);
+    }
+
+    #[test]
+    fn drops_metadata_segments() {
+        let original = encode(&photo(16, 16), |_| {});
+        let comment = [0xFF, 0xFE, 0, 6, b'h', b'i', b'!', b'!'];
+        let exif = [0xFF, 0xE1, 0, 8, b'E', b'x', b'i', b'f', 0, 0];
+        let tagged = [&original[..2], &exif, &comment, &original[2..]].concat();
+        assert_eq!(optimize(&tagged).unwrap(), optimize(&original).unwrap());
+    }
+
+    #[test]
+    fn rejects_what_it_cant_rewrite() {
+        let progressive = encode(&photo(32, 32), |e| e.set_progressive(true));
+        assert!(optimize(&progressive).is_none());
+
+        let original = encode(&photo(32, 32), |_| {});
+        assert!(optimize(&original[..original.len() / 2]).is_none());
+        assert!(optimize(b"\x89PNG\r\n\x1a\n").is_none());
+    }
+
+    #[test]
+    fn optimal_tables_fit_in_16_bits() {
+        // Fibonacci frequencies make the deepest possible tree
+        let mut frequencies = [0u32; 256];
+        let (mut a, mut b) = (1u32, 1u32);
+        for f in frequencies.iter_mut().take(30) {
+            *f = a;
+            (a, b) = (b, a + b);
+        }
+        let table = Table::optimal(&frequencies);
+        assert_eq!(table.symbols.len(), 30);
+        assert_eq!(table.counts.iter().map(|&c| c as usize).sum::<usize>(), 30);
+        assert!(Decoder::new(table.clone()).is_some());
+        // No code is all ones
+        let codes = table.codes();
+        for &symbol in &table.symbols {
+            let (code, len) = codes[symbol as usize];
+            assert!(code as u32 != (1 << len) - 1);
+        }
+
+        let single = Table::optimal(&{
+            let mut f = [0; 256];
+            f[0] = 5;
+            f
+        });
+        assert_eq!(single.codes()[0], (0, 1));
+    }
+}
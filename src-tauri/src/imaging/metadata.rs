@@ -0,0 +1,1219 @@
+//! EXIF and XMP handling for converted images.
+//!
+//! Re-encoding through `image` drops every tag, so whatever the policy keeps is read
+//! from the source file, rebuilt as a fresh EXIF block (orientation reset, since the
+//! pixels are already upright) and spliced into the encoded output: APP1 segments
+//! for JPEG, `eXIf`/`iTXt` chunks for PNG, `EXIF`/`XMP ` chunks for WebP and
+//! `Exif`/`mime` items for AVIF. Other formats are written without metadata.
+
+use serde::{Deserialize, Serialize};
+use std::ops::Range;
+use std::path::Path;
+
+const XMP_JPEG_HEADER: &[u8] = b"http://ns.adobe.com/xap/1.0/\0";
+const XMP_PNG_KEYWORD: &[u8] = b"XML:com.adobe.xmp";
+const PNG_SIGNATURE: &[u8] = b"\x89PNG\r\n\x1a\n";
+/// XMP properties that give away where a photo was taken.
+const XMP_LOCATION_MARKERS: &[&str] = &[
+    "exif:GPS",
+    "Iptc4xmpCore:Location",
+    "Iptc4xmpExt:LocationCreated",
+    "Iptc4xmpExt:LocationShown",
+    "photoshop:City",
+    "photoshop:State",
+    "photoshop:Country",
+];
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum MetadataPolicy {
+    /// Write no metadata from the source
+    #[default]
+    Strip,
+    /// Keep everything except GPS tags and XMP location data
+    StripLocation,
+    Preserve,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(default)]
+pub struct MetadataOptions {
+    pub policy: MetadataPolicy,
+    /// Written as EXIF Artist, whatever the policy. The source XMP is dropped when
+    /// author or copyright are set so it can't contradict them.
+    pub author: Option<String>,
+    /// Written as EXIF Copyright, whatever the policy
+    pub copyright: Option<String>,
+}
+
+impl MetadataOptions {
+    fn overrides(&self) -> bool {
+        self.author.is_some() || self.copyright.is_some()
+    }
+}
+
+/// Metadata to embed in an output image.
+#[derive(Debug, Default)]
+pub struct Metadata {
+    /// A TIFF-structured EXIF block, starting with the byte order mark
+    pub exif: Option<Vec<u8>>,
+    pub xmp: Option<Vec<u8>>,
+}
+
+impl Metadata {
+    pub fn is_empty(&self) -> bool {
+        self.exif.is_none() && self.xmp.is_none()
+    }
+
+    /// Bytes the metadata adds to an output, roughly (container overhead aside).
+    pub fn len(&self) -> u64 {
+        let len = |b: &Option<Vec<u8>>| b.as_ref().map_or(0, |b| b.len() as u64 + 32);
+        len(&self.exif) + len(&self.xmp)
+    }
+}
+
+/// Tags that describe the source file's own layout or pixels rather than the photo,
+/// and would be wrong in the converted image.
+fn is_layout_tag(tag: exif::Tag) -> bool {
+    use exif::Tag;
+    [
+        Tag::ExifIFDPointer,
+        Tag::GPSInfoIFDPointer,
+        Tag::InteropIFDPointer,
+        Tag::ImageWidth,
+        Tag::ImageLength,
+        Tag::BitsPerSample,
+        Tag::Compression,
+        Tag::PhotometricInterpretation,
+        Tag::StripOffsets,
+        Tag::SamplesPerPixel,
+        Tag::RowsPerStrip,
+        Tag::StripByteCounts,
+        Tag::PlanarConfiguration,
+        Tag::TileOffsets,
+        Tag::TileByteCounts,
+        Tag::JPEGInterchangeFormat,
+        Tag::JPEGInterchangeFormatLength,
+        Tag::PixelXDimension,
+        Tag::PixelYDimension,
+    ]
+    .contains(&tag)
+}
+
+fn ascii_field(tag: exif::Tag, text: &str) -> exif::Field {
+    exif::Field {
+        tag,
+        ifd_num: exif::In::PRIMARY,
+        value: exif::Value::Ascii(vec![text.as_bytes().to_vec()]),
+    }
+}
+
+/// What `options` keeps of the metadata in `source` (the input file's bytes).
+pub fn collect(source: &[u8], options: &MetadataOptions) -> Result<Metadata, String> {
+    let mut fields: Vec<exif::Field> = Vec::new();
+    let mut xmp = None;
+
+    if options.policy != MetadataPolicy::Strip {
+        if let Ok(exif) = exif::Reader::new().read_from_container(&mut std::io::Cursor::new(source))
+        {
+            fields = exif
+                .fields()
+                .filter(|f| f.ifd_num == exif::In::PRIMARY && !is_layout_tag(f.tag))
+                .filter(|f| {
+                    options.policy != MetadataPolicy::StripLocation
+                        || f.tag.context() != exif::Context::Gps
+                })
+                .cloned()
+                .collect();
+        }
+        xmp = read_xmp(source).filter(|packet| {
+            let text = String::from_utf8_lossy(packet);
+            options.policy != MetadataPolicy::StripLocation
+                || !XMP_LOCATION_MARKERS.iter().any(|m| text.contains(m))
+        });
+    }
+
+    if options.overrides() {
+        xmp = None;
+        let overrides = [
+            (exif::Tag::Artist, &options.author),
+            (exif::Tag::Copyright, &options.copyright),
+        ];
+        for (tag, value) in overrides {
+            if let Some(value) = value {
+                fields.retain(|f| f.tag != tag);
+                fields.push(ascii_field(tag, value));
+            }
+        }
+    }
+
+    // The pixels are written upright
+    fields.retain(|f| f.tag != exif::Tag::Orientation);
+    let exif = if fields.is_empty() {
+        None
+    } else {
+        fields.push(exif::Field {
+            tag: exif::Tag::Orientation,
+            ifd_num: exif::In::PRIMARY,
+            value: exif::Value::Short(vec![1]),
+        });
+        let mut writer = exif::experimental::Writer::new();
+        for field in &fields {
+            writer.push_field(field);
+        }
+        let mut out = std::io::Cursor::new(Vec::new());
+        writer
+            .write(&mut out, false)
+            .map_err(|e| format!("Failed to write EXIF: {}", e))?;
+        Some(out.into_inner())
+    };
+
+    Ok(Metadata { exif, xmp })
+}
+
+/// Adds `metadata` to an encoded image. Formats without metadata support are
+/// returned unchanged.
+pub fn embed(format: &str, data: Vec<u8>, metadata: &Metadata) -> Result<Vec<u8>, String> {
+    if metadata.is_empty() {
+        return Ok(data);
+    }
+    match format {
+        "jpg" | "jpeg" => embed_jpeg(&data, metadata),
+        "png" => embed_png(&data, metadata),
+        "webp" => embed_webp(&data, metadata),
+        "avif" => embed_avif(&data, metadata),
+        _ => Ok(data),
+    }
+}
+
+/// The XMP packet of a JPEG, PNG or WebP file.
+fn read_xmp(data: &[u8]) -> Option<Vec<u8>> {
+    if data.starts_with(&[0xFF, 0xD8]) {
+        jpeg_segments(data).into_iter().find_map(|(marker, range)| {
+            let payload = &data[range];
+            (marker == 0xE1 && payload.starts_with(XMP_JPEG_HEADER))
+                .then(|| payload[XMP_JPEG_HEADER.len()..].to_vec())
+        })
+    } else if data.starts_with(PNG_SIGNATURE) {
+        png_chunks(data).into_iter().find_map(|(kind, range)| {
+            let payload = &data[range];
+            if &kind != b"iTXt" || !payload.starts_with(XMP_PNG_KEYWORD) {
+                return None;
+            }
+            // keyword\0, compression flag, method, language\0, translated keyword\0
+            let rest = payload.get(XMP_PNG_KEYWORD.len() + 1..)?;
+            if rest.first() != Some(&0) {
+                return None;
+            }
+            let rest = rest.get(2..)?;
+            let language_end = rest.iter().position(|&b| b == 0)?;
+            let rest = &rest[language_end + 1..];
+            let translated_end = rest.iter().position(|&b| b == 0)?;
+            Some(rest[translated_end + 1..].to_vec())
+        })
+    } else if is_webp(data) {
+        webp_chunks(data)
+            .into_iter()
+            .find(|(kind, _)| kind == b"XMP ")
+            .map(|(_, range)| data[range].to_vec())
+    } else {
+        None
+    }
+}
+
+// JPEG
+
+/// Marker and payload of every segment before the scan data.
+fn jpeg_segments(data: &[u8]) -> Vec<(u8, Range<usize>)> {
+    let mut segments = Vec::new();
+    let mut pos = 2;
+    while pos + 4 <= data.len() && data[pos] == 0xFF {
+        let marker = data[pos + 1];
+        if marker == 0xFF {
+            // Fill byte
+            pos += 1;
+            continue;
+        }
+        if marker == 0xDA || marker == 0xD9 {
+            break;
+        }
+        let len = u16::from_be_bytes([data[pos + 2], data[pos + 3]]) as usize;
+        if len < 2 || pos + 2 + len > data.len() {
+            break;
+        }
+        segments.push((marker, pos + 4..pos + 2 + len));
+        pos += 2 + len;
+    }
+    segments
+}
+
+/// `data` without the EXIF, XMP, comment and other application segments
+/// [`super::jpeg::optimize`] drops too; everything else is copied byte for byte.
+pub(super) fn strip_jpeg(data: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(data.len());
+    let mut pos = 0;
+    for (marker, range) in jpeg_segments(data) {
+        if matches!(marker, 0xE1 | 0xE3..=0xED | 0xEF | 0xFE) {
+            out.extend_from_slice(&data[pos..range.start - 4]);
+            pos = range.end;
+        }
+    }
+    out.extend_from_slice(&data[pos..]);
+    out
+}
+
+fn jpeg_app1(header: &[u8], payload: &[u8]) -> Result<Vec<u8>, String> {
+    let len = 2 + header.len() + payload.len();
+    if len > u16::MAX as usize {
+        return Err("Metadata is too large for a JPEG segment".to_string());
+    }
+    let mut segment = vec![0xFF, 0xE1];
+    segment.extend_from_slice(&(len as u16).to_be_bytes());
+    segment.extend_from_slice(header);
+    segment.extend_from_slice(payload);
+    Ok(segment)
+}
+
+/// APP1 segments go after SOI and the JFIF APP0, where readers expect them.
+fn embed_jpeg(data: &[u8], metadata: &Metadata) -> Result<Vec<u8>, String> {
+    if !data.starts_with(&[0xFF, 0xD8]) {
+        return Err("Not a JPEG file".to_string());
+    }
+    let insert_at = jpeg_segments(data)
+        .into_iter()
+        .take_while(|(marker, _)| *marker == 0xE0)
+        .last()
+        .map_or(2, |(_, range)| range.end);
+
+    let mut out = data[..insert_at].to_vec();
+    if let Some(exif) = &metadata.exif {
+        out.extend(jpeg_app1(b"Exif\0\0", exif)?);
+    }
+    if let Some(xmp) = &metadata.xmp {
+        out.extend(jpeg_app1(XMP_JPEG_HEADER, xmp)?);
+    }
+    out.extend_from_slice(&data[insert_at..]);
+    Ok(out)
+}
+
+// PNG
+
+/// Type and data range of every chunk.
+fn png_chunks(data: &[u8]) -> Vec<([u8; 4], Range<usize>)> {
+    let mut chunks = Vec::new();
+    let mut pos = PNG_SIGNATURE.len();
+    while pos + 12 <= data.len() {
+        let len =
+            u32::from_be_bytes([data[pos], data[pos + 1], data[pos + 2], data[pos + 3]]) as usize;
+        let kind = [data[pos + 4], data[pos + 5], data[pos + 6], data[pos + 7]];
+        if pos + 12 + len > data.len() {
+            break;
+        }
+        chunks.push((kind, pos + 8..pos + 8 + len));
+        pos += 12 + len;
+    }
+    chunks
+}
+
+fn crc32(bytes: &[u8]) -> u32 {
+    let mut crc = 0xFFFF_FFFFu32;
+    for &byte in bytes {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 {
+                (crc >> 1) ^ 0xEDB8_8320
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    !crc
+}
+
+fn png_chunk(kind: &[u8; 4], payload: &[u8]) -> Vec<u8> {
+    let mut chunk = (payload.len() as u32).to_be_bytes().to_vec();
+    chunk.extend_from_slice(kind);
+    chunk.extend_from_slice(payload);
+    let crc = crc32(&chunk[4..]);
+    chunk.extend_from_slice(&crc.to_be_bytes());
+    chunk
+}
+
+/// `eXIf` and an uncompressed XMP `iTXt` go before the first `IDAT`, or the first
+/// APNG `fcTL` that precedes it.
+fn embed_png(data: &[u8], metadata: &Metadata) -> Result<Vec<u8>, String> {
+    let insert_at = png_chunks(data)
+        .into_iter()
+        .find(|(kind, _)| kind == b"IDAT" || kind == b"fcTL")
+        .map(|(_, range)| range.start - 8)
+        .ok_or_else(|| "Not a PNG file".to_string())?;
+
+    let mut out = data[..insert_at].to_vec();
+    if let Some(exif) = &metadata.exif {
+        out.extend(png_chunk(b"eXIf", exif));
+    }
+    if let Some(xmp) = &metadata.xmp {
+        let mut payload = XMP_PNG_KEYWORD.to_vec();
+        // Terminator, no compression, method, empty language and translated keyword
+        payload.extend_from_slice(&[0, 0, 0, 0, 0]);
+        payload.extend_from_slice(xmp);
+        out.extend(png_chunk(b"iTXt", &payload));
+    }
+    out.extend_from_slice(&data[insert_at..]);
+    Ok(out)
+}
+
+// WebP
+
+fn is_webp(data: &[u8]) -> bool {
+    data.len() >= 12 && &data[..4] == b"RIFF" && &data[8..12] == b"WEBP"
+}
+
+/// FourCC and data range of every chunk.
+pub(super) fn webp_chunks(data: &[u8]) -> Vec<([u8; 4], Range<usize>)> {
+    let mut chunks = Vec::new();
+    let mut pos = 12;
+    while pos + 8 <= data.len() {
+        let kind = [data[pos], data[pos + 1], data[pos + 2], data[pos + 3]];
+        let len = u32::from_le_bytes([data[pos + 4], data[pos + 5], data[pos + 6], data[pos + 7]])
+            as usize;
+        if pos + 8 + len > data.len() {
+            break;
+        }
+        chunks.push((kind, pos + 8..pos + 8 + len));
+        // Chunks are padded to an even size
+        pos += 8 + len + (len & 1);
+    }
+    chunks
+}
+
+pub(super) fn webp_chunk(kind: &[u8; 4], payload: &[u8]) -> Vec<u8> {
+    let mut chunk = kind.to_vec();
+    chunk.extend_from_slice(&(payload.len() as u32).to_le_bytes());
+    chunk.extend_from_slice(payload);
+    if payload.len() & 1 == 1 {
+        chunk.push(0);
+    }
+    chunk
+}
+
+/// Canvas size and whether there's alpha, from a simple-format `VP8 `/`VP8L` chunk.
+fn webp_canvas(kind: &[u8; 4], payload: &[u8]) -> Option<(u32, u32, bool)> {
+    match kind {
+        b"VP8 " if payload.len() >= 10 => {
+            let width = u16::from_le_bytes([payload[6], payload[7]]) & 0x3FFF;
+            let height = u16::from_le_bytes([payload[8], payload[9]]) & 0x3FFF;
+            Some((width as u32, height as u32, false))
+        }
+        b"VP8L" if payload.len() >= 5 => {
+            let bits = u32::from_le_bytes([payload[1], payload[2], payload[3], payload[4]]);
+            let width = (bits & 0x3FFF) + 1;
+            let height = ((bits >> 14) & 0x3FFF) + 1;
+            Some((width, height, bits >> 28 & 1 == 1))
+        }
+        _ => None,
+    }
+}
+
+/// Metadata needs the extended format: a `VP8X` header with the EXIF/XMP flags,
+/// then the image chunks, then `EXIF` and `XMP `.
+fn embed_webp(data: &[u8], metadata: &Metadata) -> Result<Vec<u8>, String> {
+    if !is_webp(data) {
+        return Err("Not a WebP file".to_string());
+    }
+    let chunks = webp_chunks(data);
+    let (first_kind, first_range) = chunks
+        .first()
+        .cloned()
+        .ok_or_else(|| "Empty WebP file".to_string())?;
+
+    let mut header = if &first_kind == b"VP8X" {
+        data[first_range].to_vec()
+    } else {
+        let (width, height, alpha) = webp_canvas(&first_kind, &data[first_range])
+            .ok_or_else(|| "Unsupported WebP layout".to_string())?;
+        let mut header = vec![if alpha { 0x10 } else { 0 }, 0, 0, 0];
+        header.extend_from_slice(&(width - 1).to_le_bytes()[..3]);
+        header.extend_from_slice(&(height - 1).to_le_bytes()[..3]);
+        header
+    };
+    if metadata.exif.is_some() {
+        header[0] |= 0x08;
+    }
+    if metadata.xmp.is_some() {
+        header[0] |= 0x04;
+    }
+
+    let mut body = b"WEBP".to_vec();
+    body.extend(webp_chunk(b"VP8X", &header));
+    for (kind, range) in &chunks {
+        if !matches!(kind, b"VP8X" | b"EXIF" | b"XMP ") {
+            body.extend(webp_chunk(kind, &data[range.clone()]));
+        }
+    }
+    if let Some(exif) = &metadata.exif {
+        body.extend(webp_chunk(b"EXIF", exif));
+    }
+    if let Some(xmp) = &metadata.xmp {
+        body.extend(webp_chunk(b"XMP ", xmp));
+    }
+
+    let mut out = b"RIFF".to_vec();
+    out.extend_from_slice(&(body.len() as u32).to_le_bytes());
+    out.extend(body);
+    Ok(out)
+}
+
+// AVIF (HEIF)
+
+struct Bmff {
+    kind: [u8; 4],
+    start: usize,
+    /// Where the content starts, after size, type and any large size
+    body: usize,
+    end: usize,
+}
+
+/// The boxes laid out back to back in `data[range]`.
+fn bmff_boxes(data: &[u8], range: Range<usize>) -> Result<Vec<Bmff>, String> {
+    let malformed = || "Malformed AVIF file".to_string();
+    let mut boxes = Vec::new();
+    let mut pos = range.start;
+    while pos + 8 <= range.end {
+        let size = u32::from_be_bytes(data[pos..pos + 4].try_into().unwrap()) as usize;
+        let kind: [u8; 4] = data[pos + 4..pos + 8].try_into().unwrap();
+        let (size, header) = match size {
+            0 => (range.end - pos, 8),
+            1 => {
+                let large = data.get(pos + 8..pos + 16).ok_or_else(malformed)?;
+                (u64::from_be_bytes(large.try_into().unwrap()) as usize, 16)
+            }
+            size => (size, 8),
+        };
+        if size < header || pos + size > range.end {
+            return Err(malformed());
+        }
+        boxes.push(Bmff {
+            kind,
+            start: pos,
+            body: pos + header,
+            end: pos + size,
+        });
+        pos += size;
+    }
+    Ok(boxes)
+}
+
+fn bmff_box(kind: &[u8; 4], body: &[u8]) -> Vec<u8> {
+    let mut out = ((body.len() + 8) as u32).to_be_bytes().to_vec();
+    out.extend_from_slice(kind);
+    out.extend_from_slice(body);
+    out
+}
+
+/// Big-endian reads that fail instead of panicking on truncated boxes.
+struct Cursor<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl Cursor<'_> {
+    fn take(&mut self, n: usize) -> Result<&[u8], String> {
+        let bytes = self
+            .data
+            .get(self.pos..self.pos + n)
+            .ok_or_else(|| "Malformed AVIF file".to_string())?;
+        self.pos += n;
+        Ok(bytes)
+    }
+
+    fn uint(&mut self, size: usize) -> Result<u64, String> {
+        Ok(self
+            .take(size)?
+            .iter()
+            .fold(0u64, |acc, &b| (acc << 8) | b as u64))
+    }
+}
+
+struct ItemLocation {
+    id: u32,
+    construction_method: u16,
+    data_reference_index: u16,
+    /// Absolute offset (base offset included) and length
+    extents: Vec<(u64, u64)>,
+}
+
+fn parse_iloc(body: &[u8]) -> Result<(u8, Vec<ItemLocation>), String> {
+    let mut r = Cursor { data: body, pos: 0 };
+    let version = r.uint(1)? as u8;
+    r.uint(3)?; // flags
+    let sizes = r.uint(1)?;
+    let (offset_size, length_size) = ((sizes >> 4) as usize, (sizes & 0xF) as usize);
+    let sizes = r.uint(1)?;
+    let base_offset_size = (sizes >> 4) as usize;
+    let index_size = if version >= 1 {
+        (sizes & 0xF) as usize
+    } else {
+        0
+    };
+    if [offset_size, length_size, base_offset_size, index_size]
+        .iter()
+        .any(|s| ![0, 4, 8].contains(s))
+    {
+        return Err("Unsupported AVIF item locations".to_string());
+    }
+
+    let count = r.uint(if version < 2 { 2 } else { 4 })?;
+    let mut items = Vec::new();
+    for _ in 0..count {
+        let id = r.uint(if version < 2 { 2 } else { 4 })? as u32;
+        let construction_method = if version >= 1 {
+            r.uint(2)? as u16 & 0xF
+        } else {
+            0
+        };
+        let data_reference_index = r.uint(2)? as u16;
+        let base = r.uint(base_offset_size)?;
+        let extent_count = r.uint(2)?;
+        let mut extents = Vec::new();
+        for _ in 0..extent_count {
+            r.uint(index_size)?;
+            let offset = r.uint(offset_size)?;
+            let length = r.uint(length_size)?;
+            extents.push((base + offset, length));
+        }
+        items.push(ItemLocation {
+            id,
+            construction_method,
+            data_reference_index,
+            extents,
+        });
+    }
+    Ok((version, items))
+}
+
+/// `iloc` with 32-bit offsets and lengths and no base offsets.
+fn write_iloc(version: u8, items: &[ItemLocation]) -> Result<Vec<u8>, String> {
+    let too_large = || "AVIF file is too large for metadata".to_string();
+    let mut body = vec![version, 0, 0, 0, 0x44, 0];
+    let wide = version >= 2;
+    let push_id = |body: &mut Vec<u8>, id: u32| {
+        if wide {
+            body.extend_from_slice(&id.to_be_bytes());
+        } else {
+            body.extend_from_slice(&(id as u16).to_be_bytes());
+        }
+    };
+    push_id(&mut body, items.len() as u32);
+    for item in items {
+        if !wide && item.id > u16::MAX as u32 {
+            return Err(too_large());
+        }
+        push_id(&mut body, item.id);
+        if version >= 1 {
+            body.extend_from_slice(&item.construction_method.to_be_bytes());
+        }
+        body.extend_from_slice(&item.data_reference_index.to_be_bytes());
+        body.extend_from_slice(&(item.extents.len() as u16).to_be_bytes());
+        for &(offset, length) in &item.extents {
+            let offset = u32::try_from(offset).map_err(|_| too_large())?;
+            let length = u32::try_from(length).map_err(|_| too_large())?;
+            body.extend_from_slice(&offset.to_be_bytes());
+            body.extend_from_slice(&length.to_be_bytes());
+        }
+    }
+    Ok(bmff_box(b"iloc", &body))
+}
+
+fn infe(id: u32, item_type: &[u8; 4], content_type: Option<&str>) -> Vec<u8> {
+    let mut body = if id > u16::MAX as u32 {
+        let mut body = vec![3, 0, 0, 0];
+        body.extend_from_slice(&id.to_be_bytes());
+        body
+    } else {
+        let mut body = vec![2, 0, 0, 0];
+        body.extend_from_slice(&(id as u16).to_be_bytes());
+        body
+    };
+    // Protection index, type and an empty name
+    body.extend_from_slice(&[0, 0]);
+    body.extend_from_slice(item_type);
+    body.push(0);
+    if let Some(content_type) = content_type {
+        body.extend_from_slice(content_type.as_bytes());
+        body.push(0);
+    }
+    bmff_box(b"infe", &body)
+}
+
+/// Adds the EXIF and XMP as items describing (`cdsc`) the primary image, with
+/// their data in a new `mdat` at the end; offsets of boxes after `meta` are moved
+/// by however much `meta` grew.
+fn embed_avif(data: &[u8], metadata: &Metadata) -> Result<Vec<u8>, String> {
+    let top = bmff_boxes(data, 0..data.len())?;
+    let meta = top
+        .iter()
+        .find(|b| &b.kind == b"meta")
+        .ok_or_else(|| "AVIF file has no meta box".to_string())?;
+    let children = bmff_boxes(data, meta.body + 4..meta.end)?;
+    let child = |kind: &[u8; 4]| children.iter().find(|b| &b.kind == kind);
+
+    let pitm = child(b"pitm").ok_or_else(|| "AVIF file has no primary item".to_string())?;
+    let primary = {
+        let mut r = Cursor {
+            data: &data[pitm.body..pitm.end],
+            pos: 0,
+        };
+        let version = r.uint(1)?;
+        r.uint(3)?;
+        r.uint(if version == 0 { 2 } else { 4 })? as u32
+    };
+    let iloc = child(b"iloc").ok_or_else(|| "AVIF file has no item locations".to_string())?;
+    let (iloc_version, mut locations) = parse_iloc(&data[iloc.body..iloc.end])?;
+    let iinf = child(b"iinf").ok_or_else(|| "AVIF file has no item info".to_string())?;
+    let iref = child(b"iref");
+
+    let mut payloads: Vec<(&[u8; 4], Option<&str>, Vec<u8>)> = Vec::new();
+    if let Some(exif) = &metadata.exif {
+        // Offset from here to the TIFF header, then the block
+        let mut payload = vec![0, 0, 0, 0];
+        payload.extend_from_slice(exif);
+        payloads.push((b"Exif", None, payload));
+    }
+    if let Some(xmp) = &metadata.xmp {
+        payloads.push((b"mime", Some("application/rdf+xml"), xmp.clone()));
+    }
+    let first_id = locations.iter().map(|l| l.id).max().unwrap_or(0) + 1;
+    let ids: Vec<u32> = (first_id..).take(payloads.len()).collect();
+
+    // iinf keeps its entries and gets ours appended
+    let iinf_body = &data[iinf.body..iinf.end];
+    let mut r = Cursor {
+        data: iinf_body,
+        pos: 0,
+    };
+    let count_size = if r.uint(1)? == 0 { 2 } else { 4 };
+    r.uint(3)?;
+    let count = r.uint(count_size)? as usize + payloads.len();
+    let mut new_iinf = iinf_body[..4].to_vec();
+    new_iinf.extend_from_slice(&count.to_be_bytes()[8 - count_size..]);
+    new_iinf.extend_from_slice(&iinf_body[4 + count_size..]);
+    for (id, (item_type, content_type, _)) in ids.iter().zip(&payloads) {
+        new_iinf.extend(infe(*id, item_type, *content_type));
+    }
+    let new_iinf = bmff_box(b"iinf", &new_iinf);
+
+    let mut new_iref = match iref {
+        Some(iref) if iref.end - iref.body >= 4 => data[iref.body..iref.end].to_vec(),
+        Some(_) => return Err("Malformed AVIF file".to_string()),
+        None => vec![0, 0, 0, 0],
+    };
+    let wide_refs = new_iref[0] != 0;
+    for id in &ids {
+        let mut body = Vec::new();
+        if wide_refs {
+            body.extend_from_slice(&id.to_be_bytes());
+            body.extend_from_slice(&1u16.to_be_bytes());
+            body.extend_from_slice(&primary.to_be_bytes());
+        } else {
+            body.extend_from_slice(&(*id as u16).to_be_bytes());
+            body.extend_from_slice(&1u16.to_be_bytes());
+            body.extend_from_slice(&(primary as u16).to_be_bytes());
+        }
+        new_iref.extend(bmff_box(b"cdsc", &body));
+    }
+    let new_iref = bmff_box(b"iref", &new_iref);
+
+    let build_meta = |locations: &[ItemLocation]| -> Result<Vec<u8>, String> {
+        let mut body = data[meta.body..meta.body + 4].to_vec();
+        for b in &children {
+            match &b.kind {
+                b"iloc" => body.extend(write_iloc(iloc_version, locations)?),
+                b"iinf" => {
+                    body.extend_from_slice(&new_iinf);
+                    if iref.is_none() {
+                        body.extend_from_slice(&new_iref);
+                    }
+                }
+                b"iref" => body.extend_from_slice(&new_iref),
+                _ => body.extend_from_slice(&data[b.start..b.end]),
+            }
+        }
+        Ok(bmff_box(b"meta", &body))
+    };
+
+    // Sizes don't depend on offset values, so measure first and place after
+    for (id, (_, _, payload)) in ids.iter().zip(&payloads) {
+        locations.push(ItemLocation {
+            id: *id,
+            construction_method: 0,
+            data_reference_index: 0,
+            extents: vec![(0, payload.len() as u64)],
+        });
+    }
+    let meta_len = build_meta(&locations)?.len();
+    let delta = meta_len as i64 - (meta.end - meta.start) as i64;
+    let mut offset = (data.len() as i64 + delta) as u64 + 8;
+    let added = locations.len() - payloads.len();
+    for (i, location) in locations.iter_mut().enumerate() {
+        if i >= added {
+            location.extents[0].0 = offset;
+            offset += location.extents[0].1;
+        } else if location.construction_method == 0 {
+            for extent in &mut location.extents {
+                if extent.0 >= meta.end as u64 {
+                    extent.0 = (extent.0 as i64 + delta) as u64;
+                }
+            }
+        }
+    }
+
+    let mut out = data[..meta.start].to_vec();
+    out.extend(build_meta(&locations)?);
+    out.extend_from_slice(&data[meta.end..]);
+    let mdat: Vec<u8> = payloads.into_iter().flat_map(|(_, _, p)| p).collect();
+    out.extend(bmff_box(b"mdat", &mdat));
+    Ok(out)
+}
+
+// Inspection
+
+#[derive(Serialize, Debug)]
+pub struct MetadataEntry {
+    /// "tiff", "exif", "gps" or "interop", or "thumbnail" for the thumbnail IFD
+    pub group: String,
+    pub tag: String,
+    pub value: String,
+}
+
+#[derive(Serialize, Debug)]
+pub struct GpsLocation {
+    pub latitude: f64,
+    pub longitude: f64,
+}
+
+/// Summary and full tag list for `inspect_image_metadata`.
+#[derive(Serialize, Debug, Default)]
+pub struct ImageMetadata {
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+    pub orientation: u32,
+    pub capture_date: Option<String>,
+    pub camera: Option<String>,
+    pub author: Option<String>,
+    pub copyright: Option<String>,
+    pub location: Option<GpsLocation>,
+    pub xmp: Option<String>,
+    pub exif: Vec<MetadataEntry>,
+}
+
+fn ascii_value(exif: &exif::Exif, tag: exif::Tag) -> Option<String> {
+    match &exif.get_field(tag, exif::In::PRIMARY)?.value {
+        exif::Value::Ascii(parts) => {
+            let text = parts
+                .iter()
+                .map(|p| String::from_utf8_lossy(p).trim().to_string())
+                .collect::<Vec<_>>()
+                .join(" ");
+            (!text.is_empty()).then_some(text)
+        }
+        _ => None,
+    }
+}
+
+/// Decimal degrees from the degrees/minutes/seconds rationals and the N/S/E/W ref.
+fn gps_coordinate(exif: &exif::Exif, tag: exif::Tag, ref_tag: exif::Tag) -> Option<f64> {
+    let exif::Value::Rational(parts) = &exif.get_field(tag, exif::In::PRIMARY)?.value else {
+        return None;
+    };
+    let degrees = parts
+        .iter()
+        .zip([1.0, 60.0, 3600.0])
+        .map(|(r, div)| r.to_f64() / div)
+        .sum::<f64>();
+    let negative = ascii_value(exif, ref_tag).is_some_and(|r| r == "S" || r == "W");
+    degrees
+        .is_finite()
+        .then_some(if negative { -degrees } else { degrees })
+}
+
+pub fn inspect(path: &Path) -> Result<ImageMetadata, String> {
+    let data = std::fs::read(path).map_err(|e| format!("Failed to read image: {}", e))?;
+    let (width, height) = match image::image_dimensions(path) {
+        Ok((w, h)) => (Some(w), Some(h)),
+        Err(_) => (None, None),
+    };
+    let mut metadata = ImageMetadata {
+        width,
+        height,
+        orientation: 1,
+        xmp: read_xmp(&data).map(|x| String::from_utf8_lossy(&x).to_string()),
+        ..Default::default()
+    };
+
+    let Ok(exif) = exif::Reader::new().read_from_container(&mut std::io::Cursor::new(&data)) else {
+        return Ok(metadata);
+    };
+    metadata.orientation = exif
+        .get_field(exif::Tag::Orientation, exif::In::PRIMARY)
+        .and_then(|f| f.value.get_uint(0))
+        .unwrap_or(1);
+    metadata.capture_date = ascii_value(&exif, exif::Tag::DateTimeOriginal)
+        .or_else(|| ascii_value(&exif, exif::Tag::DateTime));
+    metadata.camera = match (
+        ascii_value(&exif, exif::Tag::Make),
+        ascii_value(&exif, exif::Tag::Model),
+    ) {
+        (Some(make), Some(model)) if model.starts_with(&make) => Some(model),
+        (Some(make), Some(model)) => Some(format!("{} {}", make, model)),
+        (make, model) => make.or(model),
+    };
+    metadata.author = ascii_value(&exif, exif::Tag::Artist);
+    metadata.copyright = ascii_value(&exif, exif::Tag::Copyright);
+    metadata.location = gps_coordinate(&exif, exif::Tag::GPSLatitude, exif::Tag::GPSLatitudeRef)
+        .zip(gps_coordinate(
+            &exif,
+            exif::Tag::GPSLongitude,
+            exif::Tag::GPSLongitudeRef,
+        ))
+        .map(|(latitude, longitude)| GpsLocation {
+            latitude,
+            longitude,
+        });
+    metadata.exif = exif
+        .fields()
+        .map(|f| MetadataEntry {
+            group: if f.ifd_num == exif::In::THUMBNAIL {
+                "thumbnail".to_string()
+            } else {
+                format!("{:?}", f.tag.context()).to_lowercase()
+            },
+            tag: f.tag.to_string(),
+            value: f.display_value().with_unit(&exif).to_string(),
+        })
+        .collect();
+    Ok(metadata)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use exif::{Field, In, Rational, Tag, Value};
+
+    const XMP: &[u8] = b"<x:xmpmeta xmlns:x=\"adobe:ns:meta/\"><rdf:RDF/></x:xmpmeta>";
+    const XMP_WITH_GPS: &[u8] =
+        b"<x:xmpmeta xmlns:x=\"adobe:ns:meta/\"><exif:GPSLatitude>48,30N</exif:GPSLatitude></x:xmpmeta>";
+
+    fn field(tag: Tag, value: Value) -> Field {
+        Field {
+            tag,
+            ifd_num: In::PRIMARY,
+            value,
+        }
+    }
+
+    fn ascii(text: &str) -> Value {
+        Value::Ascii(vec![text.as_bytes().to_vec()])
+    }
+
+    fn degrees(d: u32, m: u32) -> Value {
+        Value::Rational(vec![
+            Rational { num: d, denom: 1 },
+            Rational { num: m, denom: 1 },
+            Rational { num: 0, denom: 1 },
+        ])
+    }
+
+    /// A camera's EXIF: make, model, capture date, a rotated orientation and GPS
+    fn camera_exif() -> Vec<u8> {
+        let fields = [
+            field(Tag::Make, ascii("Goblin")),
+            field(Tag::Model, ascii("Goblin Cam 2")),
+            field(Tag::DateTimeOriginal, ascii("2024:05:01 10:20:30")),
+            field(Tag::Orientation, Value::Short(vec![6])),
+            field(Tag::GPSLatitudeRef, ascii("N")),
+            field(Tag::GPSLatitude, degrees(48, 30)),
+            field(Tag::GPSLongitudeRef, ascii("W")),
+            field(Tag::GPSLongitude, degrees(2, 15)),
+        ];
+        let mut writer = exif::experimental::Writer::new();
+        for field in &fields {
+            writer.push_field(field);
+        }
+        let mut out = std::io::Cursor::new(Vec::new());
+        writer.write(&mut out, false).unwrap();
+        out.into_inner()
+    }
+
+    fn picture(alpha: bool) -> image::DynamicImage {
+        let img = image::RgbaImage::from_fn(16, 12, |x, y| {
+            image::Rgba([(x * 16) as u8, (y * 20) as u8, 128, 255 - (x * 8) as u8])
+        });
+        if alpha {
+            image::DynamicImage::ImageRgba8(img)
+        } else {
+            image::DynamicImage::ImageRgb8(image::DynamicImage::ImageRgba8(img).into_rgb8())
+        }
+    }
+
+    fn png(img: &image::DynamicImage) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        img.write_to(
+            &mut std::io::Cursor::new(&mut bytes),
+            image::ImageFormat::Png,
+        )
+        .unwrap();
+        bytes
+    }
+
+    /// A JPEG straight from the camera, with `camera_exif` and location XMP
+    fn camera_jpeg() -> Vec<u8> {
+        let jpeg = super::super::encode_lossy(&picture(false), "jpg", 90).unwrap();
+        let metadata = Metadata {
+            exif: Some(camera_exif()),
+            xmp: Some(XMP_WITH_GPS.to_vec()),
+        };
+        embed("jpg", jpeg, &metadata).unwrap()
+    }
+
+    fn read_exif(data: &[u8]) -> exif::Exif {
+        exif::Reader::new()
+            .read_from_container(&mut std::io::Cursor::new(data))
+            .unwrap()
+    }
+
+    fn text(exif: &exif::Exif, tag: Tag) -> Option<String> {
+        ascii_value(exif, tag)
+    }
+
+    fn assert_camera_tags(data: &[u8]) {
+        let exif = read_exif(data);
+        assert_eq!(text(&exif, Tag::Make).as_deref(), Some("Goblin"));
+        assert_eq!(
+            text(&exif, Tag::DateTimeOriginal).as_deref(),
+            Some("2024:05:01 10:20:30")
+        );
+        assert!(gps_coordinate(&exif, Tag::GPSLatitude, Tag::GPSLatitudeRef) == Some(48.5));
+    }
+
+    fn both() -> Metadata {
+        Metadata {
+            exif: Some(camera_exif()),
+            xmp: Some(XMP.to_vec()),
+        }
+    }
+
+    /// Embeds EXIF and XMP, reads them back and decodes the result to the same pixels.
+    fn round_trip(format: &str, encoded: Vec<u8>) -> Vec<u8> {
+        let before = image::load_from_memory(&encoded).unwrap();
+        let out = embed(format, encoded, &both()).unwrap();
+        assert_camera_tags(&out);
+        assert_eq!(read_xmp(&out).as_deref(), Some(XMP));
+        let after = image::load_from_memory(&out).unwrap();
+        assert_eq!(after.to_rgba8(), before.to_rgba8());
+        out
+    }
+
+    #[test]
+    fn jpeg_round_trip() {
+        let jpeg = super::super::encode_lossy(&picture(false), "jpg", 90).unwrap();
+        let out = round_trip("jpg", jpeg);
+        // APP1s follow the JFIF APP0
+        let markers: Vec<u8> = jpeg_segments(&out).iter().map(|(m, _)| *m).collect();
+        assert_eq!(markers[..3], [0xE0, 0xE1, 0xE1]);
+    }
+
+    #[test]
+    fn png_round_trip() {
+        let out = round_trip("png", png(&picture(true)));
+        let kinds: Vec<[u8; 4]> = png_chunks(&out).into_iter().map(|(k, _)| k).collect();
+        let position = |kind: &[u8; 4]| kinds.iter().position(|k| k == kind).unwrap();
+        assert!(position(b"eXIf") < position(b"IDAT"));
+        assert!(position(b"iTXt") < position(b"IDAT"));
+        // Every chunk's CRC still checks out
+        for (kind, range) in png_chunks(&out) {
+            let crc = u32::from_be_bytes(out[range.end..range.end + 4].try_into().unwrap());
+            let mut checked = kind.to_vec();
+            checked.extend_from_slice(&out[range]);
+            assert_eq!(crc32(&checked), crc);
+        }
+    }
+
+    #[test]
+    fn webp_round_trip() {
+        use image::codecs::webp::WebPEncoder;
+
+        let lossless = |img: &image::DynamicImage| {
+            let mut bytes = Vec::new();
+            WebPEncoder::new_lossless(&mut bytes)
+                .encode(img.as_bytes(), img.width(), img.height(), img.color())
+                .unwrap();
+            bytes
+        };
+        // Simple lossy (VP8), lossless with alpha (VP8L) and already extended (VP8X)
+        for (encoded, alpha) in [
+            (
+                super::super::encode_lossy(&picture(false), "webp", 90).unwrap(),
+                false,
+            ),
+            (lossless(&picture(true)), true),
+            (
+                super::super::encode_lossy(&picture(true), "webp", 90).unwrap(),
+                true,
+            ),
+        ] {
+            let out = round_trip("webp", encoded);
+            let chunks = webp_chunks(&out);
+            assert_eq!(&chunks[0].0, b"VP8X");
+            let flags = out[chunks[0].1.start];
+            assert_eq!(flags & 0x0C, 0x0C, "EXIF and XMP flags");
+            assert_eq!(flags & 0x10 != 0, alpha, "alpha flag");
+            // Canvas is 16x12, stored minus one
+            assert_eq!(out[chunks[0].1.start + 4], 15);
+            assert_eq!(out[chunks[0].1.start + 7], 11);
+            let riff = u32::from_le_bytes(out[4..8].try_into().unwrap()) as usize;
+            assert_eq!(riff + 8, out.len());
+        }
+    }
+
+    /// Every item of an AVIF file and its bytes, by id.
+    fn avif_items(data: &[u8]) -> Vec<(u32, Vec<u8>)> {
+        let top = bmff_boxes(data, 0..data.len()).unwrap();
+        let meta = top.iter().find(|b| &b.kind == b"meta").unwrap();
+        let children = bmff_boxes(data, meta.body + 4..meta.end).unwrap();
+        let iloc = children.iter().find(|b| &b.kind == b"iloc").unwrap();
+        let (_, items) = parse_iloc(&data[iloc.body..iloc.end]).unwrap();
+        items
+            .iter()
+            .map(|item| {
+                let bytes = item
+                    .extents
+                    .iter()
+                    .flat_map(|&(offset, len)| &data[offset as usize..(offset + len) as usize])
+                    .copied()
+                    .collect();
+                (item.id, bytes)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn avif_round_trip() {
+        // With alpha the file already has an `iref` (the alpha plane's `auxl`)
+        for alpha in [false, true] {
+            let avif = super::super::encode_lossy(&picture(alpha), "avif", 80).unwrap();
+            let before = avif_items(&avif);
+            let out = embed("avif", avif, &both()).unwrap();
+            assert_camera_tags(&out);
+
+            // The coded image items are untouched, even though `meta` grew
+            let after = avif_items(&out);
+            assert_eq!(after[..before.len()], before[..]);
+            let mut exif = vec![0, 0, 0, 0];
+            exif.extend(camera_exif());
+            assert_eq!(after[before.len()..].len(), 2);
+            assert_eq!(after[before.len()].1, exif);
+            assert_eq!(after[before.len() + 1].1, XMP);
+        }
+    }
+
+    #[test]
+    fn strip_location_keeps_the_date() {
+        let source = camera_jpeg();
+        let options = MetadataOptions {
+            policy: MetadataPolicy::StripLocation,
+            ..Default::default()
+        };
+        let metadata = collect(&source, &options).unwrap();
+        let exif = exif::Reader::new()
+            .read_raw(metadata.exif.unwrap())
+            .unwrap();
+        assert!(exif.fields().all(|f| f.tag.context() != exif::Context::Gps));
+        assert_eq!(
+            text(&exif, Tag::DateTimeOriginal).as_deref(),
+            Some("2024:05:01 10:20:30")
+        );
+        assert_eq!(text(&exif, Tag::Model).as_deref(), Some("Goblin Cam 2"));
+        // The pixels are written upright
+        let orientation = exif.get_field(Tag::Orientation, In::PRIMARY).unwrap();
+        assert_eq!(orientation.value.get_uint(0), Some(1));
+        // The XMP gives the place away too
+        assert!(metadata.xmp.is_none());
+
+        let options = MetadataOptions {
+            policy: MetadataPolicy::Preserve,
+            ..Default::default()
+        };
+        let metadata = collect(&source, &options).unwrap();
+        let exif = exif::Reader::new()
+            .read_raw(metadata.exif.unwrap())
+            .unwrap();
+        assert!(exif.get_field(Tag::GPSLatitude, In::PRIMARY).is_some());
+        assert_eq!(metadata.xmp.as_deref(), Some(XMP_WITH_GPS));
+
+        assert!(collect(&source, &MetadataOptions::default())
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn author_and_copyright_are_written() {
+        let options = MetadataOptions {
+            policy: MetadataPolicy::Strip,
+            author: Some("Ana".to_string()),
+            copyright: Some("(c) 2024 Ana".to_string()),
+        };
+        let metadata = collect(&camera_jpeg(), &options).unwrap();
+        let exif = exif::Reader::new()
+            .read_raw(metadata.exif.unwrap())
+            .unwrap();
+        assert_eq!(text(&exif, Tag::Artist).as_deref(), Some("Ana"));
+        assert_eq!(text(&exif, Tag::Copyright).as_deref(), Some("(c) 2024 Ana"));
+        // Nothing else from the source under the strip policy
+        assert!(exif.get_field(Tag::Make, In::PRIMARY).is_none());
+
+        // Overrides replace the source's own and drop its XMP
+        let options = MetadataOptions {
+            policy: MetadataPolicy::Preserve,
+            ..options
+        };
+        let metadata = collect(&camera_jpeg(), &options).unwrap();
+        assert!(metadata.xmp.is_none());
+        let exif = exif::Reader::new()
+            .read_raw(metadata.exif.unwrap())
+            .unwrap();
+        assert_eq!(text(&exif, Tag::Artist).as_deref(), Some("Ana"));
+        assert_eq!(text(&exif, Tag::Make).as_deref(), Some("Goblin"));
+    }
+
+    #[test]
+    fn inspect_reports_the_summary() {
+        let options = MetadataOptions {
+            policy: MetadataPolicy::Preserve,
+            author: Some("Ana".to_string()),
+            copyright: Some("(c) 2024 Ana".to_string()),
+        };
+        let metadata = collect(&camera_jpeg(), &options).unwrap();
+        let jpeg = super::super::encode_lossy(&picture(false), "jpg", 90).unwrap();
+        let path =
+            std::env::temp_dir().join(format!("taskgoblin-inspect-{}.jpg", std::process::id()));
+        std::fs::write(&path, embed("jpg", jpeg, &metadata).unwrap()).unwrap();
+
+        let report = inspect(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!((report.width, report.height), (Some(16), Some(12)));
+        assert_eq!(report.orientation, 1);
+        assert_eq!(report.author.as_deref(), Some("Ana"));
+        assert_eq!(report.copyright.as_deref(), Some("(c) 2024 Ana"));
+        assert_eq!(report.capture_date.as_deref(), Some("2024:05:01 10:20:30"));
+        // The model already starts with the make
+        assert_eq!(report.camera.as_deref(), Some("Goblin Cam 2"));
+        let location = report.location.unwrap();
+        assert_eq!((location.latitude, location.longitude), (48.5, -2.25));
+        assert!(report
+            .exif
+            .iter()
+            .any(|e| e.group == "gps" && e.tag == "GPSLatitude"));
+        assert!(report.xmp.is_none());
+    }
+}
@@ -0,0 +1,646 @@
+//! Image conversion shared by `process_image` and the batch command: decode, resize
+//! and encode to the requested format.
+
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+pub mod animation;
+pub mod batch;
+pub mod heif;
+pub mod jpeg;
+pub mod metadata;
+pub mod transform;
+pub mod watermark;
+
+/// What to do with each image.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct ImageOptions {
+    /// Output format: jpg/jpeg, png, webp, avif, bmp, gif, tiff, heic/heif
+    pub format: String,
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+    /// 1-100 for the lossy formats
+    pub quality: Option<u8>,
+    /// Smaller files at a fixed quality (and best PNG compression)
+    #[serde(default)]
+    pub optimize: bool,
+    /// Smaller files with the pixels unchanged: oxipng for PNG output, and for JPEG
+    /// input written as JPEG with nothing that changes the pixels (no resize,
+    /// transform, watermark, quality, `optimize` or size target) the same DCT
+    /// coefficients with optimized Huffman tables; JPEGs that can't be rewritten
+    /// that way (progressive, arithmetic coded) are copied as they are, minus the
+    /// metadata the policy drops. Otherwise ignored.
+    #[serde(default)]
+    pub lossless_optimize: bool,
+    /// Largest acceptable output (JPEG, WebP and AVIF): quality is searched, and the
+    /// image downscaled if needed, until it fits. `quality` becomes the upper bound.
+    #[serde(default)]
+    pub max_bytes: Option<u64>,
+    /// Orientation, crop and fit; EXIF orientation is always applied first
+    #[serde(default)]
+    pub transform: transform::Transform,
+    /// EXIF/XMP to carry over or set; JPEG, PNG, WebP and AVIF only
+    #[serde(default)]
+    pub metadata: metadata::MetadataOptions,
+    /// Logo or text stamped over the result, after resizing
+    #[serde(default)]
+    pub watermark: Option<watermark::Watermark>,
+}
+
+/// What `process` read and wrote.
+#[derive(Serialize, Debug, Clone)]
+pub struct ProcessReport {
+    /// Detected from the file contents, not its extension
+    pub input_format: String,
+    /// Upright size of the input
+    pub input_width: u32,
+    pub input_height: u32,
+    pub input_bytes: u64,
+    pub width: u32,
+    pub height: u32,
+    /// Quality used by the lossy encoders
+    pub quality: Option<u8>,
+    pub bytes: u64,
+    /// Share of the input size saved; negative when the output is bigger
+    pub savings_percent: f64,
+    /// Animation frames written; 1 for still images
+    pub frames: usize,
+}
+
+/// Named, reusable `ImageOptions`, stored in the app config.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ImagePreset {
+    pub name: String,
+    pub options: ImageOptions,
+}
+
+/// Presets a fresh install starts with.
+pub fn default_presets() -> Vec<ImagePreset> {
+    let preset =
+        |name: &str, format: &str, width: u32, quality: Option<u8>, optimize: bool| ImagePreset {
+            name: name.to_string(),
+            options: ImageOptions {
+                format: format.to_string(),
+                width: Some(width),
+                height: None,
+                quality,
+                optimize,
+                lossless_optimize: false,
+                max_bytes: None,
+                transform: Default::default(),
+                metadata: Default::default(),
+                watermark: None,
+            },
+        };
+    vec![
+        preset("Web thumbnail", "webp", 400, Some(75), false),
+        preset("Retina 2x", "png", 2880, None, true),
+        preset("Email attachment", "jpg", 1600, Some(70), false),
+    ]
+}
+
+/// Output formats `process` can write.
+const FORMATS: &[&str] = &[
+    "jpg", "jpeg", "png", "webp", "avif", "bmp", "gif", "tiff", "heic", "heif",
+];
+
+impl ImageOptions {
+    /// Rejects formats `process` cannot write and out-of-range settings, before any
+    /// output file is created.
+    pub fn validate(&self) -> Result<(), String> {
+        if !FORMATS.contains(&self.format.to_lowercase().as_str()) {
+            return Err(format!("Unsupported output format: {}", self.format));
+        }
+        self.transform.validate()?;
+        if let Some(watermark) = &self.watermark {
+            watermark.validate()?;
+        }
+        if self.width == Some(0) || self.height == Some(0) {
+            return Err("Width and height must be greater than 0".to_string());
+        }
+        if self.quality.is_some_and(|q| !(1..=100).contains(&q)) {
+            return Err("Quality must be between 1 and 100".to_string());
+        }
+        if let Some(max_bytes) = self.max_bytes {
+            if !is_lossy(&self.format) {
+                return Err("A target file size needs JPEG, WebP or AVIF output".to_string());
+            }
+            if max_bytes < MIN_TARGET_BYTES {
+                return Err(format!(
+                    "Target file size must be at least {} bytes",
+                    MIN_TARGET_BYTES
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// File extension for the output format.
+    pub fn extension(&self) -> String {
+        match self.format.to_lowercase().as_str() {
+            "jpeg" => "jpg".to_string(),
+            "heif" => "heic".to_string(),
+            other => other.to_string(),
+        }
+    }
+}
+
+/// Lowest quality tried before a size target falls back to downscaling; below this
+/// the artifacts are worse than a smaller image.
+const MIN_TARGET_QUALITY: u8 = 30;
+/// Each downscale keeps at least this share of the side length, so a size target
+/// never collapses the image in one step.
+const MIN_DOWNSCALE: f64 = 0.5;
+/// Smallest side a size target may downscale to.
+const MIN_TARGET_SIDE: u32 = 16;
+/// Anything smaller can't hold an image header, let alone pixels.
+const MIN_TARGET_BYTES: u64 = 1024;
+
+fn is_lossy(format: &str) -> bool {
+    matches!(
+        format.to_lowercase().as_str(),
+        "jpg" | "jpeg" | "webp" | "avif"
+    )
+}
+
+/// Quality for a lossy format when no size target is set.
+fn fixed_quality(format: &str, quality: Option<u8>, optimize: bool) -> u8 {
+    match (format, optimize) {
+        ("webp", true) => 75,
+        (_, true) => 70,
+        _ => quality.unwrap_or(80),
+    }
+}
+
+/// Encodes `img` as JPEG, WebP or AVIF at `quality` (1-100).
+fn encode_lossy(img: &image::DynamicImage, format: &str, quality: u8) -> Result<Vec<u8>, String> {
+    let mut bytes = Vec::new();
+    match format {
+        "jpg" | "jpeg" => {
+            let mut encoder =
+                image::codecs::jpeg::JpegEncoder::new_with_quality(&mut bytes, quality);
+            encoder
+                .encode_image(img)
+                .map_err(|e| format!("Failed to encode JPEG: {}", e))?;
+        }
+        "webp" => {
+            use image::codecs::webp::{WebPEncoder, WebPQuality};
+
+            #[allow(deprecated)]
+            let encoder = WebPEncoder::new_with_quality(&mut bytes, WebPQuality::lossy(quality));
+
+            encoder
+                .encode(img.as_bytes(), img.width(), img.height(), img.color())
+                .map_err(|e| format!("Failed to encode WebP: {}", e))?;
+        }
+        "avif" => {
+            let (width, height) = (img.width() as usize, img.height() as usize);
+            let rgba = img.to_rgba8();
+            let pixels = rgba.as_raw();
+
+            // Map raw bytes to RGBA8 pixels manually (safe and simple)
+            let pixels_rgba: Vec<ravif::RGBA8> = pixels
+                .chunks_exact(4)
+                .map(|c| ravif::RGBA8::new(c[0], c[1], c[2], c[3]))
+                .collect();
+
+            let img_ravif = ravif::Img::new(pixels_rgba.as_slice(), width, height);
+            let speed = 8; // Faster encoding for better UX
+
+            let res = ravif::Encoder::new()
+                .with_quality(quality as f32)
+                .with_speed(speed)
+                .encode_rgba(img_ravif)
+                .map_err(|e| format!("AVIF encoding failed: {}", e))?;
+            bytes = res.avif_file;
+        }
+        _ => return Err(format!("Unsupported output format: {}", format)),
+    }
+    Ok(bytes)
+}
+
+/// Highest quality up to `max_quality` whose encoding fits in `max_bytes`, found by
+/// binary search down to `MIN_TARGET_QUALITY` (or `max_quality` if that is lower);
+/// when even the lowest doesn't fit the image is downscaled by the estimated ratio
+/// and searched again. Returns the encoding,
+/// the quality and the final image size.
+fn fit_to_size(
+    mut img: image::DynamicImage,
+    format: &str,
+    max_bytes: u64,
+    max_quality: u8,
+) -> Result<(Vec<u8>, u8, u32, u32), String> {
+    let min_quality = MIN_TARGET_QUALITY.min(max_quality);
+    loop {
+        let smallest = encode_lossy(&img, format, min_quality)?;
+        if smallest.len() as u64 <= max_bytes {
+            let (mut lo, mut hi) = (min_quality, max_quality);
+            let mut best = (smallest, min_quality);
+            while lo < hi {
+                let mid = lo + (hi - lo).div_ceil(2);
+                let bytes = encode_lossy(&img, format, mid)?;
+                if bytes.len() as u64 <= max_bytes {
+                    best = (bytes, mid);
+                    lo = mid;
+                } else {
+                    hi = mid - 1;
+                }
+            }
+            return Ok((best.0, best.1, img.width(), img.height()));
+        }
+
+        // Size scales roughly with the pixel count; aim a little under the target
+        let ratio = (max_bytes as f64 / smallest.len() as f64).sqrt() * 0.95;
+        let ratio = ratio.clamp(MIN_DOWNSCALE, 0.95);
+        let width = (img.width() as f64 * ratio).round() as u32;
+        let height = (img.height() as f64 * ratio).round() as u32;
+        if width < MIN_TARGET_SIDE || height < MIN_TARGET_SIDE {
+            return Err(format!(
+                "Cannot fit the image in {} bytes as {}",
+                max_bytes,
+                format.to_uppercase()
+            ));
+        }
+        img = img.resize_exact(width, height, image::imageops::FilterType::Lanczos3);
+    }
+}
+
+/// Input format from the file's leading bytes, named by its usual extension.
+fn detect_format(data: &[u8]) -> Option<String> {
+    if heif::is_heif(data) {
+        return Some("heic".to_string());
+    }
+    image::guess_format(data)
+        .ok()
+        .and_then(|format| format.extensions_str().first())
+        .map(|ext| ext.to_string())
+}
+
+/// Writes `bytes` to a temp file beside `path` and renames it into place, so a
+/// failed write never leaves a truncated output behind.
+fn write_atomic(path: &Path, bytes: &[u8]) -> Result<(), String> {
+    use std::io::Write;
+
+    let name = path
+        .file_name()
+        .ok_or_else(|| format!("Invalid output path: {}", path.display()))?;
+    let temp = path.with_file_name(format!(
+        ".{}.{}.tmp",
+        name.to_string_lossy(),
+        std::process::id()
+    ));
+    let result = std::fs::File::create(&temp)
+        .and_then(|mut file| {
+            file.write_all(bytes)?;
+            file.sync_all()
+        })
+        .and_then(|_| std::fs::rename(&temp, path));
+    if let Err(e) = result {
+        let _ = std::fs::remove_file(&temp);
+        return Err(format!("Failed to write output file: {}", e));
+    }
+    Ok(())
+}
+
+/// Whether a JPEG can go through [`jpeg::optimize`] instead of being re-encoded:
+/// `lossless_optimize` is on, the output is JPEG and nothing changes the pixels.
+fn keeps_jpeg_coefficients(
+    input_format: &str,
+    format: &str,
+    options: &ImageOptions,
+    input_path: &Path,
+) -> bool {
+    options.lossless_optimize
+        && input_format == "jpg"
+        && matches!(format, "jpg" | "jpeg")
+        && options.width.is_none()
+        && options.height.is_none()
+        && options.quality.is_none()
+        && !options.optimize
+        && options.max_bytes.is_none()
+        && options.watermark.is_none()
+        && options.transform.is_identity()
+        // Turning the image upright would change the pixels too
+        && transform::exif_orientation(input_path) == 1
+}
+
+/// Converts `input_path` into `output_path` according to `options`. The output is
+/// encoded in memory and only then written, atomically. Animated GIF, WebP and
+/// APNG keep all their frames when written as one of those; other formats get the
+/// first frame.
+pub fn process(
+    input_path: &Path,
+    output_path: &Path,
+    options: &ImageOptions,
+) -> Result<ProcessReport, String> {
+    let format = options.format.to_lowercase();
+
+    let source = std::fs::read(input_path).map_err(|e| format!("Failed to read image: {}", e))?;
+    let input_format = detect_format(&source)
+        .ok_or_else(|| format!("Not a recognized image: {}", input_path.display()))?;
+
+    // Kept metadata is read before decoding, since the decoder drops it
+    let metadata = if options.metadata == metadata::MetadataOptions::default() {
+        metadata::Metadata::default()
+    } else {
+        metadata::collect(&source, &options.metadata)?
+    };
+
+    let frames = if animation::can_encode(&format) {
+        animation::decode(&source, &input_format)?
+    } else {
+        None
+    };
+    let frame_count = frames.as_ref().map_or(1, Vec::len);
+
+    // Progressive and other JPEGs `jpeg::optimize` can't rewrite are kept as they
+    // are rather than re-encoded, which would lose quality
+    let optimized = if frames.is_none()
+        && keeps_jpeg_coefficients(&input_format, &format, options, input_path)
+    {
+        Some(jpeg::optimize(&source).unwrap_or_else(|| metadata::strip_jpeg(&source)))
+    } else {
+        None
+    };
+
+    let (bytes, quality, (input_width, input_height), (width, height)) = match (frames, optimized) {
+        (Some(frames), _) => {
+            if options.max_bytes.is_some() {
+                return Err("A target file size isn't supported for animations".to_string());
+            }
+            let transform = &options.transform;
+            let orientation = transform::exif_orientation(input_path);
+            let mut input_size = (0, 0);
+            let frames = animation::map_frames(frames, |img| {
+                let img = transform::orient(img, orientation);
+                input_size = (img.width(), img.height());
+                let img = transform.apply(img)?;
+                Ok(transform::resize(
+                    img,
+                    options.width,
+                    options.height,
+                    transform.fit,
+                    transform.background,
+                ))
+            })?;
+            let size = frames[0].buffer().dimensions();
+            let frames = match &options.watermark {
+                Some(watermark) => {
+                    let mark = watermark.render(size.0)?;
+                    animation::map_frames(frames, |img| Ok(watermark.stamp(img, &mark)))?
+                }
+                None => frames,
+            };
+            let quality = fixed_quality(&format, options.quality, options.optimize);
+            let bytes = animation::encode(frames, &format, quality, options.optimize)?;
+            let bytes = metadata::embed(&format, bytes, &metadata)?;
+            (
+                bytes,
+                (format == "webp").then_some(quality),
+                input_size,
+                size,
+            )
+        }
+        (None, Some(bytes)) => {
+            let size = image::image_dimensions(input_path)
+                .map_err(|e| format!("Failed to open image: {}", e))?;
+            (metadata::embed("jpg", bytes, &metadata)?, None, size, size)
+        }
+        (None, None) => {
+            // Load image, upright as viewers show it
+            let img = if input_format == "heic" {
+                heif::decode(input_path)?
+            } else {
+                let orientation = transform::exif_orientation(input_path);
+                let img = image::load_from_memory(&source)
+                    .map_err(|e| format!("Failed to open image: {}", e))?;
+                transform::orient(img, orientation)
+            };
+            let input_size = (img.width(), img.height());
+            let (bytes, quality, width, height) = encode_still(img, &format, options, &metadata)?;
+            (bytes, quality, input_size, (width, height))
+        }
+    };
+
+    write_atomic(output_path, &bytes)?;
+
+    let input_bytes = source.len() as u64;
+    let bytes = bytes.len() as u64;
+    let savings_percent = if input_bytes == 0 {
+        0.0
+    } else {
+        (1.0 - bytes as f64 / input_bytes as f64) * 100.0
+    };
+    Ok(ProcessReport {
+        input_format,
+        input_width,
+        input_height,
+        input_bytes,
+        width,
+        height,
+        quality,
+        bytes,
+        savings_percent,
+        frames: frame_count,
+    })
+}
+
+/// Transforms, resizes and encodes a single upright image as `format`. Returns the
+/// encoding, the lossy quality used and the output size.
+fn encode_still(
+    img: image::DynamicImage,
+    format: &str,
+    options: &ImageOptions,
+    metadata: &metadata::Metadata,
+) -> Result<(Vec<u8>, Option<u8>, u32, u32), String> {
+    use image::{ImageEncoder, ImageFormat};
+
+    let ImageOptions {
+        width,
+        height,
+        quality,
+        optimize,
+        lossless_optimize,
+        max_bytes,
+        transform,
+        watermark,
+        ..
+    } = options;
+    let (quality, optimize, lossless_optimize) = (*quality, *optimize, *lossless_optimize);
+
+    let img = transform.apply(img)?;
+    let img = transform::resize(img, *width, *height, transform.fit, transform.background);
+    let img = match watermark {
+        Some(watermark) => watermark.apply(img)?,
+        None => img,
+    };
+    // JPEG has no alpha; transparent areas would turn black
+    let img = if matches!(format, "jpg" | "jpeg") {
+        let white = crate::annotate::Color([255, 255, 255, 255]);
+        transform::flatten(img, transform.background.unwrap_or(white))
+    } else {
+        img
+    };
+
+    Ok(if is_lossy(format) {
+        let (bytes, quality, width, height) = match max_bytes {
+            Some(max_bytes) => {
+                // Leave room for the metadata
+                let budget = max_bytes.saturating_sub(metadata.len()).max(1);
+                fit_to_size(img, format, budget, quality.unwrap_or(90))?
+            }
+            None => {
+                let quality = fixed_quality(format, quality, optimize);
+                let bytes = encode_lossy(&img, format, quality)?;
+                (bytes, quality, img.width(), img.height())
+            }
+        };
+        let bytes = metadata::embed(format, bytes, metadata)?;
+        (bytes, Some(quality), width, height)
+    } else {
+        let mut bytes = Vec::new();
+        let mut cursor = std::io::Cursor::new(&mut bytes);
+        match format {
+            "png" => {
+                if optimize {
+                    let encoder = image::codecs::png::PngEncoder::new_with_quality(
+                        &mut cursor,
+                        image::codecs::png::CompressionType::Best,
+                        image::codecs::png::FilterType::Adaptive,
+                    );
+                    encoder
+                        .write_image(img.as_bytes(), img.width(), img.height(), img.color())
+                        .map_err(|e| format!("Failed to encode optimized PNG: {}", e))?;
+                } else {
+                    img.write_to(&mut cursor, ImageFormat::Png)
+                        .map_err(|e| format!("Failed to write PNG: {}", e))?;
+                }
+                if lossless_optimize {
+                    bytes = oxipng::optimize_from_memory(&bytes, &oxipng::Options::from_preset(2))
+                        .map_err(|e| format!("Failed to optimize PNG: {}", e))?;
+                }
+                bytes = metadata::embed("png", bytes, metadata)?;
+            }
+            "bmp" => {
+                img.write_to(&mut cursor, ImageFormat::Bmp)
+                    .map_err(|e| format!("Failed to write BMP: {}", e))?;
+            }
+            "gif" => {
+                img.write_to(&mut cursor, ImageFormat::Gif)
+                    .map_err(|e| format!("Failed to write GIF: {}", e))?;
+            }
+            "tiff" => {
+                img.write_to(&mut cursor, ImageFormat::Tiff)
+                    .map_err(|e| format!("Failed to write TIFF: {}", e))?;
+            }
+            "heic" | "heif" => {
+                bytes = heif::encode(&img, fixed_quality(format, quality, optimize), metadata)?;
+            }
+            _ => return Err(format!("Unsupported output format: {}", format)),
+        }
+        (bytes, None, img.width(), img.height())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn noise(width: u32, height: u32) -> image::DynamicImage {
+        image::DynamicImage::ImageRgb8(image::RgbImage::from_fn(width, height, |x, y| {
+            image::Rgb([
+                ((x * 7) ^ (y * 13)) as u8,
+                ((x * y) >> 3) as u8,
+                (x * 31 + y * 17) as u8,
+            ])
+        }))
+    }
+
+    #[test]
+    fn fit_to_size_never_raises_the_quality() {
+        let (_, quality, ..) = fit_to_size(noise(64, 64), "jpg", u64::MAX, 10).unwrap();
+        assert_eq!(quality, 10);
+        let (_, quality, ..) = fit_to_size(noise(64, 64), "jpg", u64::MAX, 85).unwrap();
+        assert_eq!(quality, 85);
+    }
+
+    #[test]
+    fn fit_to_size_lowers_quality_then_size() {
+        let img = noise(256, 256);
+        let full = encode_lossy(&img, "jpg", 90).unwrap().len() as u64;
+
+        let (bytes, quality, width, _) = fit_to_size(img.clone(), "jpg", full / 2, 90).unwrap();
+        assert!((bytes.len() as u64) <= full / 2);
+        assert!((MIN_TARGET_QUALITY..90).contains(&quality));
+        assert_eq!(width, 256);
+
+        let smallest = encode_lossy(&img, "jpg", MIN_TARGET_QUALITY).unwrap();
+        let (bytes, _, width, _) = fit_to_size(img, "jpg", smallest.len() as u64 / 3, 90).unwrap();
+        assert!((bytes.len() as u64) <= smallest.len() as u64 / 3);
+        assert!(width < 256);
+    }
+
+    #[test]
+    fn lossless_optimize_keeps_jpeg_pixels() {
+        let dir = std::env::temp_dir().join(format!("taskgoblin-jpeg-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let (input, output) = (dir.join("in.jpg"), dir.join("out.jpg"));
+        let mut source = Vec::new();
+        image::codecs::jpeg::JpegEncoder::new_with_quality(&mut source, 90)
+            .encode_image(&noise(64, 48))
+            .unwrap();
+        std::fs::write(&input, &source).unwrap();
+
+        let mut options = ImageOptions {
+            format: "jpg".to_string(),
+            lossless_optimize: true,
+            ..Default::default()
+        };
+        let report = process(&input, &output, &options).unwrap();
+        assert_eq!(report.quality, None);
+        assert_eq!((report.width, report.height), (64, 48));
+        assert!(report.bytes < source.len() as u64);
+        let pixels = |path: &Path| image::open(path).unwrap().to_rgb8();
+        assert_eq!(pixels(&output), pixels(&input));
+
+        // Resizing changes the pixels anyway, so it's a regular encode
+        options.width = Some(32);
+        let report = process(&input, &output, &options).unwrap();
+        assert!(report.quality.is_some());
+        assert_eq!(report.width, 32);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn lossless_optimize_copies_progressive_jpegs() {
+        let dir =
+            std::env::temp_dir().join(format!("taskgoblin-progressive-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let (input, output) = (dir.join("in.jpg"), dir.join("out.jpg"));
+        let img = noise(64, 48).to_rgb8();
+        let mut source = Vec::new();
+        let mut encoder = jpeg_encoder::Encoder::new(&mut source, 90);
+        encoder.set_progressive(true);
+        encoder
+            .encode(img.as_raw(), 64, 48, jpeg_encoder::ColorType::Rgb)
+            .unwrap();
+        std::fs::write(&input, &source).unwrap();
+
+        let options = ImageOptions {
+            format: "jpg".to_string(),
+            lossless_optimize: true,
+            ..Default::default()
+        };
+        let report = process(&input, &output, &options).unwrap();
+        assert_eq!(std::fs::read(&output).unwrap(), source);
+        assert_eq!(report.quality, None);
+        assert_eq!(report.savings_percent, 0.0);
+
+        // Metadata the policy drops still goes
+        let exif = [0xFF, 0xE1, 0, 8, b'E', b'x', b'i', b'f', 0, 0];
+        std::fs::write(&input, [&source[..2], &exif, &source[2..]].concat()).unwrap();
+        process(&input, &output, &options).unwrap();
+        assert_eq!(std::fs::read(&output).unwrap(), source);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,404 @@
+//! Geometry applied before encoding: EXIF orientation, crop, rotation, flips and
+//! fitting into the requested size.
+
+use crate::annotate::Color;
+use image::{imageops::FilterType, DynamicImage, Rgba, RgbaImage};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+/// How an image is sized when both a width and a height are given. With only one
+/// of them the other follows the aspect ratio and the mode doesn't matter.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum FitMode {
+    /// Scale to fit inside the box, keeping the aspect ratio
+    #[default]
+    Contain,
+    /// Scale to cover the box, keeping the aspect ratio, and crop the overflow evenly
+    Cover,
+    /// Stretch to the box
+    Fill,
+    /// Contain, then pad to exactly the box with the background color
+    Exact,
+}
+
+/// A rectangle in pixels of the upright image (after EXIF orientation).
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct Crop {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+/// Applied in order: crop, rotate, flip, then the fit into the target size.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(default)]
+pub struct Transform {
+    /// Clockwise, in degrees: 0, 90, 180 or 270
+    pub rotate: u16,
+    pub flip_horizontal: bool,
+    pub flip_vertical: bool,
+    pub fit: FitMode,
+    /// Padding for `exact` and the backdrop for transparency in formats without
+    /// alpha; transparent (white for JPEG) when unset
+    pub background: Option<Color>,
+    pub crop: Option<Crop>,
+}
+
+impl Transform {
+    pub fn validate(&self) -> Result<(), String> {
+        if !matches!(self.rotate, 0 | 90 | 180 | 270) {
+            return Err("Rotation must be 0, 90, 180 or 270 degrees".to_string());
+        }
+        if self
+            .crop
+            .is_some_and(|crop| crop.width == 0 || crop.height == 0)
+        {
+            return Err("Crop width and height must be greater than 0".to_string());
+        }
+        Ok(())
+    }
+
+    /// Whether crop, rotation and flips leave the pixels as they are.
+    pub fn is_identity(&self) -> bool {
+        self.crop.is_none() && self.rotate == 0 && !self.flip_horizontal && !self.flip_vertical
+    }
+
+    /// Crop, rotation and flips; sizing is done by [`resize`].
+    pub fn apply(&self, img: DynamicImage) -> Result<DynamicImage, String> {
+        let img = match self.crop {
+            Some(rect) => crop(&img, rect)?,
+            None => img,
+        };
+        let img = match self.rotate {
+            90 => img.rotate90(),
+            180 => img.rotate180(),
+            270 => img.rotate270(),
+            _ => img,
+        };
+        let img = if self.flip_horizontal {
+            img.fliph()
+        } else {
+            img
+        };
+        Ok(if self.flip_vertical { img.flipv() } else { img })
+    }
+}
+
+/// EXIF orientation (1-8) of the file; 1 when it has none or can't be read.
+pub fn exif_orientation(path: &Path) -> u32 {
+    let Ok(file) = std::fs::File::open(path) else {
+        return 1;
+    };
+    exif::Reader::new()
+        .read_from_container(&mut std::io::BufReader::new(file))
+        .ok()
+        .and_then(|exif| {
+            exif.get_field(exif::Tag::Orientation, exif::In::PRIMARY)
+                .and_then(|field| field.value.get_uint(0))
+        })
+        .filter(|o| (1..=8).contains(o))
+        .unwrap_or(1)
+}
+
+/// Turns pixels stored with EXIF `orientation` upright, as viewers display them.
+pub fn orient(img: DynamicImage, orientation: u32) -> DynamicImage {
+    match orientation {
+        2 => img.fliph(),
+        3 => img.rotate180(),
+        4 => img.flipv(),
+        // Transpose
+        5 => img.rotate90().fliph(),
+        6 => img.rotate90(),
+        // Transverse
+        7 => img.rotate270().fliph(),
+        8 => img.rotate270(),
+        _ => img,
+    }
+}
+
+/// `rect` clipped to the image; an error if nothing of it is inside.
+fn crop(img: &DynamicImage, rect: Crop) -> Result<DynamicImage, String> {
+    if rect.x >= img.width() || rect.y >= img.height() {
+        return Err(format!(
+            "Crop rectangle is outside the {}x{} image",
+            img.width(),
+            img.height()
+        ));
+    }
+    let width = rect.width.min(img.width() - rect.x);
+    let height = rect.height.min(img.height() - rect.y);
+    Ok(img.crop_imm(rect.x, rect.y, width, height))
+}
+
+/// `side * target / reference`, rounded and at least 1.
+fn proportional(side: u32, target: u32, reference: u32) -> u32 {
+    ((side as f64 * target as f64 / reference as f64).round() as u32).max(1)
+}
+
+/// Sizes `img` to `width` x `height` according to `fit`. With only one of them the
+/// other follows the aspect ratio.
+pub fn resize(
+    img: DynamicImage,
+    width: Option<u32>,
+    height: Option<u32>,
+    fit: FitMode,
+    background: Option<Color>,
+) -> DynamicImage {
+    let (width, height) = match (width, height) {
+        (None, None) => return img,
+        (Some(w), Some(h)) => (w, h),
+        (Some(w), None) => (w, proportional(img.height(), w, img.width())),
+        (None, Some(h)) => (proportional(img.width(), h, img.height()), h),
+    };
+    if (width, height) == (img.width(), img.height()) {
+        return img;
+    }
+
+    match fit {
+        FitMode::Contain => img.resize(width, height, FilterType::Lanczos3),
+        FitMode::Cover => img.resize_to_fill(width, height, FilterType::Lanczos3),
+        FitMode::Fill => img.resize_exact(width, height, FilterType::Lanczos3),
+        FitMode::Exact => {
+            let inner = img.resize(width, height, FilterType::Lanczos3);
+            let color = background.map_or([0, 0, 0, 0], |c| c.0);
+            let mut canvas = RgbaImage::from_pixel(width, height, Rgba(color));
+            image::imageops::overlay(
+                &mut canvas,
+                &inner.to_rgba8(),
+                ((width - inner.width()) / 2) as i64,
+                ((height - inner.height()) / 2) as i64,
+            );
+            DynamicImage::ImageRgba8(canvas)
+        }
+    }
+}
+
+/// Composites transparent pixels over the opaque `background`, for formats that
+/// would otherwise just drop the alpha channel.
+pub fn flatten(img: DynamicImage, background: Color) -> DynamicImage {
+    if !img.color().has_alpha() {
+        return img;
+    }
+    let [br, bg, bb, _] = background.0;
+    let rgba = img.to_rgba8();
+    let rgb = image::RgbImage::from_fn(rgba.width(), rgba.height(), |x, y| {
+        let [r, g, b, a] = rgba.get_pixel(x, y).0;
+        let a = a as u32;
+        let mix = |c: u8, back: u8| ((c as u32 * a + back as u32 * (255 - a) + 127) / 255) as u8;
+        image::Rgb([mix(r, br), mix(g, bg), mix(b, bb)])
+    });
+    DynamicImage::ImageRgb8(rgb)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::GenericImageView;
+
+    const RED: [u8; 4] = [255, 0, 0, 255];
+    const BLUE: [u8; 4] = [0, 0, 255, 255];
+
+    /// Every pixel different, so any misplaced one shows
+    fn numbered(width: u32, height: u32) -> DynamicImage {
+        DynamicImage::ImageRgba8(RgbaImage::from_fn(width, height, |x, y| {
+            Rgba([x as u8 * 40, y as u8 * 40, 100, 255])
+        }))
+    }
+
+    /// Left half red, right half blue
+    fn halves(width: u32, height: u32) -> DynamicImage {
+        DynamicImage::ImageRgba8(RgbaImage::from_fn(width, height, |x, _| {
+            Rgba(if x < width / 2 { RED } else { BLUE })
+        }))
+    }
+
+    fn px(img: &DynamicImage, x: u32, y: u32) -> [u8; 4] {
+        img.get_pixel(x, y).0
+    }
+
+    fn close(a: [u8; 4], b: [u8; 4]) -> bool {
+        a.iter().zip(b).all(|(a, b)| a.abs_diff(b) <= 2)
+    }
+
+    fn transform(f: impl FnOnce(&mut Transform)) -> Transform {
+        let mut transform = Transform::default();
+        f(&mut transform);
+        transform
+    }
+
+    #[test]
+    fn orient_turns_every_exif_orientation_upright() {
+        let upright = numbered(3, 2);
+        let (w, h) = upright.dimensions();
+        for orientation in 1..=8 {
+            // Where each upright pixel is stored, per the EXIF definitions
+            let stored_at = |x: u32, y: u32| match orientation {
+                2 => (w - 1 - x, y),
+                3 => (w - 1 - x, h - 1 - y),
+                4 => (x, h - 1 - y),
+                5 => (y, x),
+                6 => (y, w - 1 - x),
+                7 => (h - 1 - y, w - 1 - x),
+                8 => (h - 1 - y, x),
+                _ => (x, y),
+            };
+            let (sw, sh) = if orientation >= 5 { (h, w) } else { (w, h) };
+            let mut stored = RgbaImage::new(sw, sh);
+            for (x, y, pixel) in upright.to_rgba8().enumerate_pixels() {
+                let (sx, sy) = stored_at(x, y);
+                stored.put_pixel(sx, sy, *pixel);
+            }
+
+            let oriented = orient(DynamicImage::ImageRgba8(stored), orientation);
+            assert_eq!(
+                oriented.to_rgba8(),
+                upright.to_rgba8(),
+                "orientation {}",
+                orientation
+            );
+        }
+    }
+
+    #[test]
+    fn crop_is_clipped_to_the_image() {
+        let img = numbered(4, 3);
+        let cropped = transform(|t| {
+            t.crop = Some(Crop {
+                x: 2,
+                y: 1,
+                width: 10,
+                height: 10,
+            })
+        })
+        .apply(img.clone())
+        .unwrap();
+        assert_eq!(cropped.dimensions(), (2, 2));
+        assert_eq!(px(&cropped, 0, 0), px(&img, 2, 1));
+        assert_eq!(px(&cropped, 1, 1), px(&img, 3, 2));
+
+        let outside = transform(|t| {
+            t.crop = Some(Crop {
+                x: 4,
+                y: 0,
+                width: 1,
+                height: 1,
+            })
+        });
+        assert!(outside.apply(img).is_err());
+
+        let empty = transform(|t| {
+            t.crop = Some(Crop {
+                x: 0,
+                y: 0,
+                width: 0,
+                height: 1,
+            })
+        });
+        assert!(empty.validate().is_err());
+    }
+
+    #[test]
+    fn rotates_and_flips() {
+        let img = numbered(3, 2);
+        let top_left = px(&img, 0, 0);
+        let corner = |t: Transform| {
+            let out = t.apply(img.clone()).unwrap();
+            let (w, h) = out.dimensions();
+            let at = [(0, 0), (w - 1, 0), (0, h - 1), (w - 1, h - 1)]
+                .into_iter()
+                .position(|(x, y)| px(&out, x, y) == top_left);
+            ((w, h), at)
+        };
+
+        // Corners: 0 top-left, 1 top-right, 2 bottom-left, 3 bottom-right
+        assert_eq!(corner(transform(|_| {})), ((3, 2), Some(0)));
+        assert_eq!(corner(transform(|t| t.rotate = 90)), ((2, 3), Some(1)));
+        assert_eq!(corner(transform(|t| t.rotate = 180)), ((3, 2), Some(3)));
+        assert_eq!(corner(transform(|t| t.rotate = 270)), ((2, 3), Some(2)));
+        assert_eq!(
+            corner(transform(|t| t.flip_horizontal = true)),
+            ((3, 2), Some(1))
+        );
+        assert_eq!(
+            corner(transform(|t| t.flip_vertical = true)),
+            ((3, 2), Some(2))
+        );
+        // Rotation comes before the flip
+        let both = transform(|t| {
+            t.rotate = 90;
+            t.flip_horizontal = true;
+        });
+        assert!(!both.is_identity());
+        assert_eq!(corner(both), ((2, 3), Some(0)));
+
+        assert!(transform(|t| t.rotate = 45).validate().is_err());
+        assert!(transform(|_| {}).is_identity());
+    }
+
+    #[test]
+    fn fit_modes() {
+        let img = halves(40, 20);
+        let fit = |fit: FitMode| resize(img.clone(), Some(20), Some(20), fit, None);
+
+        let contain = fit(FitMode::Contain);
+        assert_eq!(contain.dimensions(), (20, 10));
+        assert!(close(px(&contain, 0, 0), RED) && close(px(&contain, 19, 9), BLUE));
+
+        // Scaled to the height, a quarter cut from each side
+        let cover = fit(FitMode::Cover);
+        assert_eq!(cover.dimensions(), (20, 20));
+        assert!(close(px(&cover, 0, 0), RED) && close(px(&cover, 19, 19), BLUE));
+
+        let fill = fit(FitMode::Fill);
+        assert_eq!(fill.dimensions(), (20, 20));
+        assert!(close(px(&fill, 0, 19), RED) && close(px(&fill, 19, 0), BLUE));
+
+        // Contained 20x10, centered on a transparent 20x20
+        let exact = fit(FitMode::Exact);
+        assert_eq!(exact.dimensions(), (20, 20));
+        assert_eq!(px(&exact, 0, 0), [0, 0, 0, 0]);
+        assert_eq!(px(&exact, 19, 19), [0, 0, 0, 0]);
+        assert!(close(px(&exact, 0, 10), RED) && close(px(&exact, 19, 10), BLUE));
+    }
+
+    #[test]
+    fn exact_pads_with_the_background() {
+        let green = Color([0, 200, 0, 255]);
+        let padded = resize(
+            halves(20, 40),
+            Some(20),
+            Some(20),
+            FitMode::Exact,
+            Some(green),
+        );
+        assert_eq!(padded.dimensions(), (20, 20));
+        // 10x20 content in the middle, 5 columns of padding either side
+        assert_eq!(px(&padded, 4, 0), green.0);
+        assert_eq!(px(&padded, 15, 19), green.0);
+        assert!(close(px(&padded, 6, 10), RED) && close(px(&padded, 13, 10), BLUE));
+    }
+
+    #[test]
+    fn one_side_keeps_the_aspect_ratio() {
+        let img = halves(40, 20);
+        let by_width = resize(img.clone(), Some(10), None, FitMode::Fill, None);
+        assert_eq!(by_width.dimensions(), (10, 5));
+        let by_height = resize(img.clone(), None, Some(5), FitMode::Cover, None);
+        assert_eq!(by_height.dimensions(), (10, 5));
+        let same = resize(img.clone(), Some(40), Some(20), FitMode::Exact, None);
+        assert_eq!(same.to_rgba8(), img.to_rgba8());
+    }
+
+    #[test]
+    fn flatten_blends_over_the_background() {
+        let img = DynamicImage::ImageRgba8(RgbaImage::from_fn(2, 1, |x, _| {
+            Rgba(if x == 0 { [0, 0, 0, 0] } else { [0, 0, 0, 128] })
+        }));
+        let flat = flatten(img, Color([255, 255, 255, 255]));
+        assert!(!flat.color().has_alpha());
+        assert_eq!(px(&flat, 0, 0), [255, 255, 255, 255]);
+        assert_eq!(px(&flat, 1, 0), [127, 127, 127, 255]);
+    }
+}
@@ -0,0 +1,354 @@
+//! Watermarks stamped over the output pixels after resizing: a logo image or a line
+//! of text, placed once or tiled across the whole image.
+
+use crate::annotate::Color;
+use image::{imageops::FilterType, DynamicImage, RgbaImage};
+use serde::{Deserialize, Serialize};
+
+/// Where a single (untiled) watermark sits.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum Position {
+    TopLeft,
+    Top,
+    TopRight,
+    Left,
+    Center,
+    Right,
+    BottomLeft,
+    Bottom,
+    #[default]
+    BottomRight,
+}
+
+fn white() -> Color {
+    Color([255, 255, 255, 255])
+}
+
+/// What gets stamped, tagged by `type`, e.g. `{"type": "text", "text": "© ACME"}`.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Mark {
+    /// Any image `process` can read; transparency is kept
+    Image { path: String },
+    /// A single line or several (`\n`), in the bundled DejaVu Sans so the result
+    /// doesn't depend on the installed fonts
+    Text {
+        text: String,
+        #[serde(default = "white")]
+        color: Color,
+    },
+}
+
+fn default_opacity() -> f32 {
+    0.5
+}
+
+fn default_scale() -> f32 {
+    0.2
+}
+
+fn default_margin() -> f32 {
+    0.02
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Watermark {
+    #[serde(default)]
+    pub position: Position,
+    /// 0.0 (invisible) to 1.0
+    #[serde(default = "default_opacity")]
+    pub opacity: f32,
+    /// Watermark width as a share of the image width
+    #[serde(default = "default_scale")]
+    pub scale: f32,
+    /// Distance from the edges, and between tiles, as a share of the shorter side
+    #[serde(default = "default_margin")]
+    pub margin: f32,
+    /// Repeat over the whole image; `position` is ignored
+    #[serde(default)]
+    pub tile: bool,
+    pub mark: Mark,
+}
+
+impl Watermark {
+    pub fn validate(&self) -> Result<(), String> {
+        if !(0.0..=1.0).contains(&self.opacity) {
+            return Err("Watermark opacity must be between 0 and 1".to_string());
+        }
+        if !(self.scale > 0.0 && self.scale <= 1.0) {
+            return Err("Watermark scale must be greater than 0 and at most 1".to_string());
+        }
+        if !(0.0..=0.5).contains(&self.margin) {
+            return Err("Watermark margin must be between 0 and 0.5".to_string());
+        }
+        match &self.mark {
+            Mark::Image { path } if !std::path::Path::new(path).is_file() => {
+                Err(format!("Watermark image not found: {}", path))
+            }
+            Mark::Text { text, .. } if text.trim().is_empty() => {
+                Err("Watermark text is empty".to_string())
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// The mark scaled for an image `width` pixels wide, at the configured opacity.
+    pub fn render(&self, width: u32) -> Result<RgbaImage, String> {
+        let target = ((width as f32 * self.scale).round() as u32).max(1);
+        let mut mark = match &self.mark {
+            Mark::Image { path } => {
+                let logo = image::open(path)
+                    .map_err(|e| format!("Failed to open watermark image: {}", e))?;
+                let height = (logo.height() as f64 * target as f64 / logo.width() as f64).round();
+                logo.resize_exact(target, (height as u32).max(1), FilterType::Lanczos3)
+                    .into_rgba8()
+            }
+            Mark::Text { text, color } => {
+                // Measured at a reference size, then drawn at the size that fills `target`
+                const REFERENCE_SIZE: f32 = 100.0;
+                let reference = crate::annotate::render_text(text, *color, REFERENCE_SIZE)?;
+                let size = REFERENCE_SIZE * target as f32 / reference.width() as f32;
+                crate::annotate::render_text(text, *color, size)?
+            }
+        };
+        for pixel in mark.pixels_mut() {
+            pixel.0[3] = (pixel.0[3] as f32 * self.opacity).round() as u8;
+        }
+        Ok(mark)
+    }
+
+    /// Draws `mark` (from [`Watermark::render`]) over `img`.
+    pub fn stamp(&self, img: DynamicImage, mark: &RgbaImage) -> DynamicImage {
+        let opaque = !img.color().has_alpha();
+        let mut canvas = img.into_rgba8();
+        let (width, height) = (canvas.width() as i64, canvas.height() as i64);
+        let (mark_width, mark_height) = (mark.width() as i64, mark.height() as i64);
+        let margin = (width.min(height) as f32 * self.margin).round() as i64;
+
+        if self.tile {
+            for y in (0..height).step_by((mark_height + margin).max(1) as usize) {
+                for x in (0..width).step_by((mark_width + margin).max(1) as usize) {
+                    image::imageops::overlay(&mut canvas, mark, x, y);
+                }
+            }
+        } else {
+            use Position::*;
+            let x = match self.position {
+                TopLeft | Left | BottomLeft => margin,
+                Top | Center | Bottom => (width - mark_width) / 2,
+                TopRight | Right | BottomRight => width - mark_width - margin,
+            };
+            let y = match self.position {
+                TopLeft | Top | TopRight => margin,
+                Left | Center | Right => (height - mark_height) / 2,
+                BottomLeft | Bottom | BottomRight => height - mark_height - margin,
+            };
+            image::imageops::overlay(&mut canvas, mark, x, y);
+        }
+        // Stamping never adds transparency, so don't add an alpha channel either
+        let img = DynamicImage::ImageRgba8(canvas);
+        if opaque {
+            DynamicImage::ImageRgb8(img.into_rgb8())
+        } else {
+            img
+        }
+    }
+
+    pub fn apply(&self, img: DynamicImage) -> Result<DynamicImage, String> {
+        let mark = self.render(img.width())?;
+        Ok(self.stamp(img, &mark))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{Rgb, RgbImage, Rgba};
+
+    const RED: Rgba<u8> = Rgba([255, 0, 0, 255]);
+
+    /// A 10x5 opaque red logo written to the temp dir
+    fn logo(name: &str) -> String {
+        let path = std::env::temp_dir().join(format!(
+            "taskgoblin-watermark-{}-{}.png",
+            name,
+            std::process::id()
+        ));
+        RgbaImage::from_pixel(10, 5, RED).save(&path).unwrap();
+        path.to_string_lossy().to_string()
+    }
+
+    fn image_mark(path: &str) -> Watermark {
+        Watermark {
+            position: Position::default(),
+            opacity: 1.0,
+            scale: 0.2,
+            margin: 0.1,
+            tile: false,
+            mark: Mark::Image {
+                path: path.to_string(),
+            },
+        }
+    }
+
+    fn black() -> DynamicImage {
+        DynamicImage::ImageRgb8(RgbImage::new(100, 50))
+    }
+
+    fn is_red(img: &RgbImage, x: u32, y: u32) -> bool {
+        img.get_pixel(x, y) == &Rgb([255, 0, 0])
+    }
+
+    #[test]
+    fn image_mark_scales_to_the_width() {
+        let path = logo("scale");
+        let mut watermark = image_mark(&path);
+        let mark = watermark.render(100).unwrap();
+        assert_eq!(mark.dimensions(), (20, 10));
+        assert!(mark.pixels().all(|p| *p == RED));
+
+        watermark.scale = 0.5;
+        assert_eq!(watermark.render(30).unwrap().dimensions(), (15, 8));
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn opacity_scales_the_alpha() {
+        let path = logo("opacity");
+        let mut watermark = image_mark(&path);
+        watermark.opacity = 0.5;
+        let mark = watermark.render(100).unwrap();
+        assert!(mark.pixels().all(|p| p.0 == [255, 0, 0, 128]));
+
+        // Half red over black
+        let out = watermark.apply(black()).unwrap().into_rgb8();
+        assert_eq!(out.get_pixel(75, 35), &Rgb([128, 0, 0]));
+        assert_eq!(out.get_pixel(0, 0), &Rgb([0, 0, 0]));
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn every_position_keeps_the_margin() {
+        let path = logo("position");
+        let mut watermark = image_mark(&path);
+        // A 20x10 mark on 100x50 with a 5px margin
+        for (position, (x, y)) in [
+            (Position::TopLeft, (5, 5)),
+            (Position::Top, (40, 5)),
+            (Position::TopRight, (75, 5)),
+            (Position::Left, (5, 20)),
+            (Position::Center, (40, 20)),
+            (Position::Right, (75, 20)),
+            (Position::BottomLeft, (5, 35)),
+            (Position::Bottom, (40, 35)),
+            (Position::BottomRight, (75, 35)),
+        ] {
+            watermark.position = position;
+            let out = watermark.apply(black()).unwrap();
+            // Opaque in, opaque out
+            assert!(!out.color().has_alpha());
+            let out = out.into_rgb8();
+            assert!(is_red(&out, x, y), "{:?}", position);
+            assert!(is_red(&out, x + 19, y + 9), "{:?}", position);
+            assert!(!is_red(&out, x - 1, y) && !is_red(&out, x, y - 1));
+            assert!(!is_red(&out, x + 20, y) && !is_red(&out, x, y + 10));
+            assert_eq!(out.pixels().filter(|p| p.0 == [255, 0, 0]).count(), 200);
+        }
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn tiles_cover_the_image() {
+        let path = logo("tile");
+        let mut watermark = image_mark(&path);
+        watermark.tile = true;
+        watermark.margin = 0.0;
+        let out = watermark.apply(black()).unwrap().into_rgb8();
+        assert!(out.pixels().all(|p| p.0 == [255, 0, 0]));
+
+        // 20x10 tiles every 25px across and 15px down, clipped at the edges
+        watermark.margin = 0.1;
+        let out = watermark.apply(black()).unwrap().into_rgb8();
+        for x in [0, 19, 25, 44, 50, 75, 94] {
+            assert!(is_red(&out, x, 0) && is_red(&out, x, 45), "{}", x);
+        }
+        for x in [20, 24, 45, 95, 99] {
+            assert!(!is_red(&out, x, 0), "{}", x);
+        }
+        for y in [0, 9, 15, 24, 30, 45, 49] {
+            assert!(is_red(&out, 0, y), "{}", y);
+        }
+        for y in [10, 14, 25, 40] {
+            assert!(!is_red(&out, 0, y), "{}", y);
+        }
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn text_mark_is_the_same_everywhere() {
+        let watermark = Watermark {
+            position: Position::Center,
+            opacity: 0.5,
+            scale: 0.5,
+            margin: 0.0,
+            tile: false,
+            mark: Mark::Text {
+                text: "TaskGoblin".to_string(),
+                color: Color([0, 0, 255, 255]),
+            },
+        };
+        let mark = watermark.render(400).unwrap();
+        // Rendered in the bundled font, so the size is fixed
+        assert_eq!(mark.dimensions(), (200, 44));
+        assert_eq!(watermark.render(400).unwrap(), mark);
+        // Every inked pixel has the color, at no more than half opacity
+        let inked: Vec<_> = mark.pixels().filter(|p| p.0[3] > 0).collect();
+        assert!(inked.len() > 1000);
+        assert!(inked
+            .iter()
+            .all(|p| p.0[..3] == [0, 0, 255] && p.0[3] <= 128));
+        assert!(inked.iter().any(|p| p.0[3] == 128));
+
+        let out = watermark
+            .apply(DynamicImage::ImageRgb8(RgbImage::new(400, 100)))
+            .unwrap()
+            .into_rgb8();
+        let blue = |x: u32, y: u32| out.get_pixel(x, y).0[2] > 0;
+        // Centered: ink inside the middle 200px only
+        assert!((0..100).all(|y| !blue(99, y) && !blue(300, y)));
+        assert!((100..300).any(|x| (0..100).any(|y| blue(x, y))));
+    }
+
+    #[test]
+    fn validate_rejects_bad_settings() {
+        let path = logo("validate");
+        let good = image_mark(&path);
+        assert!(good.validate().is_ok());
+        for bad in [
+            Watermark {
+                opacity: 1.5,
+                ..good.clone()
+            },
+            Watermark {
+                scale: 0.0,
+                ..good.clone()
+            },
+            Watermark {
+                margin: 0.6,
+                ..good.clone()
+            },
+            image_mark("/nonexistent/logo.png"),
+            Watermark {
+                mark: Mark::Text {
+                    text: " ".to_string(),
+                    color: white(),
+                },
+                ..good.clone()
+            },
+        ] {
+            assert!(bad.validate().is_err(), "{:?}", bad);
+        }
+        std::fs::remove_file(path).unwrap();
+    }
+}
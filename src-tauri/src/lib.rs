@@ -1,3 +1,12 @@
+mod annotate;
+mod capture;
+mod clipboard_history;
+mod codes;
+mod imaging;
+mod ocr;
+mod pii;
+mod recording;
+
 use device_query::{DeviceQuery, DeviceState, Keycode};
 use enigo::{Enigo, Mouse, Settings};
 use serde::{Deserialize, Serialize};
@@ -10,9 +19,20 @@ use tauri::{
 };
 
 #[derive(Serialize, Deserialize, Debug)]
+#[serde(default)]
 struct AppConfig {
     last_x: Option<i32>,
     last_y: Option<i32>,
+    /// Preferred OCR languages as BCP-47 tags, in priority order
+    ocr_languages: Vec<String>,
+    /// How many OCR results to keep in the history; 0 turns the history off
+    ocr_history_limit: usize,
+    /// Folder for paint captures; None means Downloads
+    capture_save_dir: Option<String>,
+    /// Paint capture file name without extension; supports {timestamp}, {date} and {time}
+    capture_filename_template: String,
+    /// Named export settings for the image converter
+    image_presets: Vec<imaging::ImagePreset>,
 }
 
 impl Default for AppConfig {
@@ -20,22 +40,114 @@ impl Default for AppConfig {
         Self {
             last_x: None,
             last_y: None,
+            ocr_languages: ocr::default_languages(),
+            ocr_history_limit: ocr::history::DEFAULT_LIMIT,
+            capture_save_dir: None,
+            capture_filename_template: "TaskGoblin_Capture_{timestamp}".to_string(),
+            image_presets: imaging::default_presets(),
+        }
+    }
+}
+
+/// Loads the stored config, applies `f` and writes it back, so each caller only
+/// touches its own fields. A config that fails to load is left alone rather than
+/// overwritten with defaults.
+fn update_config(f: impl FnOnce(&mut AppConfig)) -> Result<(), String> {
+    let mut config: AppConfig = confy::load("mouse-crazy-app", None)
+        .map_err(|e| format!("Failed to load config: {}", e))?;
+    f(&mut config);
+    confy::store("mouse-crazy-app", None, config)
+        .map_err(|e| format!("Failed to save config: {}", e))
+}
+
+/// Region picked on the capture overlay, in physical pixels relative to the top-left
+/// of the monitor it was drawn on.
+#[derive(Debug, Clone, Copy)]
+struct CaptureRegion {
+    /// Monitor index as used by the `capture` module
+    monitor: usize,
+    /// DPI scale of that monitor, used to turn the overlay's logical pixels into physical ones
+    scale_factor: f64,
+    x: i32,
+    y: i32,
+    w: i32,
+    h: i32,
+    /// Output mode chosen on the overlay toolbar
+    ocr_mode: ocr::OutputMode,
+}
+
+impl CaptureRegion {
+    /// Converts a selection in CSS (logical) pixels to physical pixels. Edges are
+    /// rounded separately so adjacent selections never gain or lose a pixel.
+    fn from_logical(
+        monitor: usize,
+        scale_factor: f64,
+        x: f64,
+        y: f64,
+        w: f64,
+        h: f64,
+        ocr_mode: ocr::OutputMode,
+    ) -> Self {
+        let left = (x * scale_factor).round() as i32;
+        let top = (y * scale_factor).round() as i32;
+        let right = ((x + w) * scale_factor).round() as i32;
+        let bottom = ((y + h) * scale_factor).round() as i32;
+        Self {
+            monitor,
+            scale_factor,
+            x: left,
+            y: top,
+            w: right - left,
+            h: bottom - top,
+            ocr_mode,
+        }
+    }
+}
+
+/// Overlay windows are labelled `capture-<monitor index>`.
+const CAPTURE_OVERLAY_PREFIX: &str = "capture-";
+
+fn close_capture_overlays(app_handle: &tauri::AppHandle) {
+    for (label, win) in app_handle.webview_windows() {
+        if label.starts_with(CAPTURE_OVERLAY_PREFIX) {
+            let _ = win.close();
         }
     }
 }
 
+/// Region recording lifecycle, polled by the recording island.
+enum RecordingState {
+    Idle,
+    /// Picking the region; taken before any await so a second start is refused
+    Starting,
+    /// Counting down before the first frame
+    Countdown {
+        starts_at: Instant,
+    },
+    Recording(recording::Recorder),
+}
+
 struct AppState {
     mouse_moving: std::sync::Mutex<bool>,
     is_paint_mode: std::sync::Mutex<bool>,
     is_dialog_open: std::sync::Mutex<bool>,
     is_capturing: Arc<std::sync::Mutex<bool>>, // Arc so it can be cloned into threads
-    // Sends capture region (x,y,w,h) or None (cancel) back from the Tauri overlay window
-    capture_tx:
-        tokio::sync::Mutex<Option<tokio::sync::oneshot::Sender<Option<(i32, i32, i32, i32)>>>>,
+    // Sends the capture region or None (cancel) back from the Tauri overlay window
+    capture_tx: tokio::sync::Mutex<Option<tokio::sync::oneshot::Sender<Option<CaptureRegion>>>>,
     shutdown_cancel_tx: tokio::sync::Mutex<Option<tokio::sync::oneshot::Sender<()>>>,
     shutdown_target: tokio::sync::Mutex<Option<u64>>,
     shutdown_duration: tokio::sync::Mutex<Option<u64>>,
     last_tray_pos: tokio::sync::Mutex<Option<tauri::PhysicalPosition<i32>>>,
+    ocr_languages: std::sync::Mutex<Vec<String>>,
+    ocr_history: std::sync::Mutex<ocr::history::HistoryStore>,
+    ocr_history_limit: std::sync::Mutex<usize>,
+    clipboard_history: Arc<std::sync::Mutex<clipboard_history::ClipboardHistory>>, // Shared with the watcher thread
+    recording: std::sync::Mutex<RecordingState>,
+    scroll_capture_stop: Arc<std::sync::Mutex<bool>>, // Checked by the scrolling capture thread
+    // Untouched capture the annotation editor is working on
+    annotation_base: std::sync::Mutex<Option<image::RgbaImage>>,
+    // Cancel flag of every running image batch by its id, checked between files
+    image_batches: std::sync::Mutex<std::collections::HashMap<String, Arc<std::sync::Mutex<bool>>>>,
 }
 
 #[tauri::command]
@@ -63,10 +175,7 @@ async fn schedule_whatsapp(
     let _ = app_handle;
 
     // Strictly sanitize phone
-    let sanitized_phone = phone
-        .chars()
-        .filter(|c| c.is_digit(10) || *c == '+')
-        .collect::<String>();
+    let sanitized_phone = pii::sanitize_phone(&phone);
 
     println!(
         "Scheduled WhatsApp to {} in {} seconds via {}",
@@ -93,7 +202,7 @@ async fn schedule_whatsapp(
         #[cfg(target_os = "macos")]
         {
             let _ = std::process::Command::new("open").arg(&url).spawn();
-            
+
             if method == "browser" {
                 // Wait longer for WhatsApp Web to load
                 tokio::time::sleep(tokio::time::Duration::from_secs(12)).await;
@@ -141,7 +250,7 @@ async fn schedule_whatsapp(
             // Auto-send logic for Windows: Wait longer if browser, else 8s
             let wait_time = if method == "browser" { 16 } else { 8 };
             tokio::time::sleep(tokio::time::Duration::from_secs(wait_time)).await;
-            
+
             let _ = tauri::async_runtime::spawn_blocking(move || {
                 use enigo::{Direction, Enigo, Key, Keyboard, Settings};
                 if let Ok(mut enigo) = Enigo::new(&Settings::default()) {
@@ -364,19 +473,19 @@ fn check_contacts() -> Result<bool, String> {
                 print("DENIED")
             }
         "#;
-        
+
         // Write to tmp to support older Mac toolchains
         let script_path = "/tmp/task_goblin_check_contacts.swift";
         let _ = std::fs::write(script_path, swift_script);
-        
+
         let output = Command::new("swift").arg(script_path).output();
         let _ = std::fs::remove_file(script_path);
-        
+
         if let Ok(out) = output {
             let stdout = String::from_utf8_lossy(&out.stdout).trim().to_string();
             return Ok(stdout == "AUTHORIZED");
         }
-        
+
         Ok(false)
     }
     #[cfg(not(target_os = "macos"))]
@@ -1151,184 +1260,264 @@ async fn get_shutdown_time(state: State<'_, AppState>) -> Result<serde_json::Val
     }))
 }
 
-#[tauri::command]
-async fn process_image(
-    input_path: String,
-    output_path: String,
-    format: String,
+/// Conversion settings sent with `process_image` and `process_images_batch`: the
+/// named preset if given, with any explicit setting taking precedence over it.
+/// Without a preset the format is required.
+#[derive(Deserialize, Debug)]
+struct ImageRequest {
+    preset: Option<String>,
+    format: Option<String>,
     width: Option<u32>,
     height: Option<u32>,
     quality: Option<u8>,
-    optimize: bool,
-) -> Result<(), String> {
-    use image::{ImageEncoder, ImageFormat};
-    use std::fs::File;
+    optimize: Option<bool>,
+    /// Shrinks PNG output, and JPEG input kept as JPEG with the pixels untouched,
+    /// without changing a pixel
+    lossless_optimize: Option<bool>,
+    /// Best quality (JPEG, WebP, AVIF) that fits this size, downscaling if even low
+    /// quality is too big
+    max_bytes: Option<u64>,
+    /// Crop, rotation, flips and how width and height are fitted
+    transform: Option<imaging::transform::Transform>,
+    /// Which EXIF/XMP survive (none by default)
+    metadata: Option<imaging::metadata::MetadataOptions>,
+    /// Logo or text stamped over every output, after resizing
+    watermark: Option<imaging::watermark::Watermark>,
+}
 
-    println!(
-        "Processing image: {} to {} as {}",
-        input_path, output_path, format
-    );
+fn resolve_image_options(request: ImageRequest) -> Result<imaging::ImageOptions, String> {
+    let mut options = match request.preset.filter(|p| !p.trim().is_empty()) {
+        Some(name) => {
+            let config: AppConfig = confy::load("mouse-crazy-app", None).unwrap_or_default();
+            config
+                .image_presets
+                .into_iter()
+                .find(|p| p.name.eq_ignore_ascii_case(name.trim()))
+                .map(|p| p.options)
+                .ok_or_else(|| format!("Image preset not found: {}", name))?
+        }
+        None => imaging::ImageOptions {
+            format: request
+                .format
+                .clone()
+                .ok_or_else(|| "An output format or a preset is required".to_string())?,
+            ..Default::default()
+        },
+    };
 
-    // Load image
-    let img = image::open(&input_path).map_err(|e| format!("Failed to open image: {}", e))?;
+    if let Some(format) = request.format {
+        options.format = format;
+    }
+    options.width = request.width.or(options.width);
+    options.height = request.height.or(options.height);
+    options.quality = request.quality.or(options.quality);
+    options.optimize = request.optimize.unwrap_or(options.optimize);
+    options.lossless_optimize = request
+        .lossless_optimize
+        .unwrap_or(options.lossless_optimize);
+    options.max_bytes = request.max_bytes.or(options.max_bytes);
+    if let Some(transform) = request.transform {
+        options.transform = transform;
+    }
+    if let Some(metadata) = request.metadata {
+        options.metadata = metadata;
+    }
+    options.watermark = request.watermark.or(options.watermark);
+    options.validate()?;
+    Ok(options)
+}
 
-    // Resize if requested
-    let img = if width.is_some() || height.is_some() {
-        let (w, h) = match (width, height) {
-            (Some(w), Some(h)) => (w, h),
-            (Some(w), None) => {
-                let ratio = w as f32 / img.width() as f32;
-                (w, (img.height() as f32 * ratio) as u32)
-            }
-            (None, Some(h)) => {
-                let ratio = h as f32 / img.height() as f32;
-                ((img.width() as f32 * ratio) as u32, h)
-            }
-            _ => unreachable!(),
-        };
-        img.resize(w, h, image::imageops::FilterType::Lanczos3)
-    } else {
-        img
-    };
+/// Converts one image as described by `request`; the report has the quality
+/// `max_bytes` settled on. The input format is detected from its contents, the
+/// output is written atomically, and the report compares input and output sizes
+/// and dimensions. Animated GIF, WebP and APNG keep every frame and timing when
+/// converted between those three formats.
+#[tauri::command]
+async fn process_image(
+    input_path: String,
+    output_path: String,
+    request: ImageRequest,
+) -> Result<imaging::ProcessReport, String> {
+    let options = resolve_image_options(request)?;
+    tauri::async_runtime::spawn_blocking(move || {
+        imaging::process(
+            std::path::Path::new(&input_path),
+            std::path::Path::new(&output_path),
+            &options,
+        )
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
 
-    let output_file =
-        File::create(&output_path).map_err(|e| format!("Failed to create output file: {}", e))?;
-
-    match format.to_lowercase().as_str() {
-        "jpg" | "jpeg" => {
-            let q = if optimize { 70 } else { quality.unwrap_or(80) };
-            let mut encoder = image::codecs::jpeg::JpegEncoder::new_with_quality(output_file, q);
-            encoder
-                .encode_image(&img)
-                .map_err(|e| format!("Failed to encode JPEG: {}", e))?;
-        }
-        "png" => {
-            if optimize {
-                let encoder = image::codecs::png::PngEncoder::new_with_quality(
-                    output_file,
-                    image::codecs::png::CompressionType::Best,
-                    image::codecs::png::FilterType::Adaptive,
-                );
-                encoder
-                    .write_image(img.as_bytes(), img.width(), img.height(), img.color())
-                    .map_err(|e| format!("Failed to encode optimized PNG: {}", e))?;
-            } else {
-                img.write_to(&mut std::io::BufWriter::new(output_file), ImageFormat::Png)
-                    .map_err(|e| format!("Failed to write PNG: {}", e))?;
-            }
+/// Converts many images with the same `request` as `process_image`. `inputs` are
+/// files, folders or glob patterns; outputs go to `output_dir` (created if needed)
+/// or next to each input, never overwriting anything. Runs on up to `workers`
+/// threads, emits `image-batch-progress` (`batch_id` and `progress`) after every
+/// file and stops starting new files after `cancel_image_batch` with the same
+/// `batch_id`, which the caller picks and which must be unique among running batches.
+#[tauri::command]
+async fn process_images_batch(
+    window: tauri::WebviewWindow,
+    state: State<'_, AppState>,
+    batch_id: String,
+    inputs: Vec<String>,
+    output_dir: Option<String>,
+    request: ImageRequest,
+    workers: Option<usize>,
+) -> Result<imaging::batch::BatchSummary, String> {
+    let options = resolve_image_options(request)?;
+
+    let output_dir = output_dir
+        .filter(|d| !d.trim().is_empty())
+        .map(std::path::PathBuf::from);
+    if let Some(dir) = &output_dir {
+        std::fs::create_dir_all(dir)
+            .map_err(|e| format!("Failed to create output folder: {}", e))?;
+    }
+
+    let files = imaging::batch::expand_inputs(&inputs)?;
+    if files.is_empty() {
+        return Err("No images found to process".to_string());
+    }
+    let jobs = imaging::batch::plan(files, output_dir.as_deref(), &options.extension());
+
+    let workers = workers.unwrap_or_else(|| {
+        std::thread::available_parallelism()
+            .map(|n| n.get())
+            .unwrap_or(2)
+    });
+    let cancel = Arc::new(std::sync::Mutex::new(false));
+    {
+        let mut batches = state.image_batches.lock().map_err(|e| e.to_string())?;
+        if batches.contains_key(&batch_id) {
+            return Err(format!("Image batch {} is already running", batch_id));
         }
-        "webp" => {
-            use image::codecs::webp::{WebPEncoder, WebPQuality};
+        batches.insert(batch_id.clone(), cancel.clone());
+    }
 
-            let q = if optimize { 75 } else { quality.unwrap_or(80) };
-            let mut writer = std::io::BufWriter::new(output_file);
-            #[allow(deprecated)]
-            let encoder = WebPEncoder::new_with_quality(&mut writer, WebPQuality::lossy(q));
+    let id = batch_id.clone();
+    let summary = tauri::async_runtime::spawn_blocking(move || {
+        imaging::batch::run(&jobs, &options, workers, &cancel, |progress| {
+            let _ = window.emit(
+                "image-batch-progress",
+                serde_json::json!({ "batch_id": id, "progress": progress }),
+            );
+        })
+    })
+    .await
+    .map_err(|e| e.to_string());
+    state
+        .image_batches
+        .lock()
+        .map_err(|e| e.to_string())?
+        .remove(&batch_id);
+    summary
+}
 
-            encoder
-                .encode(img.as_bytes(), img.width(), img.height(), img.color())
-                .map_err(|e| format!("Failed to encode WebP: {}", e))?;
-        }
-        "avif" => {
-            let (width, height) = (img.width() as usize, img.height() as usize);
-            let rgba = img.to_rgba8();
-            let pixels = rgba.as_raw();
+/// Stops the running `process_images_batch` started with `batch_id` once the files
+/// in progress are written. Batches that already finished are ignored.
+#[tauri::command]
+async fn cancel_image_batch(state: State<'_, AppState>, batch_id: String) -> Result<(), String> {
+    let batches = state.image_batches.lock().map_err(|e| e.to_string())?;
+    if let Some(cancel) = batches.get(&batch_id) {
+        *cancel.lock().map_err(|e| e.to_string())? = true;
+    }
+    Ok(())
+}
 
-            // Map raw bytes to RGBA8 pixels manually (safe and simple)
-            let pixels_rgba: Vec<ravif::RGBA8> = pixels
-                .chunks_exact(4)
-                .map(|c| ravif::RGBA8::new(c[0], c[1], c[2], c[3]))
-                .collect();
+/// EXIF summary (date, camera, author, location...), every EXIF tag and the XMP
+/// packet of an image, to check what a conversion would keep or strip.
+#[tauri::command]
+async fn inspect_image_metadata(path: String) -> Result<imaging::metadata::ImageMetadata, String> {
+    tauri::async_runtime::spawn_blocking(move || {
+        imaging::metadata::inspect(std::path::Path::new(&path))
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
 
-            let img_ravif = ravif::Img::new(pixels_rgba.as_slice(), width, height);
+/// Saves every frame of an animated GIF, WebP or APNG as numbered PNGs in
+/// `output_dir`, with each frame's display time.
+#[tauri::command]
+async fn extract_image_frames(
+    input_path: String,
+    output_dir: String,
+) -> Result<Vec<imaging::animation::ExtractedFrame>, String> {
+    tauri::async_runtime::spawn_blocking(move || {
+        imaging::animation::extract(
+            std::path::Path::new(&input_path),
+            std::path::Path::new(&output_dir),
+        )
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
 
-            let q = if optimize {
-                70.0
-            } else {
-                quality.unwrap_or(80) as f32
-            };
-            let speed = 8; // Faster encoding for better UX
-
-            let res = ravif::Encoder::new()
-                .with_quality(q)
-                .with_speed(speed)
-                .encode_rgba(img_ravif)
-                .map_err(|e| format!("AVIF encoding failed: {}", e))?;
-
-            let avif_file = res.avif_file;
-
-            use std::io::Write;
-            let mut writer = std::io::BufWriter::new(output_file);
-            writer
-                .write_all(&avif_file)
-                .map_err(|e| format!("Failed to write AVIF file: {}", e))?;
-        }
-        "bmp" => {
-            img.write_to(&mut std::io::BufWriter::new(output_file), ImageFormat::Bmp)
-                .map_err(|e| format!("Failed to write BMP: {}", e))?;
-        }
-        "gif" => {
-            img.write_to(&mut std::io::BufWriter::new(output_file), ImageFormat::Gif)
-                .map_err(|e| format!("Failed to write GIF: {}", e))?;
-        }
-        "tiff" => {
-            img.write_to(&mut std::io::BufWriter::new(output_file), ImageFormat::Tiff)
-                .map_err(|e| format!("Failed to write TIFF: {}", e))?;
-        }
-        "heic" | "heif" => {
-            // MacOS native conversion using sips
-            // Since we already resized 'img' if requested, we should save it to a temp folder first
-            // or if no resize was requested, just call sips on the input file.
-
-            use std::process::Command;
-
-            // If we resized, we need to export the resized version to a temp file first
-            if width.is_some() || height.is_some() {
-                let temp_path = format!("{}.tmp.png", output_path);
-                img.write_to(
-                    &mut std::io::BufWriter::new(
-                        File::create(&temp_path).map_err(|e| e.to_string())?,
-                    ),
-                    ImageFormat::Png,
-                )
-                .map_err(|e| format!("Failed to write temp PNG for HEIC conversion: {}", e))?;
-
-                let output = Command::new("sips")
-                    .args(["-s", "format", "heic", &temp_path, "--out", &output_path])
-                    .output()
-                    .map_err(|e| format!("Sips command failed: {}", e))?;
-
-                let _ = std::fs::remove_file(temp_path);
-
-                if !output.status.success() {
-                    return Err(format!(
-                        "sips failed: {}",
-                        String::from_utf8_lossy(&output.stderr)
-                    ));
-                }
-            } else {
-                // No resize needed, just convert input to output directly
-                let output = Command::new("sips")
-                    .args(["-s", "format", "heic", &input_path, "--out", &output_path])
-                    .output()
-                    .map_err(|e| format!("Sips command failed: {}", e))?;
-
-                if !output.status.success() {
-                    return Err(format!(
-                        "sips failed: {}",
-                        String::from_utf8_lossy(&output.stderr)
-                    ));
-                }
-            }
-        }
-        _ => return Err(format!("Unsupported output format: {}", format)),
+#[tauri::command]
+async fn list_image_presets() -> Result<Vec<imaging::ImagePreset>, String> {
+    let config: AppConfig = confy::load("mouse-crazy-app", None).unwrap_or_default();
+    Ok(config.image_presets)
+}
+
+/// Adds a preset, or replaces the one with the same name (case-insensitive). Pass
+/// `previous_name` to rename an existing preset. Returns the updated list.
+#[tauri::command]
+async fn save_image_preset(
+    preset: imaging::ImagePreset,
+    previous_name: Option<String>,
+) -> Result<Vec<imaging::ImagePreset>, String> {
+    let name = preset.name.trim().to_string();
+    if name.is_empty() {
+        return Err("Preset name cannot be empty".to_string());
+    }
+    preset.options.validate()?;
+
+    let config: AppConfig = confy::load("mouse-crazy-app", None).unwrap_or_default();
+    let mut presets = config.image_presets;
+    let previous = previous_name.unwrap_or_else(|| name.clone());
+    let index = presets
+        .iter()
+        .position(|p| p.name.eq_ignore_ascii_case(previous.trim()));
+    let clashes = presets
+        .iter()
+        .enumerate()
+        .any(|(i, p)| Some(i) != index && p.name.eq_ignore_ascii_case(&name));
+    if clashes {
+        return Err(format!("A preset named {} already exists", name));
     }
 
-    Ok(())
+    let preset = imaging::ImagePreset {
+        name,
+        options: preset.options,
+    };
+    match index {
+        Some(index) => presets[index] = preset,
+        None => presets.push(preset),
+    }
+    update_config(|config| config.image_presets = presets.clone())?;
+    Ok(presets)
 }
 
+/// Returns the updated list.
 #[tauri::command]
-async fn extract_text_from_screen(window: tauri::WebviewWindow) -> Result<String, String> {
+async fn delete_image_preset(name: String) -> Result<Vec<imaging::ImagePreset>, String> {
+    let config: AppConfig = confy::load("mouse-crazy-app", None).unwrap_or_default();
+    let mut presets = config.image_presets;
+    let before = presets.len();
+    presets.retain(|p| !p.name.eq_ignore_ascii_case(name.trim()));
+    if presets.len() == before {
+        return Err(format!("Image preset not found: {}", name));
+    }
+    update_config(|config| config.image_presets = presets.clone())?;
+    Ok(presets)
+}
+
+#[tauri::command]
+async fn extract_text_from_screen(
+    window: tauri::WebviewWindow,
+    mode: Option<ocr::OutputMode>,
+) -> Result<ocr::OcrOutput, String> {
     let spawn_result_island = |app_handle: &tauri::AppHandle, status: &str| {
         if let Some(island) = app_handle.get_webview_window("ocr-island") {
             let _ = island.close();
@@ -1378,530 +1567,252 @@ async fn extract_text_from_screen(window: tauri::WebviewWindow) -> Result<String
         });
     };
 
-    #[cfg(target_os = "macos")]
-    {
-        use std::fs;
-        use std::process::Command;
-
-        // 0. Preliminary check: Is 'swift' available?
-        let swift_check = Command::new("which")
-            .arg("swift")
-            .output()
-            .map_err(|e| format!("Failed to check for swift: {}", e))?;
+    let app_handle = window.app_handle().clone();
 
-        if !swift_check.status.success() {
-            return Err("El comando 'swift' no está disponible. Por favor, instala Xcode Command Line Tools (ejecuta 'xcode-select --install' en la Terminal).".to_string());
+    let (img, region) = match pick_screen_region(&window, Some(mode.unwrap_or_default())).await {
+        Ok(Some((img, region, _))) => (img, region),
+        // Cancelled (Esc or an empty selection)
+        Ok(None) => return Ok(ocr::OcrOutput::default()),
+        Err(e) => {
+            spawn_result_island(&app_handle, "error");
+            return Err(e);
         }
+    };
 
-        let was_visible = window.is_visible().unwrap_or(false);
-
-        // Ensure the window is fully hidden before taking the screenshot
-        if was_visible {
-            let _ = window.hide();
-            // Give macOS time to animate the window away
-            tokio::time::sleep(std::time::Duration::from_millis(300)).await;
+    let _ = app_handle.emit("ocr-start", ());
+
+    // Show island "Copiando texto..." while OCR runs (same pill UI as shutdown countdown)
+    let ocr_island_label = "ocr-island";
+    if let Some(existing) = app_handle.get_webview_window(ocr_island_label) {
+        let _ = existing.close();
+    }
+    let _ocr_island = tauri::WebviewWindowBuilder::new(
+        &app_handle,
+        ocr_island_label,
+        tauri::WebviewUrl::App("island.html?mode=ocr&status=loading".parse().unwrap()),
+    )
+    .title("OCR")
+    .inner_size(240.0, 75.0)
+    .transparent(true)
+    .decorations(false)
+    .always_on_top(true)
+    .resizable(false)
+    .skip_taskbar(true)
+    .shadow(false)
+    .position(0.0, 30.0)
+    .build()
+    .ok();
+    if let Some(ref island) = app_handle.get_webview_window(ocr_island_label) {
+        if let Ok(Some(monitor)) = island.current_monitor() {
+            let mw = monitor.size().width as f64;
+            let ww = island
+                .outer_size()
+                .unwrap_or(tauri::PhysicalSize::new(240, 75))
+                .width as f64;
+            let x = mw / 2.0 - ww / 2.0;
+            let _ = island.set_position(tauri::Position::Physical(tauri::PhysicalPosition::new(
+                x as i32, 20,
+            )));
         }
+    }
 
-        let temp_image_path = "/tmp/mouse_crazy_ocr_capture.png";
+    // Give the island a longer moment to mount its listener and load Vite bundles
+    tokio::time::sleep(std::time::Duration::from_millis(1000)).await;
 
-        // 1. Trigger interactive screencapture.
-        // -i = interactive (selection), -x = no sound
-        let capture_res = tauri::async_runtime::spawn_blocking(move || {
-            Command::new("screencapture")
-                .arg("-i")
-                .arg("-x")
-                .arg(temp_image_path)
-                .output()
-        })
-        .await
-        .map_err(|e| e.to_string())?;
+    let languages = app_handle
+        .state::<AppState>()
+        .ocr_languages
+        .lock()
+        .map(|l| l.clone())
+        .unwrap_or_else(|_| ocr::default_languages());
 
-        // After capture is complete (or aborted), show the window again only if it was visible
-        if was_visible {
-            let _ = window.show();
-            let _ = window.set_focus();
+    let ocr_res = tauri::async_runtime::spawn_blocking(move || {
+        let engine = ocr::default_engine();
+        engine
+            .recognize(&img, &languages)
+            .map(|result| (result, img))
+            .map_err(|e| format!("OCR error ({}): {}", engine.name(), e))
+    })
+    .await
+    .map_err(|e| {
+        if let Some(w) = app_handle.get_webview_window(ocr_island_label) {
+            let _ = w.close();
         }
+        e.to_string()
+    })?;
+
+    let _ = app_handle.emit("ocr-end", ());
+
+    let res = ocr_res.map(|(result, img)| (ocr::OcrOutput::new(result, region.ocr_mode), img));
+
+    if let Ok((output, img)) = &res {
+        if !output.text.trim().is_empty() {
+            let history_region = Some(ocr::history::HistoryRegion {
+                monitor: region.monitor,
+                scale_factor: region.scale_factor,
+                x: region.x,
+                y: region.y,
+                w: region.w,
+                h: region.h,
+            });
+            // A failed history write is reported but never fails the OCR itself
+            if let Err(e) = record_ocr_history(&app_handle, output, history_region, img) {
+                notify_user(&app_handle, "OCR history", &e);
+            }
+        }
+    }
 
-        let app_handle = window.app_handle();
-        let ocr_island_label = "ocr-island";
-
-        let res = match capture_res {
-            Ok(output) => {
-                // If user pressed Escape to cancel, the file might not exist
-                if !std::path::Path::new(temp_image_path).exists() {
-                    // Try to differentiate between Cancel and "No Permission"
-                    // Usually if capture fails due to TCC permissions, it might return a non-zero exit code or stderr message
-                    if !output.status.success() {
-                        let stderr = String::from_utf8_lossy(&output.stderr);
-                        if stderr.contains("denied") || stderr.contains("permission") {
-                            return Err("Permiso de 'Grabación de Pantalla' denegado. Por favor, habilítalo en Ajustes del Sistema > Privacidad y Seguridad.".to_string());
-                        }
-                        return Err(format!("Error en captura: {}", stderr));
-                    }
-
-                    // If status is success but file doesn't exist, it's likely a cancel (Esc)
-                    return Ok("".to_string());
-                }
-
-                use tauri::Emitter;
-                let _ = app_handle.emit("ocr-start", ());
-
-                // Show island "Copiando texto..." while OCR runs
-                if let Some(existing) = app_handle.get_webview_window(ocr_island_label) {
-                    let _ = existing.close();
-                }
-                let _ocr_island = tauri::WebviewWindowBuilder::new(
-                    app_handle,
-                    ocr_island_label,
-                    tauri::WebviewUrl::App("island.html?mode=ocr&status=loading".parse().unwrap()),
-                )
-                .title("OCR")
-                .inner_size(240.0, 75.0)
-                .transparent(true)
-                .decorations(false)
-                .always_on_top(true)
-                .resizable(false)
-                .skip_taskbar(true)
-                .shadow(false)
-                .position(0.0, 30.0)
-                .build()
-                .ok();
-
-                if let Some(ref island) = app_handle.get_webview_window(ocr_island_label) {
-                    if let Ok(Some(monitor)) = island.current_monitor() {
-                        let mw = monitor.size().width as f64;
-                        let ww = island
-                            .outer_size()
-                            .unwrap_or(tauri::PhysicalSize::new(240, 75))
-                            .width as f64;
-                        let x = mw / 2.0 - ww / 2.0;
-                        let _ = island.set_position(tauri::Position::Physical(
-                            tauri::PhysicalPosition::new(x as i32, 20),
-                        ));
-                    }
-                }
-
-                // Give the island a longer moment to mount its listener and load Vite bundles
-                tokio::time::sleep(std::time::Duration::from_millis(1000)).await;
-
-                // 2. Swift script to run Vision OCR on the image
-                let swift_script = r#"
-                    import Vision
-                    import Cocoa
-
-                    let imagePath = "/tmp/mouse_crazy_ocr_capture.png"
-                    guard let image = NSImage(contentsOfFile: imagePath),
-                          let tiffData = image.tiffRepresentation,
-                          let bitmap = NSBitmapImageRep(data: tiffData),
-                          let cgImage = bitmap.cgImage else {
-                        print("ERROR: Failed to load image. If this happens consistently, check Screen Recording permissions.")
-                        exit(1)
-                    }
-
-                    let request = VNRecognizeTextRequest { (request, error) in
-                        guard let observations = request.results as? [VNRecognizedTextObservation] else { return }
-                        let text = observations.compactMap { $0.topCandidates(1).first?.string }.joined(separator: "\n")
-                        if let encoded = text.data(using: .utf8)?.base64EncodedString() {
-                            print("BASE64:\(encoded)")
-                        } else {
-                            print(text)
-                        }
-                    }
-                    request.recognitionLevel = .accurate
-                    request.usesLanguageCorrection = true
-                    
-                    let desiredLangs = ["es-ES", "en-US"]
-                    if let supportedLangs = try? VNRecognizeTextRequest.supportedRecognitionLanguages(for: .accurate, revision: request.revision) {
-                        let validLangs = desiredLangs.filter { supportedLangs.contains($0) }
-                        request.recognitionLanguages = validLangs.isEmpty ? supportedLangs : validLangs
-                    } else {
-                        request.recognitionLanguages = desiredLangs
-                    }
-
-                    let handler = VNImageRequestHandler(cgImage: cgImage, options: [:])
-                    do {
-                        try handler.perform([request])
-                    } catch {
-                        print("ERROR: \(error)")
-                        exit(1)
-                    }
-                "#;
-                
-                // Write swift script to a temporary file instead of using -e (fixes error on M1 macs without swift-driver)
-                let swift_script_path = "/tmp/mouse_crazy_ocr_script.swift";
-                if let Err(e) = std::fs::write(swift_script_path, swift_script) {
-                    println!("Failed to write swift script: {}", e);
-                }
+    let res = res.map(|(output, _)| output);
+    spawn_result_island(&app_handle, ocr::island_status(&res));
 
-                let ocr_res = tauri::async_runtime::spawn_blocking(move || {
-                    Command::new("swift").arg(swift_script_path).output()
-                })
-                .await
-                .map_err(|e| e.to_string())?;
-
-                let _ = std::fs::remove_file(swift_script_path);
-
-                // 3. Clean up the temp image
-                let _ = fs::remove_file(temp_image_path);
-
-                let _ = app_handle.emit("ocr-end", ());
-
-                let (emit_status, res) = match ocr_res {
-                    Ok(out) => {
-                        let stdout = String::from_utf8_lossy(&out.stdout).trim().to_string();
-                        let stderr = String::from_utf8_lossy(&out.stderr).trim().to_string();
-
-                        if !out.status.success() || stdout.starts_with("ERROR:") {
-                            let err_msg = if !stdout.is_empty() { stdout } else { stderr };
-                            ("error".to_string(), Err(format!("Error OCR: {}", err_msg)))
-                        } else if stdout.starts_with("BASE64:") {
-                            // Decode Base64 to get perfect UTF-8 string
-                            let b64_data = stdout.trim_start_matches("BASE64:");
-                            use base64::{engine::general_purpose, Engine as _};
-                            match general_purpose::STANDARD.decode(b64_data) {
-                                Ok(bytes) => {
-                                    let text = String::from_utf8_lossy(&bytes).to_string();
-                                    let status = if text.trim().is_empty() {
-                                        "no_text"
-                                    } else {
-                                        "success"
-                                    };
-                                    (status.to_string(), Ok(text))
-                                }
-                                Err(_) => ("success".to_string(), Ok(stdout)),
-                            }
-                        } else {
-                            let status = if stdout.trim().is_empty() {
-                                "no_text"
-                            } else {
-                                "success"
-                            };
-                            (status.to_string(), Ok(stdout))
-                        }
-                    }
-                    Err(e) => (
-                        "error".to_string(),
-                        Err(format!("No se pudo ejecutar el script de OCR: {}", e)),
-                    ),
-                };
+    res
+}
 
-                spawn_result_island(&app_handle, emit_status.as_str());
+/// Shows a window hidden for a capture again when dropped, so every way out of a
+/// capture flow brings it back.
+struct WindowRestore(Option<tauri::WebviewWindow>);
 
-                res
-            }
-            Err(e) => {
-                spawn_result_island(&app_handle, "error");
-                Err(format!("Fallo al iniciar screencapture: {}", e))
-            }
-        };
-        res
+impl WindowRestore {
+    /// Leaves the window hidden, for flows that keep capturing after the pick.
+    fn keep_hidden(mut self) {
+        self.0 = None;
     }
-    #[cfg(target_os = "windows")]
-    {
-        use std::process::Command;
-
-        let app_handle = window.app_handle().clone();
-        let was_visible = window.is_visible().unwrap_or(false);
-
-        // Minimize the sidebar so it's not in the screenshot
-        if was_visible {
-            let _ = window.minimize();
-            tokio::time::sleep(std::time::Duration::from_millis(250)).await;
-        }
+}
 
-        // Create a oneshot channel; store the sender in AppState
-        let (tx, rx) = tokio::sync::oneshot::channel::<Option<(i32, i32, i32, i32)>>();
-        {
-            let state = app_handle.state::<AppState>();
-            let mut lock = state.capture_tx.lock().await;
-            *lock = Some(tx);
+impl Drop for WindowRestore {
+    fn drop(&mut self) {
+        if let Some(window) = self.0.take() {
+            let _ = window.unminimize();
+            let _ = window.show();
+            let _ = window.set_focus();
         }
+    }
+}
 
-        // Open the Tauri transparent fullscreen overlay window (capture.html)
-        // This is instant — no console window, no PowerShell needed for the UI
-        if let Some(existing) = app_handle.get_webview_window("capture") {
-            let _ = existing.close();
-        }
-        let _cap_win = tauri::WebviewWindowBuilder::new(
-            &app_handle,
-            "capture",
-            tauri::WebviewUrl::App("capture.html".into()),
-        )
-        .fullscreen(true)
-        .transparent(true)
-        .decorations(false)
-        .always_on_top(true)
-        .skip_taskbar(true)
-        .shadow(false)
-        .resizable(false)
-        .build()
-        .map_err(|e| format!("Failed to open capture window: {}", e))?;
+/// Lets the user pick a screen region on the capture.html overlay and returns its
+/// pixels and the selection (with the OCR output mode chosen on the overlay), or
+/// `None` if cancelled. The overlay shows its OCR mode toolbar only when `mode` is
+/// set, starting on that mode.
+///
+/// `window` is hidden while picking and comes back on cancel and errors; on success
+/// it comes back when the returned [`WindowRestore`] is dropped.
+async fn pick_screen_region(
+    window: &tauri::WebviewWindow,
+    mode: Option<ocr::OutputMode>,
+) -> Result<Option<(image::RgbaImage, CaptureRegion, WindowRestore)>, String> {
+    let app_handle = window.app_handle().clone();
+    let was_visible = window.is_visible().unwrap_or(false);
+
+    // Get the sidebar out of the screenshot
+    if was_visible {
+        #[cfg(target_os = "macos")]
+        let _ = window.hide();
+        #[cfg(not(target_os = "macos"))]
+        let _ = window.minimize();
+        tokio::time::sleep(std::time::Duration::from_millis(250)).await;
+    }
+    let restore = WindowRestore(was_visible.then(|| window.clone()));
 
-        // Wait for the user to select a region (or cancel)
-        let region = rx.await.ok().flatten();
+    // Create a oneshot channel; store the sender in AppState
+    let (tx, rx) = tokio::sync::oneshot::channel::<Option<CaptureRegion>>();
+    {
+        let state = app_handle.state::<AppState>();
+        let mut lock = state.capture_tx.lock().await;
+        *lock = Some(tx);
+    }
+
+    // Open a transparent fullscreen overlay (capture.html) on every monitor
+    // This is instant — no console window, no PowerShell needed for the UI
+    close_capture_overlays(&app_handle);
+    let monitors = window
+        .available_monitors()
+        .map_err(|e| format!("Failed to list monitors: {}", e))?;
+    let capture_monitors =
+        tauri::async_runtime::spawn_blocking(|| capture::default_capturer().monitors())
+            .await
+            .map_err(|e| e.to_string())??;
+    let capture_url = match mode.and_then(|m| serde_json::to_value(m).ok()) {
+        Some(serde_json::Value::String(m)) => format!("capture.html?mode={}", m),
+        _ => "capture.html".to_string(),
+    };
 
-        let (sx, sy, sw, sh) = match region {
-            Some(r) => r,
-            None => {
-                if was_visible {
-                    let _ = window.unminimize();
-                    let _ = window.show();
-                    let _ = window.set_focus();
-                }
-                return Ok("".to_string());
-            }
+    for m in &monitors {
+        // Match the Tauri monitor to the capturer's by its center point (both physical)
+        let center_x = m.position().x + m.size().width as i32 / 2;
+        let center_y = m.position().y + m.size().height as i32 / 2;
+        let Some(index) = capture_monitors
+            .iter()
+            .find(|cm| cm.contains(center_x, center_y))
+            .map(|cm| cm.index)
+        else {
+            continue;
         };
-
-        if sw <= 0 || sh <= 0 {
-            if was_visible {
-                let _ = window.unminimize();
-                let _ = window.show();
-                let _ = window.set_focus();
-            }
-            return Ok("".to_string());
+        let label = format!("{}{}", CAPTURE_OVERLAY_PREFIX, index);
+        if app_handle.get_webview_window(&label).is_some() {
+            continue;
         }
 
-        use tauri::Emitter;
-        let _ = app_handle.emit("ocr-start", ());
-
-        // Show island "Copiando texto..." while OCR runs (same pill UI as shutdown countdown)
-        let ocr_island_label = "ocr-island";
-        if let Some(existing) = app_handle.get_webview_window(ocr_island_label) {
-            let _ = existing.close();
-        }
-        let _ocr_island = tauri::WebviewWindowBuilder::new(
+        // Builder positions are logical, in the target monitor's own scale
+        let scale = m.scale_factor();
+        let builder = tauri::WebviewWindowBuilder::new(
             &app_handle,
-            ocr_island_label,
-            tauri::WebviewUrl::App("island.html?mode=ocr&status=loading".parse().unwrap()),
+            &label,
+            tauri::WebviewUrl::App(capture_url.clone().into()),
         )
-        .title("OCR")
-        .inner_size(240.0, 75.0)
+        .position(m.position().x as f64 / scale, m.position().y as f64 / scale)
         .transparent(true)
         .decorations(false)
         .always_on_top(true)
-        .resizable(false)
         .skip_taskbar(true)
         .shadow(false)
-        .position(0.0, 30.0)
-        .build()
-        .ok();
-        if let Some(ref island) = app_handle.get_webview_window(ocr_island_label) {
-            if let Ok(Some(monitor)) = island.current_monitor() {
-                let mw = monitor.size().width as f64;
-                let ww = island
-                    .outer_size()
-                    .unwrap_or(tauri::PhysicalSize::new(240, 75))
-                    .width as f64;
-                let x = mw / 2.0 - ww / 2.0;
-                let _ = island.set_position(tauri::Position::Physical(
-                    tauri::PhysicalPosition::new(x as i32, 20),
-                ));
-            }
+        .resizable(false);
+        // Native fullscreen on macOS animates into a new Space, so cover the monitor instead
+        #[cfg(target_os = "macos")]
+        let builder = builder.inner_size(
+            m.size().width as f64 / scale,
+            m.size().height as f64 / scale,
+        );
+        #[cfg(not(target_os = "macos"))]
+        let builder = builder.fullscreen(true);
+        if let Err(e) = builder.build() {
+            close_capture_overlays(&app_handle);
+            return Err(format!("Failed to open capture window: {}", e));
         }
+    }
 
-        // Give the island a longer moment to mount its listener
-        tokio::time::sleep(std::time::Duration::from_millis(1000)).await;
+    if app_handle
+        .webview_windows()
+        .keys()
+        .all(|label| !label.starts_with(CAPTURE_OVERLAY_PREFIX))
+    {
+        return Err("No monitor found for the capture overlay".to_string());
+    }
 
-        let temp_image_path = std::env::temp_dir().join("mouse_crazy_ocr_capture.png");
-        let temp_image_str = temp_image_path.to_string_lossy().to_string();
+    // Wait for the user to select a region (or cancel)
+    let region = match rx.await.ok().flatten() {
+        Some(r) if r.w > 0 && r.h > 0 => r,
+        _ => return Ok(None),
+    };
 
-        let combined_ps = r#"
-$ErrorActionPreference = 'Stop'
-Add-Type -AssemblyName System.Drawing
-Add-Type -AssemblyName System.Runtime.WindowsRuntime
+    let img = tauri::async_runtime::spawn_blocking(move || {
+        capture::default_capturer().capture(capture::CaptureTarget::Region {
+            monitor: region.monitor,
+            x: region.x,
+            y: region.y,
+            width: region.w as u32,
+            height: region.h as u32,
+        })
+    })
+    .await
+    .map_err(|e| e.to_string())?;
 
-try {
-    # 1. Capture Screen
-    $x=[int]$env:CAP_X; $y=[int]$env:CAP_Y; $w=[int]$env:CAP_W; $h=[int]$env:CAP_H
-    $bmp = New-Object System.Drawing.Bitmap($w, $h)
-    $g   = [System.Drawing.Graphics]::FromImage($bmp)
-    $g.CopyFromScreen($x, $y, 0, 0, (New-Object System.Drawing.Size($w, $h)))
-    $g.Dispose()
-    
-    # 2. Scale 2x for better OCR recognition quality
-    $sw = $w * 2; $sh = $h * 2
-    $sbmp = New-Object System.Drawing.Bitmap($sw, $sh)
-    $sg = [System.Drawing.Graphics]::FromImage($sbmp)
-    $sg.InterpolationMode = [System.Drawing.Drawing2D.InterpolationMode]::HighQualityBicubic
-    $sg.DrawImage($bmp, 0, 0, $sw, $sh)
-    $sg.Dispose()
-    $bmp.Dispose()
-
-    $sbmp.Save($env:OCR_IMG_PATH, [System.Drawing.Imaging.ImageFormat]::Png)
-    $sbmp.Dispose()
-
-    # 2. Perform OCR (load WinRT types)
-    $null = [Windows.Media.Ocr.OcrEngine,             Windows.Foundation, ContentType=WindowsRuntime]
-    $null = [Windows.Graphics.Imaging.BitmapDecoder,  Windows.Graphics,   ContentType=WindowsRuntime]
-    $null = [Windows.Graphics.Imaging.SoftwareBitmap, Windows.Graphics,   ContentType=WindowsRuntime]
-    $null = [Windows.Globalization.Language,          Windows.Globalization, ContentType=WindowsRuntime]
-
-    $asTaskGM = [System.WindowsRuntimeSystemExtensions].GetMethods() |
-        Where-Object { $_.Name -eq 'AsTask' -and $_.IsGenericMethodDefinition -and $_.GetParameters().Count -eq 1 } |
-        Select-Object -First 1
-
-    function Await { param($op, $type)
-        $asTaskGM.MakeGenericMethod($type).Invoke($null, @($op)).GetAwaiter().GetResult()
-    }
-
-    $fileStream = [System.IO.File]::OpenRead($env:OCR_IMG_PATH)
-    $ras        = [System.IO.WindowsRuntimeStreamExtensions]::AsRandomAccessStream($fileStream)
-    $decoder    = Await ([Windows.Graphics.Imaging.BitmapDecoder]::CreateAsync($ras)) ([Windows.Graphics.Imaging.BitmapDecoder])
-    $softBmp    = Await ($decoder.GetSoftwareBitmapAsync())                            ([Windows.Graphics.Imaging.SoftwareBitmap])
-    $fileStream.Dispose()
-    Remove-Item $env:OCR_IMG_PATH -ErrorAction SilentlyContinue
-
-    # Prefer Spanish so OCR preserves accents (á, é, í, ó, ú, ñ)
-    $engine = $null
-    try {
-        $langEs = [Windows.Globalization.Language]::new('es-ES')
-        $engine = [Windows.Media.Ocr.OcrEngine]::TryCreateFromLanguage($langEs)
-    } catch { }
-    if ($null -eq $engine) {
-        try {
-            $langMx = [Windows.Globalization.Language]::new('es-MX')
-            $engine = [Windows.Media.Ocr.OcrEngine]::TryCreateFromLanguage($langMx)
-        } catch { }
-    }
-    if ($null -eq $engine) {
-        $engine = [Windows.Media.Ocr.OcrEngine]::TryCreateFromUserProfileLanguages()
-    }
-    if ($null -eq $engine) {
-        try {
-            $langEn = [Windows.Globalization.Language]::new('en-US')
-            $engine = [Windows.Media.Ocr.OcrEngine]::TryCreateFromLanguage($langEn)
-        } catch { }
-    }
-
-    $result = Await ($engine.RecognizeAsync($softBmp)) ([Windows.Media.Ocr.OcrResult])
-    if ($result -and $result.Lines) {
-        $lines = @($result.Lines)
-        $fullText = ""
-        for ($i = 0; $i -lt $lines.Count; $i++) {
-            $line = $lines[$i]
-            $fullText += $line.Text
-            if ($i -lt ($lines.Count - 1)) {
-                try {
-                    $nextLine = $lines[$i+1]
-                    # Robust coordinate access: Cast to double to ensure safe math
-                    $lineRect = $line.Rect
-                    $nextRect = $nextLine.Rect
-                    $gap = [double]$nextRect.Y - ([double]$lineRect.Y + [double]$lineRect.Height)
-                    $avgHeight = ([double]$lineRect.Height + [double]$nextRect.Height) / 2
-                    
-                    if ($gap -gt ($avgHeight * 0.7)) {
-                        $fullText += "`r`n`r`n"
-                    } else {
-                        $fullText += "`r`n"
-                    }
-                } catch {
-                    # Fallback if coordinate math fails
-                    $fullText += "`r`n"
-                }
-            }
+    match img {
+        Ok(img) => Ok(Some((img, region, restore))),
+        Err(e) => {
+            #[cfg(target_os = "macos")]
+            let e = format!("{}. Si el problema persiste, revisa el permiso de 'Grabación de Pantalla' en Ajustes del Sistema > Privacidad y Seguridad.", e);
+            Err(e)
         }
-        # Bulletproof: Encode output as Base64 to bypass all console encoding issues
-        $bytes = [System.Text.Encoding]::UTF8.GetBytes($fullText)
-        $b64 = [System.Convert]::ToBase64String($bytes)
-        Write-Output "B64:$b64"
-    }
-} catch {
-    Write-Error $_.Exception.Message
-    exit 1
-}
-"#;
-
-        let img_path_env = temp_image_str.clone();
-        let ocr_res = tauri::async_runtime::spawn_blocking(move || {
-            #[allow(unused_mut)]
-            let mut cmd = Command::new("powershell");
-            cmd.arg("-NoProfile")
-                .arg("-NonInteractive")
-                .arg("-WindowStyle")
-                .arg("Hidden")
-                .arg("-Command")
-                .arg(combined_ps)
-                .env("CAP_X", sx.to_string())
-                .env("CAP_Y", sy.to_string())
-                .env("CAP_W", sw.to_string())
-                .env("CAP_H", sh.to_string())
-                .env("OCR_IMG_PATH", &img_path_env);
-            #[cfg(target_os = "windows")]
-            {
-                use std::os::windows::process::CommandExt;
-                cmd.creation_flags(0x08000000); // CREATE_NO_WINDOW
-            }
-            cmd.output()
-        })
-        .await
-        .map_err(|e| {
-            if let Some(w) = app_handle.get_webview_window("ocr-island") {
-                let _ = w.close();
-            }
-            if was_visible {
-                let _ = window.unminimize();
-                let _ = window.show();
-            }
-            e.to_string()
-        })?;
-
-        let _ = app_handle.emit("ocr-end", ());
-
-        // Show island "Copiando texto..." while OCR runs
-        let (emit_status, res) = match ocr_res {
-            Ok(out) => {
-                let stdout = String::from_utf8_lossy(&out.stdout).trim().to_string();
-                let stderr = String::from_utf8_lossy(&out.stderr).trim().to_string();
-
-                if !out.status.success() {
-                    (
-                        "error".to_string(),
-                        Err(format!(
-                            "OCR error: {}",
-                            if !stderr.is_empty() {
-                                stderr
-                            } else {
-                                "Unknown PowerShell error".to_string()
-                            }
-                        )),
-                    )
-                } else if stdout.starts_with("B64:") {
-                    let b64_part = stdout[4..].trim();
-                    use base64::{engine::general_purpose, Engine as _};
-                    match general_purpose::STANDARD.decode(b64_part) {
-                        Ok(bytes) => {
-                            let text = String::from_utf8_lossy(&bytes).to_string();
-                            let status = if text.trim().is_empty() {
-                                "no_text"
-                            } else {
-                                "success"
-                            };
-                            (status.to_string(), Ok(text))
-                        }
-                        Err(_) => ("success".to_string(), Ok(stdout)),
-                    }
-                } else {
-                    let status = if stdout.trim().is_empty() {
-                        "no_text"
-                    } else {
-                        "success"
-                    };
-                    (status.to_string(), Ok(stdout))
-                }
-            }
-            Err(e) => (
-                "error".to_string(),
-                Err(format!("Failed to run Combined OCR: {}", e)),
-            ),
-        };
-
-        spawn_result_island(&app_handle, emit_status.as_str());
-
-        res
-    }
-    #[cfg(not(any(target_os = "macos", target_os = "windows")))]
-    {
-        Err("OCR is only supported on macOS and Windows".to_string())
     }
 }
 
@@ -1950,7 +1861,7 @@ async fn write_to_clipboard(text: String) -> Result<(), String> {
         let output = child
             .wait_with_output()
             .map_err(|e| format!("Failed to wait for swift: {}", e))?;
-            
+
         let _ = std::fs::remove_file(swift_script_path);
 
         if !output.status.success() {
@@ -1971,7 +1882,79 @@ async fn write_to_clipboard(text: String) -> Result<(), String> {
     }
     #[cfg(not(any(target_os = "macos", target_os = "windows")))]
     {
-        Err("Not supported on this OS".to_string())
+        tauri::async_runtime::spawn_blocking(move || {
+            set_clipboard(move |set| set.text(text))
+                .map_err(|e| format!("Failed to set clipboard text: {}", e))
+        })
+        .await
+        .map_err(|e| e.to_string())?
+    }
+}
+
+/// Copies HTML (e.g. an OCR table) with a plain-text fallback for apps without rich-text paste.
+#[tauri::command]
+async fn write_html_to_clipboard(html: String, alt_text: Option<String>) -> Result<(), String> {
+    tauri::async_runtime::spawn_blocking(move || {
+        set_clipboard(move |set| set.html(html, alt_text))
+            .map_err(|e| format!("Failed to set clipboard HTML: {}", e))
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+/// Copies the image file at `path` to the clipboard.
+#[tauri::command]
+async fn write_image_to_clipboard(path: String) -> Result<(), String> {
+    let img = image::open(&path)
+        .map_err(|e| format!("Failed to load image: {}", e))?
+        .to_rgba8();
+    tauri::async_runtime::spawn_blocking(move || copy_image_to_clipboard(img))
+        .await
+        .map_err(|e| e.to_string())?
+}
+
+fn copy_image_to_clipboard(img: image::RgbaImage) -> Result<(), String> {
+    let (width, height) = (img.width() as usize, img.height() as usize);
+    set_clipboard(move |set| {
+        set.image(arboard::ImageData {
+            width,
+            height,
+            bytes: std::borrow::Cow::Owned(img.into_raw()),
+        })
+    })
+    .map_err(|e| format!("Failed to set clipboard image: {}", e))
+}
+
+/// Runs `write` against a fresh arboard clipboard. Blocking.
+///
+/// On Linux (X11 and Wayland) the clipboard content is served by the process that
+/// set it and vanishes when its `Clipboard` is dropped, so the write happens on a
+/// thread that keeps ownership until another app copies something.
+fn set_clipboard<F>(write: F) -> Result<(), arboard::Error>
+where
+    F: for<'a> FnOnce(arboard::Set<'a>) -> Result<(), arboard::Error> + Send + 'static,
+{
+    #[cfg(target_os = "linux")]
+    {
+        use arboard::SetExtLinux;
+
+        let (tx, rx) = std::sync::mpsc::channel();
+        std::thread::spawn(move || {
+            let res =
+                arboard::Clipboard::new().and_then(|mut clipboard| write(clipboard.set().wait()));
+            let _ = tx.send(res);
+        });
+        // `wait()` only returns once we lose ownership, so no answer yet means the
+        // content is on the clipboard and being served.
+        match rx.recv_timeout(Duration::from_millis(300)) {
+            Ok(res) => res,
+            Err(_) => Ok(()),
+        }
+    }
+    #[cfg(not(target_os = "linux"))]
+    {
+        let mut clipboard = arboard::Clipboard::new()?;
+        write(clipboard.set())
     }
 }
 
@@ -1992,92 +1975,883 @@ fn notify_user<R: tauri::Runtime>(app: &tauri::AppHandle<R>, _title: &str, messa
     }
 }
 
+/// Where `save_paint_capture` sends the screenshot.
+#[derive(Deserialize, Debug, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+enum PaintCaptureMode {
+    #[default]
+    File,
+    Clipboard,
+    Both,
+}
+
+#[derive(Serialize, Debug)]
+struct PaintCaptureResult {
+    /// Saved file, when the mode writes one
+    path: Option<String>,
+    copied: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+struct CaptureSettings {
+    /// None saves to Downloads
+    save_dir: Option<String>,
+    filename_template: String,
+}
+
+/// Grabs `target` (the whole primary screen by default). PaintBoard.tsx hides its
+/// toolbar first, so the annotations on the overlay end up in the image.
 #[tauri::command]
-async fn save_paint_capture(app_handle: tauri::AppHandle) -> Result<(), String> {
-    use std::process::Command;
-    use std::time::{SystemTime, UNIX_EPOCH};
-    use tauri::Manager;
+async fn save_paint_capture(
+    app_handle: tauri::AppHandle,
+    mode: Option<PaintCaptureMode>,
+    target: Option<capture::CaptureTarget>,
+) -> Result<PaintCaptureResult, String> {
+    let mode = mode.unwrap_or_default();
+    let target = target.unwrap_or(capture::CaptureTarget::FullScreen);
+    let config: AppConfig = confy::load("mouse-crazy-app", None).unwrap_or_default();
+
+    let img = tauri::async_runtime::spawn_blocking(move || {
+        let capturer = capture::default_capturer();
+        capturer
+            .capture(target)
+            .map_err(|e| format!("Capture error ({}): {}", capturer.name(), e))
+    })
+    .await
+    .map_err(|e| e.to_string())??;
 
-    let downloads_dir = app_handle
-        .path()
-        .download_dir()
-        .map_err(|e| format!("Could not find downloads directory: {}", e))?;
+    let mut result = PaintCaptureResult {
+        path: None,
+        copied: false,
+    };
 
-    let now = SystemTime::now()
-        .duration_since(UNIX_EPOCH)
-        .unwrap_or_default();
+    if mode != PaintCaptureMode::Clipboard {
+        let save_path = capture_output_path(&app_handle, &config, "png")?;
+        img.save(&save_path)
+            .map_err(|e| format!("Failed to save capture: {}", e))?;
+        result.path = Some(save_path.to_string_lossy().to_string());
+    }
 
-    let timestamp = now.as_secs();
+    if mode != PaintCaptureMode::File {
+        tauri::async_runtime::spawn_blocking(move || copy_image_to_clipboard(img))
+            .await
+            .map_err(|e| e.to_string())??;
+        result.copied = true;
+    }
 
-    let filename = format!("TaskGoblin_Capture_{}.png", timestamp);
-    let save_path = downloads_dir.join(filename);
-    let save_path_str = save_path.to_string_lossy().to_string();
+    Ok(result)
+}
 
-    #[cfg(target_os = "macos")]
-    {
-        // -x = no sound
-        let output = Command::new("screencapture")
-            .arg("-x")
-            .arg(&save_path_str)
-            .output()
-            .map_err(|e| format!("Failed to capture screen: {}", e))?;
+/// Where a capture or recording with `extension` is saved: the configured folder
+/// (Downloads by default) and the filename template.
+fn capture_output_path(
+    app_handle: &tauri::AppHandle,
+    config: &AppConfig,
+    extension: &str,
+) -> Result<std::path::PathBuf, String> {
+    let save_dir = match &config.capture_save_dir {
+        Some(dir) if !dir.trim().is_empty() => std::path::PathBuf::from(dir),
+        _ => app_handle
+            .path()
+            .download_dir()
+            .map_err(|e| format!("Could not find downloads directory: {}", e))?,
+    };
+    std::fs::create_dir_all(&save_dir)
+        .map_err(|e| format!("Failed to create save directory: {}", e))?;
 
-        if !output.status.success() {
-            let stderr = String::from_utf8_lossy(&output.stderr);
-            return Err(format!("Screencapture failed: {}", stderr));
-        }
+    let filename = format!(
+        "{}.{}",
+        render_filename_template(&config.capture_filename_template),
+        extension
+    );
+    Ok(save_dir.join(filename))
+}
+
+/// Expands `{timestamp}` (Unix seconds), `{date}` (YYYY-MM-DD) and `{time}`
+/// (HH-MM-SS, local time) and replaces characters that aren't valid in file names.
+fn render_filename_template(template: &str) -> String {
+    let now = chrono::Local::now();
+    let name = template
+        .replace("{timestamp}", &now.timestamp().to_string())
+        .replace("{date}", &now.format("%Y-%m-%d").to_string())
+        .replace("{time}", &now.format("%H-%M-%S").to_string())
+        .replace(['/', '\\', ':', '*', '?', '"', '<', '>', '|'], "_");
+    let name = name.trim();
+    if name.is_empty() {
+        format!("TaskGoblin_Capture_{}", now.timestamp())
+    } else {
+        name.to_string()
     }
+}
 
-    #[cfg(target_os = "windows")]
+#[derive(Serialize, Debug)]
+struct CaptureSources {
+    monitors: Vec<capture::MonitorInfo>,
+    windows: Vec<capture::WindowInfo>,
+}
+
+/// Monitors and windows that `save_paint_capture` can target.
+#[tauri::command]
+async fn list_capture_sources() -> Result<CaptureSources, String> {
+    tauri::async_runtime::spawn_blocking(|| {
+        let capturer = capture::default_capturer();
+        Ok(CaptureSources {
+            monitors: capturer.monitors()?,
+            windows: capturer.windows().unwrap_or_default(),
+        })
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+#[tauri::command]
+async fn get_capture_settings() -> Result<CaptureSettings, String> {
+    let config: AppConfig = confy::load("mouse-crazy-app", None).unwrap_or_default();
+    Ok(CaptureSettings {
+        save_dir: config.capture_save_dir,
+        filename_template: config.capture_filename_template,
+    })
+}
+
+#[tauri::command]
+async fn set_capture_settings(settings: CaptureSettings) -> Result<(), String> {
+    if settings.filename_template.trim().is_empty() {
+        return Err("Filename template cannot be empty".to_string());
+    }
+    update_config(|config| {
+        config.capture_save_dir = settings.save_dir.filter(|d| !d.trim().is_empty());
+        config.capture_filename_template = settings.filename_template;
+    })
+}
+
+/// Seconds of countdown shown on the island before recording starts
+const RECORDING_COUNTDOWN_SECS: u64 = 3;
+
+#[derive(Serialize, Debug)]
+struct RecordingStatus {
+    /// "idle", "starting", "countdown" or "recording"
+    state: &'static str,
+    countdown_secs: u64,
+    elapsed_secs: u64,
+}
+
+/// Lets the user pick a region, then records it after a short countdown shown on the
+/// recording island (which also holds the stop button). Returns false if the
+/// selection was cancelled. The file goes where paint captures go.
+#[tauri::command]
+async fn start_region_recording(
+    window: tauri::WebviewWindow,
+    state: State<'_, AppState>,
+    fps: Option<u32>,
+    format: Option<recording::RecordingFormat>,
+    max_secs: Option<u64>,
+) -> Result<bool, String> {
     {
-        // Use PowerShell to capture the primary screen.
-        // PaintBoard.tsx already handles hiding its toolbar.
-        let ps_script = format!(
-            r#"
-            $ErrorActionPreference = 'Stop'
-            Add-Type -AssemblyName System.Drawing
-            Add-Type -AssemblyName System.Windows.Forms
-            $screen = [System.Windows.Forms.Screen]::PrimaryScreen
-            $width = $screen.Bounds.Width
-            $height = $screen.Bounds.Height
-            $left = $screen.Bounds.Left
-            $top = $screen.Bounds.Top
-            $bmp = New-Object System.Drawing.Bitmap($width, $height)
-            $g = [System.Drawing.Graphics]::FromImage($bmp)
-            $g.CopyFromScreen($left, $top, 0, 0, $bmp.Size)
-            $bmp.Save('{}', [System.Drawing.Imaging.ImageFormat]::Png)
-            $g.Dispose()
-            $bmp.Dispose()
-            "#,
-            save_path_str.replace("'", "''")
-        );
+        let mut lock = state.recording.lock().map_err(|e| e.to_string())?;
+        if !matches!(*lock, RecordingState::Idle) {
+            return Err("A recording is already in progress".to_string());
+        }
+        *lock = RecordingState::Starting;
+    }
 
-        #[allow(unused_mut)]
-        let mut cmd = Command::new("powershell");
-        cmd.arg("-NoProfile")
-            .arg("-NonInteractive")
-            .arg("-Command")
-            .arg(ps_script);
+    let app_handle = window.app_handle().clone();
+    let fps = fps.unwrap_or(recording::DEFAULT_FPS);
+    let format = format.unwrap_or_default();
+    let max_secs = max_secs.unwrap_or(recording::DEFAULT_MAX_SECS);
+    let picked = async {
+        let Some((_, region, restore)) = pick_screen_region(&window, None).await? else {
+            return Ok(None);
+        };
+        let config: AppConfig = confy::load("mouse-crazy-app", None).unwrap_or_default();
+        let path = capture_output_path(&app_handle, &config, format.extension())?;
+        Ok::<_, String>(Some((region, path, restore)))
+    }
+    .await;
 
-        #[cfg(target_os = "windows")]
+    let (region, path) = {
+        let mut lock = state.recording.lock().map_err(|e| e.to_string())?;
+        // Stopped while the region was being picked
+        if !matches!(*lock, RecordingState::Starting) {
+            return Ok(false);
+        }
+        let (region, path, restore) = match picked {
+            Ok(Some(picked)) => picked,
+            other => {
+                *lock = RecordingState::Idle;
+                return other.map(|_| false);
+            }
+        };
+        *lock = RecordingState::Countdown {
+            starts_at: Instant::now() + Duration::from_secs(RECORDING_COUNTDOWN_SECS),
+        };
+        // Out of the recorded area until the user brings it back
+        restore.keep_hidden();
+        (region, path)
+    };
+    show_recording_island(&app_handle);
+
+    tauri::async_runtime::spawn(async move {
+        tokio::time::sleep(Duration::from_secs(RECORDING_COUNTDOWN_SECS)).await;
+
+        let target = capture::CaptureTarget::Region {
+            monitor: region.monitor,
+            x: region.x,
+            y: region.y,
+            width: region.w as u32,
+            height: region.h as u32,
+        };
+        let started = tauri::async_runtime::spawn_blocking(move || {
+            recording::Recorder::start(target, fps, max_secs, format, path)
+        })
+        .await
+        .map_err(|e| e.to_string())
+        .and_then(|r| r);
+
+        let state = app_handle.state::<AppState>();
         {
-            use std::os::windows::process::CommandExt;
-            cmd.creation_flags(0x08000000); // CREATE_NO_WINDOW
+            let Ok(mut lock) = state.recording.lock() else {
+                return;
+            };
+            let counting_down = matches!(*lock, RecordingState::Countdown { .. });
+            match started {
+                Ok(recorder) if counting_down => {
+                    *lock = RecordingState::Recording(recorder);
+                }
+                // Stopped during the countdown: throw the recording away
+                Ok(recorder) => {
+                    drop(lock);
+                    tauri::async_runtime::spawn_blocking(move || {
+                        if let Ok(result) = recorder.stop() {
+                            let _ = std::fs::remove_file(result.path);
+                        }
+                    });
+                    return;
+                }
+                Err(e) => {
+                    if counting_down {
+                        *lock = RecordingState::Idle;
+                        drop(lock);
+                        if let Some(w) = app_handle.get_webview_window("recording-island") {
+                            let _ = w.close();
+                        }
+                        notify_user(&app_handle, "Recording", &e);
+                    }
+                    return;
+                }
+            }
         }
 
-        let output = cmd
-            .output()
-            .map_err(|e| format!("Failed to capture screen on Windows: {}", e))?;
+        // The recorder stops grabbing at max_secs on its own; finish the file then
+        tokio::time::sleep(Duration::from_secs(max_secs)).await;
+        let expired = matches!(
+            &*state.recording.lock().unwrap_or_else(|e| e.into_inner()),
+            RecordingState::Recording(r) if r.elapsed() >= Duration::from_secs(max_secs)
+        );
+        if expired {
+            let _ = finish_recording(&app_handle).await;
+        }
+    });
 
-        if !output.status.success() {
-            let stderr = String::from_utf8_lossy(&output.stderr);
-            return Err(format!("PowerShell capture failed: {}", stderr));
+    Ok(true)
+}
+
+/// Stops the current recording (or cancels the countdown) and returns the saved file.
+#[tauri::command]
+async fn stop_region_recording(
+    app_handle: tauri::AppHandle,
+) -> Result<Option<recording::RecordingResult>, String> {
+    finish_recording(&app_handle).await
+}
+
+#[tauri::command]
+async fn get_recording_status(state: State<'_, AppState>) -> Result<RecordingStatus, String> {
+    let lock = state.recording.lock().map_err(|e| e.to_string())?;
+    Ok(match &*lock {
+        RecordingState::Idle => RecordingStatus {
+            state: "idle",
+            countdown_secs: 0,
+            elapsed_secs: 0,
+        },
+        RecordingState::Starting => RecordingStatus {
+            state: "starting",
+            countdown_secs: 0,
+            elapsed_secs: 0,
+        },
+        RecordingState::Countdown { starts_at } => RecordingStatus {
+            state: "countdown",
+            countdown_secs: starts_at
+                .saturating_duration_since(Instant::now())
+                .as_secs_f64()
+                .ceil() as u64,
+            elapsed_secs: 0,
+        },
+        RecordingState::Recording(recorder) => RecordingStatus {
+            state: "recording",
+            countdown_secs: 0,
+            elapsed_secs: recorder.elapsed().as_secs(),
+        },
+    })
+}
+
+/// Ends the recording, closes the island and emits `recording-finished` with the result.
+async fn finish_recording(
+    app_handle: &tauri::AppHandle,
+) -> Result<Option<recording::RecordingResult>, String> {
+    let previous = {
+        let state = app_handle.state::<AppState>();
+        let mut lock = state.recording.lock().map_err(|e| e.to_string())?;
+        std::mem::replace(&mut *lock, RecordingState::Idle)
+    };
+
+    if let Some(w) = app_handle.get_webview_window("recording-island") {
+        let _ = w.close();
+    }
+
+    let RecordingState::Recording(recorder) = previous else {
+        return Ok(None);
+    };
+
+    let result = tauri::async_runtime::spawn_blocking(move || recorder.stop())
+        .await
+        .map_err(|e| e.to_string())??;
+    let _ = app_handle.emit("recording-finished", &result);
+    Ok(Some(result))
+}
+
+fn show_recording_island(app_handle: &tauri::AppHandle) {
+    let label = "recording-island";
+    if let Some(existing) = app_handle.get_webview_window(label) {
+        let _ = existing.close();
+    }
+    let _ = tauri::WebviewWindowBuilder::new(
+        app_handle,
+        label,
+        tauri::WebviewUrl::App("island.html?mode=recording".into()),
+    )
+    .title("Recording")
+    .inner_size(240.0, 75.0)
+    .transparent(true)
+    .decorations(false)
+    .always_on_top(true)
+    .resizable(false)
+    .skip_taskbar(true)
+    .shadow(false)
+    .position(0.0, 30.0)
+    .build();
+
+    // Center island at top of screen
+    if let Some(island) = app_handle.get_webview_window(label) {
+        if let Ok(Some(monitor)) = island.current_monitor() {
+            let mw = monitor.size().width as f64;
+            let ww = island
+                .outer_size()
+                .unwrap_or(tauri::PhysicalSize::new(240, 75))
+                .width as f64;
+            let x = mw / 2.0 - ww / 2.0;
+            let _ = island.set_position(tauri::Position::Physical(tauri::PhysicalPosition::new(
+                x as i32, 20,
+            )));
+        }
+    }
+}
+
+/// Scroll wheel notches for the first step of a scrolling capture; adapted as the
+/// real scroll distance per notch becomes known.
+const SCROLL_CAPTURE_START_NOTCHES: i32 = 3;
+const SCROLL_CAPTURE_MAX_NOTCHES: i32 = 24;
+const SCROLL_CAPTURE_MAX_FRAMES: u32 = 40;
+/// Stop growing the image past this height, in pixels
+const SCROLL_CAPTURE_MAX_HEIGHT: u32 = 30_000;
+/// Time for smooth scrolling and lazy-loaded content to settle before each grab
+const SCROLL_CAPTURE_SETTLE: Duration = Duration::from_millis(350);
+
+#[derive(Serialize, Debug)]
+struct ScrollCaptureResult {
+    /// Saved PNG, when the mode writes one
+    path: Option<String>,
+    copied: bool,
+    width: u32,
+    height: u32,
+    frames: u32,
+    /// Text recognized in the whole stitched image, when requested
+    ocr: Option<ocr::OcrOutput>,
+}
+
+/// Lets the user select a region, then scrolls the content under it with the mouse
+/// wheel, grabbing it after each step and stitching everything into one tall PNG.
+/// Stops at the end of the content, after `max_frames` or on `stop_scrolling_capture`.
+/// `mode` works as in `save_paint_capture`; `extract_text` also runs OCR on the result.
+/// Returns `None` if the selection was cancelled.
+#[tauri::command]
+async fn capture_scrolling(
+    window: tauri::WebviewWindow,
+    state: State<'_, AppState>,
+    mode: Option<PaintCaptureMode>,
+    extract_text: Option<bool>,
+    max_frames: Option<u32>,
+) -> Result<Option<ScrollCaptureResult>, String> {
+    let app_handle = window.app_handle().clone();
+    let (first, region, restore) = match pick_screen_region(&window, None).await? {
+        Some(capture) => capture,
+        None => return Ok(None),
+    };
+
+    let stop = state.scroll_capture_stop.clone();
+    *stop.lock().map_err(|e| e.to_string())? = false;
+    let max_frames = max_frames.unwrap_or(SCROLL_CAPTURE_MAX_FRAMES).max(1);
+
+    let img = tauri::async_runtime::spawn_blocking(move || {
+        let stitcher = scroll_and_stitch(&region, first, max_frames, &stop)?;
+        Ok::<_, String>((stitcher.frames(), stitcher.finish()?))
+    })
+    .await
+    .map_err(|e| e.to_string())?;
+    // Kept out of the frames until the scrolling is done
+    drop(restore);
+    let (frames, img) = img?;
+
+    let mode = mode.unwrap_or_default();
+    let config: AppConfig = confy::load("mouse-crazy-app", None).unwrap_or_default();
+    let mut result = ScrollCaptureResult {
+        path: None,
+        copied: false,
+        width: img.width(),
+        height: img.height(),
+        frames,
+        ocr: None,
+    };
+
+    if mode != PaintCaptureMode::Clipboard {
+        let save_path = capture_output_path(&app_handle, &config, "png")?;
+        img.save(&save_path)
+            .map_err(|e| format!("Failed to save capture: {}", e))?;
+        result.path = Some(save_path.to_string_lossy().to_string());
+    }
+
+    if extract_text.unwrap_or(false) {
+        let languages = state
+            .ocr_languages
+            .lock()
+            .map(|l| l.clone())
+            .unwrap_or_else(|_| ocr::default_languages());
+        let ocr_img = img.clone();
+        let output = tauri::async_runtime::spawn_blocking(move || {
+            let engine = ocr::default_engine();
+            engine
+                .recognize(&ocr_img, &languages)
+                .map(|r| ocr::OcrOutput::new(r, ocr::OutputMode::default()))
+                .map_err(|e| format!("OCR error ({}): {}", engine.name(), e))
+        })
+        .await
+        .map_err(|e| e.to_string())??;
+        if !output.text.trim().is_empty() {
+            if let Err(e) = record_ocr_history(&app_handle, &output, None, &img) {
+                notify_user(&app_handle, "OCR history", &e);
+            }
         }
+        result.ocr = Some(output);
+    }
+
+    if mode != PaintCaptureMode::File {
+        tauri::async_runtime::spawn_blocking(move || copy_image_to_clipboard(img))
+            .await
+            .map_err(|e| e.to_string())??;
+        result.copied = true;
     }
 
+    Ok(Some(result))
+}
+
+/// Ends a running scrolling capture after its current step; what was stitched so far
+/// is kept.
+#[tauri::command]
+async fn stop_scrolling_capture(state: State<'_, AppState>) -> Result<(), String> {
+    *state
+        .scroll_capture_stop
+        .lock()
+        .map_err(|e| e.to_string())? = true;
     Ok(())
 }
 
+/// Blocking scroll loop behind `capture_scrolling`. The step size adapts: it doubles
+/// while steps move less than a third of the region, and an overshoot (no overlap
+/// with the previous frame) is scrolled back and retried with half the notches.
+fn scroll_and_stitch(
+    region: &CaptureRegion,
+    first: image::RgbaImage,
+    max_frames: u32,
+    stop: &std::sync::Mutex<bool>,
+) -> Result<capture::Stitcher, String> {
+    use enigo::{Axis, Coordinate};
+
+    let capturer = capture::default_capturer();
+    let target = capture::CaptureTarget::Region {
+        monitor: region.monitor,
+        x: region.x,
+        y: region.y,
+        width: region.w as u32,
+        height: region.h as u32,
+    };
+    let monitor = capturer
+        .monitors()?
+        .into_iter()
+        .find(|m| m.index == region.monitor)
+        .ok_or_else(|| format!("Monitor {} not found", region.monitor))?;
+
+    // The wheel scrolls whatever is under the pointer, so park it mid-region.
+    // enigo takes points on macOS and physical pixels elsewhere.
+    let center_x = monitor.x + region.x + region.w / 2;
+    let center_y = monitor.y + region.y + region.h / 2;
+    #[cfg(target_os = "macos")]
+    let (center_x, center_y) = (
+        (center_x as f64 / region.scale_factor).round() as i32,
+        (center_y as f64 / region.scale_factor).round() as i32,
+    );
+    let mut enigo = Enigo::new(&Settings::default())
+        .map_err(|e| format!("Failed to control the mouse: {}", e))?;
+    enigo
+        .move_mouse(center_x, center_y, Coordinate::Abs)
+        .map_err(|e| format!("Failed to move the mouse: {}", e))?;
+
+    let mut stitcher = capture::Stitcher::new(first);
+    let mut notches = SCROLL_CAPTURE_START_NOTCHES;
+
+    while stitcher.frames() < max_frames && stitcher.height() < SCROLL_CAPTURE_MAX_HEIGHT {
+        if *stop.lock().map_err(|e| e.to_string())? {
+            break;
+        }
+
+        enigo
+            .scroll(notches, Axis::Vertical)
+            .map_err(|e| format!("Failed to scroll: {}", e))?;
+        std::thread::sleep(SCROLL_CAPTURE_SETTLE);
+        let frame = capturer.capture(target)?;
+
+        match stitcher.push(frame)? {
+            capture::StitchStep::Scrolled(px) => {
+                if px < region.h as u32 / 3 {
+                    notches = (notches * 2).min(SCROLL_CAPTURE_MAX_NOTCHES);
+                }
+            }
+            // Nothing moved: the end of the content (or it doesn't scroll at all)
+            capture::StitchStep::Unchanged => break,
+            capture::StitchStep::NoOverlap => {
+                if notches == 1 {
+                    break;
+                }
+                let _ = enigo.scroll(-notches, Axis::Vertical);
+                notches = (notches / 2).max(1);
+                std::thread::sleep(SCROLL_CAPTURE_SETTLE);
+            }
+        }
+    }
+
+    Ok(stitcher)
+}
+
+/// The capture the annotation editor draws over, as sent to the frontend.
+#[derive(Serialize, Debug)]
+struct AnnotationCanvas {
+    width: u32,
+    height: u32,
+    /// PNG data URL of the untouched capture
+    image: String,
+}
+
+#[derive(Serialize, Debug)]
+struct AnnotationProjectView {
+    canvas: AnnotationCanvas,
+    annotations: Vec<annotate::Annotation>,
+}
+
+fn png_data_url(img: &image::RgbaImage) -> Result<String, String> {
+    use base64::{engine::general_purpose, Engine as _};
+
+    let png = annotate::encode(img, annotate::ExportFormat::Png, None)?;
+    Ok(format!(
+        "data:image/png;base64,{}",
+        general_purpose::STANDARD.encode(png)
+    ))
+}
+
+fn annotation_canvas(img: &image::RgbaImage) -> Result<AnnotationCanvas, String> {
+    Ok(AnnotationCanvas {
+        width: img.width(),
+        height: img.height(),
+        image: png_data_url(img)?,
+    })
+}
+
+fn annotation_base(state: &AppState) -> Result<image::RgbaImage, String> {
+    state
+        .annotation_base
+        .lock()
+        .map_err(|e| e.to_string())?
+        .clone()
+        .ok_or_else(|| "No capture to annotate. Take a capture first.".to_string())
+}
+
+/// Captures once for the annotation editor: `target` directly, or a region picked
+/// on the overlay when it is omitted. Returns `None` if the selection was cancelled.
+#[tauri::command]
+async fn capture_for_annotation(
+    window: tauri::WebviewWindow,
+    state: State<'_, AppState>,
+    target: Option<capture::CaptureTarget>,
+) -> Result<Option<AnnotationCanvas>, String> {
+    let img = match target {
+        Some(target) => tauri::async_runtime::spawn_blocking(move || {
+            let capturer = capture::default_capturer();
+            capturer
+                .capture(target)
+                .map_err(|e| format!("Capture error ({}): {}", capturer.name(), e))
+        })
+        .await
+        .map_err(|e| e.to_string())??,
+        None => match pick_screen_region(&window, None).await? {
+            Some((img, _, _)) => img,
+            None => return Ok(None),
+        },
+    };
+
+    let canvas = annotation_canvas(&img)?;
+    *state.annotation_base.lock().map_err(|e| e.to_string())? = Some(img);
+    Ok(Some(canvas))
+}
+
+/// Renders `annotations` over the current capture and returns a JPEG data URL,
+/// scaled down to `max_width` if given, for previews.
+#[tauri::command]
+async fn render_annotations(
+    state: State<'_, AppState>,
+    annotations: Vec<annotate::Annotation>,
+    max_width: Option<u32>,
+) -> Result<String, String> {
+    use base64::{engine::general_purpose, Engine as _};
+
+    let base = annotation_base(&state)?;
+    let jpeg = tauri::async_runtime::spawn_blocking(move || {
+        let mut img = annotate::render(&base, &annotations)?;
+        if let Some(max_width) = max_width.filter(|w| *w > 0 && *w < img.width()) {
+            let height = (img.height() as f64 * max_width as f64 / img.width() as f64) as u32;
+            img = image::imageops::resize(
+                &img,
+                max_width,
+                height.max(1),
+                image::imageops::FilterType::Triangle,
+            );
+        }
+        annotate::encode(&img, annotate::ExportFormat::Jpeg, Some(85))
+    })
+    .await
+    .map_err(|e| e.to_string())??;
+
+    Ok(format!(
+        "data:image/jpeg;base64,{}",
+        general_purpose::STANDARD.encode(jpeg)
+    ))
+}
+
+/// Renders `annotations` at full resolution and saves and/or copies the result like
+/// `save_paint_capture`. `quality` applies to JPEG and WebP.
+#[tauri::command]
+async fn export_annotations(
+    app_handle: tauri::AppHandle,
+    state: State<'_, AppState>,
+    annotations: Vec<annotate::Annotation>,
+    format: Option<annotate::ExportFormat>,
+    quality: Option<u8>,
+    mode: Option<PaintCaptureMode>,
+) -> Result<PaintCaptureResult, String> {
+    let base = annotation_base(&state)?;
+    let format = format.unwrap_or_default();
+    let mode = mode.unwrap_or_default();
+    let config: AppConfig = confy::load("mouse-crazy-app", None).unwrap_or_default();
+
+    let img = tauri::async_runtime::spawn_blocking(move || annotate::render(&base, &annotations))
+        .await
+        .map_err(|e| e.to_string())??;
+
+    let mut result = PaintCaptureResult {
+        path: None,
+        copied: false,
+    };
+
+    if mode != PaintCaptureMode::Clipboard {
+        let save_path = capture_output_path(&app_handle, &config, format.extension())?;
+        let encoded = img.clone();
+        let bytes = tauri::async_runtime::spawn_blocking(move || {
+            annotate::encode(&encoded, format, quality)
+        })
+        .await
+        .map_err(|e| e.to_string())??;
+        std::fs::write(&save_path, bytes).map_err(|e| format!("Failed to save capture: {}", e))?;
+        result.path = Some(save_path.to_string_lossy().to_string());
+    }
+
+    if mode != PaintCaptureMode::File {
+        tauri::async_runtime::spawn_blocking(move || copy_image_to_clipboard(img))
+            .await
+            .map_err(|e| e.to_string())??;
+        result.copied = true;
+    }
+
+    Ok(result)
+}
+
+/// Saves the current capture and `annotations` as an editable project, to `path` or
+/// next to the other captures. Returns where it was written.
+#[tauri::command]
+async fn save_annotation_project(
+    app_handle: tauri::AppHandle,
+    state: State<'_, AppState>,
+    annotations: Vec<annotate::Annotation>,
+    path: Option<String>,
+) -> Result<String, String> {
+    let base = annotation_base(&state)?;
+    let path = match path {
+        Some(path) => std::path::PathBuf::from(path),
+        None => {
+            let config: AppConfig = confy::load("mouse-crazy-app", None).unwrap_or_default();
+            capture_output_path(&app_handle, &config, annotate::PROJECT_EXTENSION)?
+        }
+    };
+
+    let save_path = path.clone();
+    tauri::async_runtime::spawn_blocking(move || {
+        annotate::Project::new(&base, annotations)?.save(&save_path)
+    })
+    .await
+    .map_err(|e| e.to_string())??;
+
+    Ok(path.to_string_lossy().to_string())
+}
+
+/// Loads a project saved by `save_annotation_project` and makes its capture the one
+/// being annotated.
+#[tauri::command]
+async fn open_annotation_project(
+    state: State<'_, AppState>,
+    path: String,
+) -> Result<AnnotationProjectView, String> {
+    let (img, annotations) = tauri::async_runtime::spawn_blocking(move || {
+        let project = annotate::Project::load(std::path::Path::new(&path))?;
+        Ok::<_, String>((project.base_image()?, project.annotations))
+    })
+    .await
+    .map_err(|e| e.to_string())??;
+
+    let canvas = annotation_canvas(&img)?;
+    *state.annotation_base.lock().map_err(|e| e.to_string())? = Some(img);
+    Ok(AnnotationProjectView {
+        canvas,
+        annotations,
+    })
+}
+
+#[derive(Serialize, Debug)]
+struct RedactionResult {
+    /// PNG data URL of the redacted image
+    image: String,
+    width: u32,
+    height: u32,
+    /// Saved file, when `mode` writes one
+    path: Option<String>,
+    copied: bool,
+    /// What was hidden, with masked values
+    findings: Vec<pii::PiiFinding>,
+}
+
+/// OCRs a capture (the image at `path`, or a region picked on the overlay), finds
+/// the personal data in `kinds` (all of them by default) and pixelates it. The
+/// redacted image is only returned unless `mode` also saves or copies it, like
+/// `save_paint_capture`. Returns `None` if the selection was cancelled.
+#[tauri::command]
+async fn redact_capture(
+    app_handle: tauri::AppHandle,
+    window: tauri::WebviewWindow,
+    state: State<'_, AppState>,
+    path: Option<String>,
+    kinds: Option<Vec<pii::PiiKind>>,
+    mode: Option<PaintCaptureMode>,
+) -> Result<Option<RedactionResult>, String> {
+    let img = match path {
+        Some(path) => image::open(&path)
+            .map_err(|e| format!("Failed to open image: {}", e))?
+            .to_rgba8(),
+        None => match pick_screen_region(&window, None).await? {
+            Some((img, _, _)) => img,
+            None => return Ok(None),
+        },
+    };
+
+    let kinds = kinds
+        .filter(|k| !k.is_empty())
+        .unwrap_or_else(|| pii::PiiKind::ALL.to_vec());
+    let languages = state
+        .ocr_languages
+        .lock()
+        .map(|l| l.clone())
+        .unwrap_or_else(|_| ocr::default_languages());
+
+    let (redacted, findings) = tauri::async_runtime::spawn_blocking(move || {
+        let engine = ocr::default_engine();
+        let result = engine
+            .recognize(&img, &languages)
+            .map_err(|e| format!("OCR error ({}): {}", engine.name(), e))?;
+        let findings = pii::detect(&result, &kinds);
+
+        // A little padding so antialiased glyph edges don't survive around the blocks
+        let layers: Vec<annotate::Annotation> = findings
+            .iter()
+            .map(|f| {
+                let pad = (f.bbox.height * 0.15).max(2.0);
+                annotate::Annotation::Pixelate {
+                    rect: annotate::Rect {
+                        x: f.bbox.x - pad,
+                        y: f.bbox.y - pad,
+                        w: f.bbox.width + pad * 2.0,
+                        h: f.bbox.height + pad * 2.0,
+                    },
+                    block_size: ((f.bbox.height / 3.0) as u32).max(6),
+                }
+            })
+            .collect();
+        Ok::<_, String>((annotate::render(&img, &layers)?, findings))
+    })
+    .await
+    .map_err(|e| e.to_string())??;
+
+    let mut result = RedactionResult {
+        image: png_data_url(&redacted)?,
+        width: redacted.width(),
+        height: redacted.height(),
+        path: None,
+        copied: false,
+        findings,
+    };
+
+    let Some(mode) = mode else {
+        return Ok(Some(result));
+    };
+    if mode != PaintCaptureMode::Clipboard {
+        let config: AppConfig = confy::load("mouse-crazy-app", None).unwrap_or_default();
+        let save_path = capture_output_path(&app_handle, &config, "png")?;
+        redacted
+            .save(&save_path)
+            .map_err(|e| format!("Failed to save capture: {}", e))?;
+        result.path = Some(save_path.to_string_lossy().to_string());
+    }
+    if mode != PaintCaptureMode::File {
+        tauri::async_runtime::spawn_blocking(move || copy_image_to_clipboard(redacted))
+            .await
+            .map_err(|e| e.to_string())??;
+        result.copied = true;
+    }
+
+    Ok(Some(result))
+}
+
 #[tauri::command]
 fn test_toast(app: tauri::AppHandle) {
     notify_user(&app, "Test Toast", "Esta es una notificación de prueba");
@@ -2285,28 +3059,39 @@ async fn save_pdf_file(path: String, bytes: Vec<u8>) -> Result<(), String> {
     Ok(())
 }
 
-/// Called by the Tauri capture overlay (capture.tsx) when the user releases the mouse.
-/// Closes the overlay window and unblocks extract_text_from_screen with the selected region.
+/// Called by the Tauri capture overlay (capture.tsx) when the user releases the mouse,
+/// with the selection in the overlay's CSS pixels. The monitor and its DPI scale come
+/// from the overlay window itself. Closes every overlay and unblocks the waiting
+/// capture with the selected region.
 #[tauri::command]
 async fn finalize_capture(
     app_handle: tauri::AppHandle,
+    window: tauri::WebviewWindow,
     state: State<'_, AppState>,
-    x: i32,
-    y: i32,
-    w: i32,
-    h: i32,
+    x: f64,
+    y: f64,
+    w: f64,
+    h: f64,
+    mode: Option<ocr::OutputMode>,
 ) -> Result<(), String> {
-    // Close the overlay window first so it doesn't appear in the screenshot
-    if let Some(win) = app_handle.get_webview_window("capture") {
-        let _ = win.close();
-    }
+    let monitor = window
+        .label()
+        .strip_prefix(CAPTURE_OVERLAY_PREFIX)
+        .and_then(|i| i.parse::<usize>().ok())
+        .ok_or("finalize_capture must be called from a capture overlay")?;
+    let scale_factor = window.scale_factor().unwrap_or(1.0);
+    let region =
+        CaptureRegion::from_logical(monitor, scale_factor, x, y, w, h, mode.unwrap_or_default());
+
+    // Close the overlays first so they don't appear in the screenshot
+    close_capture_overlays(&app_handle);
     // Small delay so the window is fully gone before we capture
     tokio::time::sleep(std::time::Duration::from_millis(100)).await;
 
     // Unblock the waiting OCR task
     let mut tx_lock = state.capture_tx.lock().await;
     if let Some(tx) = tx_lock.take() {
-        let _ = tx.send(Some((x, y, w, h)));
+        let _ = tx.send(Some(region));
     }
     Ok(())
 }
@@ -2317,9 +3102,7 @@ async fn cancel_capture(
     app_handle: tauri::AppHandle,
     state: State<'_, AppState>,
 ) -> Result<(), String> {
-    if let Some(win) = app_handle.get_webview_window("capture") {
-        let _ = win.close();
-    }
+    close_capture_overlays(&app_handle);
     let mut tx_lock = state.capture_tx.lock().await;
     if let Some(tx) = tx_lock.take() {
         let _ = tx.send(None); // None = cancelled
@@ -2328,25 +3111,304 @@ async fn cancel_capture(
 }
 
 #[tauri::command]
-async fn process_screenshot_ocr(window: tauri::WebviewWindow) -> Result<String, String> {
-    match extract_text_from_screen(window).await {
-        Ok(text) => {
-            if text.trim().is_empty() {
-                return Ok("NO_TEXT".to_string());
+async fn process_screenshot_ocr(
+    window: tauri::WebviewWindow,
+    mode: Option<ocr::OutputMode>,
+) -> Result<ocr::OcrOutput, String> {
+    match extract_text_from_screen(window, mode).await {
+        Ok(output) => {
+            // Empty text means cancelled or nothing recognized; the frontend shows "no text"
+            if output.text.trim().is_empty() {
+                return Ok(output);
             }
 
-            // Copy to clipboard
-            if let Err(e) = write_to_clipboard(text.clone()).await {
+            // Table modes also copy an HTML table so rich-text apps paste real cells
+            let copied = match &output.table {
+                Some(table) if !table.rows.is_empty() => {
+                    write_html_to_clipboard(table.to_html(), Some(output.text.clone())).await
+                }
+                _ => write_to_clipboard(output.text.clone()).await,
+            };
+            if let Err(e) = copied {
                 return Err(format!("CLIPBOARD_ERROR:{}", e));
             }
 
-            // Return text on success
-            Ok(text)
+            // Return text and layout on success
+            Ok(output)
         }
         Err(e) => Err(e),
     }
 }
 
+/// Reads QR codes and barcodes in a user-selected screen region, copies the decoded
+/// payloads to the clipboard (one per line) and, if `open_urls` is set, opens URLs
+/// in the default browser.
+#[tauri::command]
+async fn decode_codes_from_screen(
+    window: tauri::WebviewWindow,
+    open_urls: Option<bool>,
+) -> Result<Vec<codes::DecodedCode>, String> {
+    use tauri_plugin_opener::OpenerExt;
+
+    let app_handle = window.app_handle().clone();
+
+    let img = match pick_screen_region(&window, None).await? {
+        Some((img, _, _)) => img,
+        None => return Ok(Vec::new()),
+    };
+
+    let found = tauri::async_runtime::spawn_blocking(move || codes::decode(&img))
+        .await
+        .map_err(|e| e.to_string())?;
+
+    if found.is_empty() {
+        return Ok(found);
+    }
+
+    let payload = found
+        .iter()
+        .map(|c| c.text.as_str())
+        .collect::<Vec<_>>()
+        .join("\n");
+    if let Err(e) = write_to_clipboard(payload).await {
+        return Err(format!("CLIPBOARD_ERROR:{}", e));
+    }
+
+    if open_urls.unwrap_or(false) {
+        for code in found.iter().filter(|c| c.is_url) {
+            let _ = app_handle.opener().open_url(code.text.trim(), None::<&str>);
+        }
+    }
+
+    Ok(found)
+}
+
+#[derive(Serialize, Debug)]
+struct PickedColor {
+    /// `#rrggbb`
+    hex: String,
+    r: u8,
+    g: u8,
+    b: u8,
+}
+
+/// Lets the user select a spot on screen and returns its average color.
+#[tauri::command]
+async fn pick_screen_color(window: tauri::WebviewWindow) -> Result<Option<PickedColor>, String> {
+    let img = match pick_screen_region(&window, None).await? {
+        Some((img, _, _)) => img,
+        None => return Ok(None),
+    };
+
+    let count = (img.width() as u64 * img.height() as u64).max(1);
+    let mut sum = [0u64; 3];
+    for px in img.pixels() {
+        for (total, channel) in sum.iter_mut().zip(px.0) {
+            *total += channel as u64;
+        }
+    }
+    let [r, g, b] = sum.map(|total| (total / count) as u8);
+
+    Ok(Some(PickedColor {
+        hex: format!("#{:02x}{:02x}{:02x}", r, g, b),
+        r,
+        g,
+        b,
+    }))
+}
+
+#[derive(Serialize, Debug)]
+struct OcrLanguages {
+    selected: Vec<String>,
+    /// Everything the OCR engine on this machine can recognize
+    supported: Vec<String>,
+}
+
+#[tauri::command]
+async fn get_ocr_languages(state: State<'_, AppState>) -> Result<OcrLanguages, String> {
+    let selected = state
+        .ocr_languages
+        .lock()
+        .map_err(|e| e.to_string())?
+        .clone();
+    let supported =
+        tauri::async_runtime::spawn_blocking(|| ocr::default_engine().supported_languages())
+            .await
+            .map_err(|e| e.to_string())?
+            .unwrap_or_default();
+    Ok(OcrLanguages {
+        selected,
+        supported,
+    })
+}
+
+/// Validates `languages` against the engine and persists them. Returns the
+/// resolved tags (e.g. "de" becomes "de-DE").
+#[tauri::command]
+async fn set_ocr_languages(
+    state: State<'_, AppState>,
+    languages: Vec<String>,
+) -> Result<Vec<String>, String> {
+    let supported =
+        tauri::async_runtime::spawn_blocking(|| ocr::default_engine().supported_languages())
+            .await
+            .map_err(|e| e.to_string())??;
+    let resolved = ocr::validate_languages(&languages, &supported)?;
+
+    *state.ocr_languages.lock().map_err(|e| e.to_string())? = resolved.clone();
+    let to_store = resolved.clone();
+    update_config(|config| config.ocr_languages = to_store)?;
+
+    Ok(resolved)
+}
+
+fn record_ocr_history(
+    app_handle: &tauri::AppHandle,
+    output: &ocr::OcrOutput,
+    region: Option<ocr::history::HistoryRegion>,
+    img: &image::RgbaImage,
+) -> Result<(), String> {
+    let state = app_handle.state::<AppState>();
+    let limit = *state.ocr_history_limit.lock().map_err(|e| e.to_string())?;
+    if limit == 0 {
+        return Ok(());
+    }
+    let store = state.ocr_history.lock().map_err(|e| e.to_string())?;
+    store.add(&output.text, output.mode, region, Some(img), limit)?;
+    Ok(())
+}
+
+/// Newest first; `offset`/`limit` page through long histories.
+#[tauri::command]
+async fn list_ocr_history(
+    state: State<'_, AppState>,
+    offset: Option<usize>,
+    limit: Option<usize>,
+) -> Result<Vec<ocr::history::HistoryEntry>, String> {
+    let store = state.ocr_history.lock().map_err(|e| e.to_string())?;
+    Ok(store.list(offset.unwrap_or(0), limit.unwrap_or(usize::MAX)))
+}
+
+/// Entries whose text contains every word of `query` (case and accent insensitive).
+#[tauri::command]
+async fn search_ocr_history(
+    state: State<'_, AppState>,
+    query: String,
+    limit: Option<usize>,
+) -> Result<Vec<ocr::history::HistoryEntry>, String> {
+    let store = state.ocr_history.lock().map_err(|e| e.to_string())?;
+    Ok(store.search(&query, limit.unwrap_or(usize::MAX)))
+}
+
+/// Deletes the given entries and returns how many were removed.
+#[tauri::command]
+async fn delete_ocr_history(state: State<'_, AppState>, ids: Vec<String>) -> Result<usize, String> {
+    let store = state.ocr_history.lock().map_err(|e| e.to_string())?;
+    store.delete(&ids)
+}
+
+#[tauri::command]
+async fn get_ocr_history_limit(state: State<'_, AppState>) -> Result<usize, String> {
+    Ok(*state.ocr_history_limit.lock().map_err(|e| e.to_string())?)
+}
+
+/// Sets how many results the history keeps (0 disables it) and prunes older entries right away.
+#[tauri::command]
+async fn set_ocr_history_limit(state: State<'_, AppState>, limit: usize) -> Result<(), String> {
+    *state.ocr_history_limit.lock().map_err(|e| e.to_string())? = limit;
+    update_config(|config| config.ocr_history_limit = limit)?;
+    let store = state.ocr_history.lock().map_err(|e| e.to_string())?;
+    store.prune(limit)
+}
+
+#[tauri::command]
+async fn get_clipboard_history(
+    state: State<'_, AppState>,
+) -> Result<Vec<clipboard_history::ClipboardEntryView>, String> {
+    let history = state.clipboard_history.lock().map_err(|e| e.to_string())?;
+    Ok(history.list())
+}
+
+/// Puts a history item back on the clipboard. With `paste` set, the window is hidden
+/// and Cmd/Ctrl+V is sent so the item lands in the app that had focus before.
+#[tauri::command]
+async fn paste_history_item(
+    window: tauri::WebviewWindow,
+    state: State<'_, AppState>,
+    id: u64,
+    paste: Option<bool>,
+) -> Result<(), String> {
+    enum Item {
+        Text(String),
+        Image(image::RgbaImage),
+    }
+
+    let item = {
+        let history = state.clipboard_history.lock().map_err(|e| e.to_string())?;
+        let entry = history
+            .get(id)
+            .ok_or_else(|| format!("Clipboard history item {} not found", id))?;
+        match &entry.content {
+            clipboard_history::ClipboardContent::Text(text) => Item::Text(text.clone()),
+            clipboard_history::ClipboardContent::Image(img) => Item::Image(img.clone()),
+        }
+    };
+
+    match item {
+        Item::Text(text) => write_to_clipboard(text).await?,
+        Item::Image(img) => {
+            tauri::async_runtime::spawn_blocking(move || copy_image_to_clipboard(img))
+                .await
+                .map_err(|e| e.to_string())??
+        }
+    }
+
+    if paste.unwrap_or(false) {
+        hide_window(window).await;
+        // Let the previous app regain focus before sending the shortcut
+        tokio::time::sleep(std::time::Duration::from_millis(250)).await;
+        tauri::async_runtime::spawn_blocking(|| {
+            use enigo::{Direction, Key, Keyboard};
+            #[cfg(target_os = "macos")]
+            let modifier = Key::Meta;
+            #[cfg(not(target_os = "macos"))]
+            let modifier = Key::Control;
+
+            let mut enigo = Enigo::new(&Settings::default())
+                .map_err(|e| format!("Failed to simulate paste: {}", e))?;
+            let _ = enigo.key(modifier, Direction::Press);
+            let _ = enigo.key(Key::Unicode('v'), Direction::Click);
+            let _ = enigo.key(modifier, Direction::Release);
+            Ok::<(), String>(())
+        })
+        .await
+        .map_err(|e| e.to_string())??;
+    }
+
+    Ok(())
+}
+
+#[tauri::command]
+async fn pin_clipboard_item(
+    state: State<'_, AppState>,
+    id: u64,
+    pinned: bool,
+) -> Result<(), String> {
+    let mut history = state.clipboard_history.lock().map_err(|e| e.to_string())?;
+    history.set_pinned(id, pinned)
+}
+
+/// Empties the history; pinned items survive unless `keep_pinned` is false.
+#[tauri::command]
+async fn clear_clipboard_history(
+    state: State<'_, AppState>,
+    keep_pinned: Option<bool>,
+) -> Result<(), String> {
+    let mut history = state.clipboard_history.lock().map_err(|e| e.to_string())?;
+    history.clear(keep_pinned.unwrap_or(true));
+    Ok(())
+}
+
 #[tauri::command]
 async fn set_dialog_open(state: State<'_, AppState>, open: bool) -> Result<(), String> {
     if let Ok(mut dialog_open) = state.is_dialog_open.lock() {
@@ -2429,7 +3491,7 @@ fn spawn_key_listener(app_handle: tauri::AppHandle) {
                         let cap_flag2 = Arc::clone(&cap_flag);
                         tauri::async_runtime::spawn(async move {
                             if let Some(window) = handle.get_webview_window("main") {
-                                let _ = process_screenshot_ocr(window.clone()).await;
+                                let _ = process_screenshot_ocr(window.clone(), None).await;
                             }
                             // Release the guard
                             if let Ok(mut cap) = cap_flag2.lock() {
@@ -2519,14 +3581,50 @@ pub fn run() {
             get_shutdown_time,
             extract_text_from_screen,
             write_to_clipboard,
+            write_html_to_clipboard,
+            write_image_to_clipboard,
             restart_app,
             process_screenshot_ocr,
+            get_ocr_languages,
+            decode_codes_from_screen,
+            pick_screen_color,
+            set_ocr_languages,
+            list_ocr_history,
+            search_ocr_history,
+            delete_ocr_history,
+            get_ocr_history_limit,
+            set_ocr_history_limit,
+            get_clipboard_history,
+            paste_history_item,
+            pin_clipboard_item,
+            clear_clipboard_history,
             convert_pdf_to_word,
             read_pdf_file,
             save_pdf_file,
             set_dialog_open,
             process_image,
+            process_images_batch,
+            cancel_image_batch,
+            inspect_image_metadata,
+            extract_image_frames,
+            list_image_presets,
+            save_image_preset,
+            delete_image_preset,
             save_paint_capture,
+            get_capture_settings,
+            list_capture_sources,
+            start_region_recording,
+            stop_region_recording,
+            get_recording_status,
+            capture_scrolling,
+            stop_scrolling_capture,
+            capture_for_annotation,
+            render_annotations,
+            export_annotations,
+            save_annotation_project,
+            open_annotation_project,
+            redact_capture,
+            set_capture_settings,
             test_toast,
             finalize_capture,
             cancel_capture,
@@ -2535,6 +3633,9 @@ pub fn run() {
         ])
         .setup(|app| {
             let config: AppConfig = confy::load("mouse-crazy-app", None).unwrap_or_default();
+            let clipboard_history = Arc::new(std::sync::Mutex::new(
+                clipboard_history::ClipboardHistory::new(clipboard_history::DEFAULT_CAPACITY),
+            ));
             app.manage(AppState {
                 mouse_moving: std::sync::Mutex::new(false),
                 is_paint_mode: std::sync::Mutex::new(false),
@@ -2549,8 +3650,23 @@ pub fn run() {
                         .last_x
                         .and_then(|x| config.last_y.map(|y| tauri::PhysicalPosition::new(x, y))),
                 ),
+                ocr_languages: std::sync::Mutex::new(config.ocr_languages.clone()),
+                ocr_history: std::sync::Mutex::new(ocr::history::HistoryStore::new(
+                    app.path()
+                        .app_data_dir()
+                        .unwrap_or_else(|_| std::env::temp_dir().join("taskgoblin")),
+                )),
+                ocr_history_limit: std::sync::Mutex::new(config.ocr_history_limit),
+                clipboard_history: clipboard_history.clone(),
+                recording: std::sync::Mutex::new(RecordingState::Idle),
+                scroll_capture_stop: Arc::new(std::sync::Mutex::new(false)),
+                annotation_base: std::sync::Mutex::new(None),
+                image_batches: std::sync::Mutex::new(std::collections::HashMap::new()),
             });
 
+            // Record text and images copied anywhere on the system
+            clipboard_history::spawn_watcher(clipboard_history);
+
             // Start global key listener for Triple-Tap Control
             spawn_key_listener(app.handle().clone());
 
@@ -2688,14 +3804,10 @@ pub fn run() {
                                             *last_pos = Some(pos);
                                         }
                                         // Persist to disk
-                                        let _ = confy::store(
-                                            "mouse-crazy-app",
-                                            None,
-                                            AppConfig {
-                                                last_x: Some(pos.x),
-                                                last_y: Some(pos.y),
-                                            },
-                                        );
+                                        let _ = update_config(|config| {
+                                            config.last_x = Some(pos.x);
+                                            config.last_y = Some(pos.y);
+                                        });
                                         let _ = window_clone.set_position(window_pos);
                                     }
                                     let _ = window_clone.show();
@@ -2741,14 +3853,10 @@ pub fn run() {
                                         let state = app_handle.state::<AppState>();
                                         let mut last_pos = state.last_tray_pos.lock().await;
                                         *last_pos = Some(sidebar_pos);
-                                        let _ = confy::store(
-                                            "mouse-crazy-app",
-                                            None,
-                                            AppConfig {
-                                                last_x: Some(sidebar_pos.x),
-                                                last_y: Some(sidebar_pos.y),
-                                            },
-                                        );
+                                        let _ = update_config(|config| {
+                                            config.last_x = Some(sidebar_pos.x);
+                                            config.last_y = Some(sidebar_pos.y);
+                                        });
                                     }
                                 }
                             }
@@ -2812,7 +3920,7 @@ pub fn run() {
                             let current_pos = device_state.get_mouse().coords;
                             let (cx, cy) = current_pos;
                             let (lx, ly) = last_pos;
-                            
+
                             // Ignore micro-jitter
                             if (cx - lx).abs() > 2 || (cy - ly).abs() > 2 {
                                 last_pos = current_pos;
@@ -2823,14 +3931,19 @@ pub fn run() {
                             // Only wiggle if the mouse has been idle for at least 3 seconds
                             if last_activity.elapsed() >= Duration::from_secs(3) {
                                 if enigo_opt.is_none() {
-                                    let _ = Enigo::new(&Settings::default()).map(|e| enigo_opt = Some(e));
+                                    let _ = Enigo::new(&Settings::default())
+                                        .map(|e| enigo_opt = Some(e));
                                 }
 
                                 if let Some(ref mut enigo) = enigo_opt {
                                     // Wiggle: move and stay until next loop iteration
-                                    let _ = enigo.move_mouse(wiggle_offset, wiggle_offset, enigo::Coordinate::Rel);
+                                    let _ = enigo.move_mouse(
+                                        wiggle_offset,
+                                        wiggle_offset,
+                                        enigo::Coordinate::Rel,
+                                    );
                                     wiggle_offset = -wiggle_offset;
-                                    
+
                                     std::thread::sleep(Duration::from_millis(100));
                                     last_pos = device_state.get_mouse().coords;
                                     last_activity = Instant::now();
@@ -2856,3 +3969,70 @@ pub fn run() {
             _ => {}
         });
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn region(scale_factor: f64, x: f64, y: f64, w: f64, h: f64) -> (i32, i32, i32, i32) {
+        let r = CaptureRegion::from_logical(0, scale_factor, x, y, w, h, ocr::OutputMode::Text);
+        (r.x, r.y, r.w, r.h)
+    }
+
+    #[test]
+    fn capture_region_at_scale_1() {
+        assert_eq!(region(1.0, 10.0, 20.0, 300.0, 200.0), (10, 20, 300, 200));
+        assert_eq!(region(1.0, 10.4, 20.6, 0.05, 1.0), (10, 21, 0, 1));
+    }
+
+    #[test]
+    fn capture_region_at_scale_1_5() {
+        // 15.75 and 10.5 round up, 167.25 down
+        assert_eq!(region(1.5, 10.5, 7.0, 101.0, 33.0), (16, 11, 151, 49));
+        // Side by side selections share their edge pixel-exactly
+        let first = region(1.5, 0.0, 0.0, 10.5, 10.0);
+        let second = region(1.5, 10.5, 0.0, 10.5, 10.0);
+        assert_eq!(first.0 + first.2, second.0);
+        assert_eq!(second.0 + second.2, 32);
+    }
+
+    #[test]
+    fn capture_region_at_scale_2() {
+        assert_eq!(region(2.0, 0.0, 0.0, 960.0, 540.0), (0, 0, 1920, 1080));
+        assert_eq!(region(2.0, 0.25, 0.75, 99.5, 50.0), (1, 2, 199, 100));
+    }
+
+    #[test]
+    fn capture_region_on_a_monitor_at_a_negative_origin() {
+        // Left of and above the primary monitor; the overlay's coordinates start at
+        // its own top-left, so the region stays relative to the monitor
+        let monitor = capture::MonitorInfo {
+            index: 1,
+            name: "Left".to_string(),
+            x: -2400,
+            y: -200,
+            width: 2400,
+            height: 1350,
+            scale_factor: 1.25,
+            is_primary: false,
+        };
+        let r = CaptureRegion::from_logical(
+            monitor.index,
+            monitor.scale_factor as f64,
+            100.0,
+            80.0,
+            400.0,
+            240.0,
+            ocr::OutputMode::Tsv,
+        );
+        assert_eq!((r.monitor, r.x, r.y, r.w, r.h), (1, 125, 100, 500, 300));
+        assert_eq!(r.ocr_mode, ocr::OutputMode::Tsv);
+        assert!(monitor.contains(monitor.x + r.x, monitor.y + r.y));
+        assert!(monitor.contains(monitor.x + r.x + r.w - 1, monitor.y + r.y + r.h - 1));
+
+        // The whole monitor, in its logical size
+        let r =
+            CaptureRegion::from_logical(1, 1.25, 0.0, 0.0, 1920.0, 1080.0, ocr::OutputMode::Text);
+        assert_eq!((r.x, r.y, r.w, r.h), (0, 0, 2400, 1350));
+    }
+}
@@ -0,0 +1,349 @@
+//! Persistent OCR history: one JSON object per line in `ocr_history.jsonl` inside
+//! the app data dir, newest last. Small enough to rewrite whole on delete/prune.
+
+use super::OutputMode;
+use serde::{Deserialize, Serialize};
+use std::io::Write;
+use std::path::PathBuf;
+
+const HISTORY_FILE: &str = "ocr_history.jsonl";
+/// Longest side of the stored thumbnail, in pixels
+const THUMBNAIL_SIZE: u32 = 240;
+
+pub const DEFAULT_LIMIT: usize = 200;
+
+/// Physical pixels relative to monitor `monitor`.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy)]
+pub struct HistoryRegion {
+    #[serde(default)]
+    pub monitor: usize,
+    #[serde(default = "default_scale")]
+    pub scale_factor: f64,
+    pub x: i32,
+    pub y: i32,
+    pub w: i32,
+    pub h: i32,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct HistoryEntry {
+    pub id: String,
+    pub text: String,
+    /// Unix timestamp (seconds)
+    pub created_at: u64,
+    #[serde(default)]
+    pub mode: OutputMode,
+    /// Screen region the text was read from, when the picker reports one
+    #[serde(default)]
+    pub region: Option<HistoryRegion>,
+    /// `data:image/jpeg;base64,...` preview of the capture
+    #[serde(default)]
+    pub thumbnail: Option<String>,
+}
+
+fn default_scale() -> f64 {
+    1.0
+}
+
+pub struct HistoryStore {
+    path: PathBuf,
+}
+
+impl HistoryStore {
+    pub fn new(data_dir: PathBuf) -> Self {
+        Self {
+            path: data_dir.join(HISTORY_FILE),
+        }
+    }
+
+    /// All entries, oldest first. Unreadable lines are skipped rather than failing the whole history.
+    fn load(&self) -> Vec<HistoryEntry> {
+        std::fs::read_to_string(&self.path)
+            .map(|content| {
+                content
+                    .lines()
+                    .filter(|l| !l.trim().is_empty())
+                    .filter_map(|l| serde_json::from_str(l).ok())
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    /// Rewrites the file through a temp file + rename so a crash never leaves half a history.
+    fn save(&self, entries: &[HistoryEntry]) -> Result<(), String> {
+        let mut content = String::new();
+        for entry in entries {
+            content.push_str(&serde_json::to_string(entry).map_err(|e| e.to_string())?);
+            content.push('\n');
+        }
+        let tmp_path = self.path.with_extension("jsonl.tmp");
+        std::fs::write(&tmp_path, content)
+            .map_err(|e| format!("Failed to write OCR history: {}", e))?;
+        std::fs::rename(&tmp_path, &self.path)
+            .map_err(|e| format!("Failed to write OCR history: {}", e))
+    }
+
+    /// Appends a result and prunes the oldest entries beyond `limit`.
+    pub fn add(
+        &self,
+        text: &str,
+        mode: OutputMode,
+        region: Option<HistoryRegion>,
+        image: Option<&image::RgbaImage>,
+        limit: usize,
+    ) -> Result<HistoryEntry, String> {
+        if let Some(dir) = self.path.parent() {
+            std::fs::create_dir_all(dir)
+                .map_err(|e| format!("Failed to create OCR history directory: {}", e))?;
+        }
+
+        let now = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap_or_default();
+        let entry = HistoryEntry {
+            id: now.as_millis().to_string(),
+            text: text.to_string(),
+            created_at: now.as_secs(),
+            mode,
+            region,
+            thumbnail: image.and_then(thumbnail_data_url),
+        };
+
+        let mut file = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .map_err(|e| format!("Failed to open OCR history: {}", e))?;
+        let line = serde_json::to_string(&entry).map_err(|e| e.to_string())?;
+        writeln!(file, "{}", line).map_err(|e| format!("Failed to write OCR history: {}", e))?;
+        drop(file);
+
+        self.prune(limit)?;
+        Ok(entry)
+    }
+
+    /// Drops the oldest entries so at most `limit` remain.
+    pub fn prune(&self, limit: usize) -> Result<(), String> {
+        let entries = self.load();
+        if entries.len() <= limit {
+            return Ok(());
+        }
+        self.save(&entries[entries.len() - limit..])
+    }
+
+    /// Newest first.
+    pub fn list(&self, offset: usize, limit: usize) -> Vec<HistoryEntry> {
+        self.load()
+            .into_iter()
+            .rev()
+            .skip(offset)
+            .take(limit)
+            .collect()
+    }
+
+    /// Entries containing every word of `query`, newest first. Matching ignores
+    /// case and accents so "cancion" finds "Canción".
+    pub fn search(&self, query: &str, limit: usize) -> Vec<HistoryEntry> {
+        let terms: Vec<String> = normalize(query)
+            .split_whitespace()
+            .map(str::to_string)
+            .collect();
+        if terms.is_empty() {
+            return self.list(0, limit);
+        }
+
+        self.load()
+            .into_iter()
+            .rev()
+            .filter(|entry| {
+                let haystack = normalize(&entry.text);
+                terms.iter().all(|t| haystack.contains(t.as_str()))
+            })
+            .take(limit)
+            .collect()
+    }
+
+    /// Removes the entries with the given ids; returns how many were removed.
+    pub fn delete(&self, ids: &[String]) -> Result<usize, String> {
+        let entries = self.load();
+        let before = entries.len();
+        let kept: Vec<HistoryEntry> = entries
+            .into_iter()
+            .filter(|e| !ids.contains(&e.id))
+            .collect();
+        let removed = before - kept.len();
+        if removed > 0 {
+            self.save(&kept)?;
+        }
+        Ok(removed)
+    }
+}
+
+fn thumbnail_data_url(img: &image::RgbaImage) -> Option<String> {
+    use base64::{engine::general_purpose, Engine as _};
+
+    let longest = img.width().max(img.height()).max(1);
+    let scale = (THUMBNAIL_SIZE as f32 / longest as f32).min(1.0);
+    let thumb = image::imageops::thumbnail(
+        img,
+        ((img.width() as f32 * scale) as u32).max(1),
+        ((img.height() as f32 * scale) as u32).max(1),
+    );
+
+    let rgb = image::DynamicImage::ImageRgba8(thumb).to_rgb8();
+    let mut bytes: Vec<u8> = Vec::new();
+    image::codecs::jpeg::JpegEncoder::new_with_quality(&mut bytes, 70)
+        .encode_image(&rgb)
+        .ok()?;
+    Some(format!(
+        "data:image/jpeg;base64,{}",
+        general_purpose::STANDARD.encode(bytes)
+    ))
+}
+
+/// Lowercases and strips the accents common in Spanish/Portuguese/German text.
+fn normalize(text: &str) -> String {
+    text.to_lowercase()
+        .chars()
+        .map(|c| match c {
+            'á' | 'à' | 'â' | 'ä' | 'ã' => 'a',
+            'é' | 'è' | 'ê' | 'ë' => 'e',
+            'í' | 'ì' | 'î' | 'ï' => 'i',
+            'ó' | 'ò' | 'ô' | 'ö' | 'õ' => 'o',
+            'ú' | 'ù' | 'û' | 'ü' => 'u',
+            'ñ' => 'n',
+            'ç' => 'c',
+            _ => c,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn store(name: &str) -> (HistoryStore, PathBuf) {
+        let dir = std::env::temp_dir().join(format!(
+            "taskgoblin-history-{}-{}",
+            name,
+            std::process::id()
+        ));
+        let _ = std::fs::remove_dir_all(&dir);
+        (HistoryStore::new(dir.clone()), dir)
+    }
+
+    fn texts(entries: &[HistoryEntry]) -> Vec<&str> {
+        entries.iter().map(|e| e.text.as_str()).collect()
+    }
+
+    #[test]
+    fn persists_across_stores() {
+        let (history, dir) = store("persist");
+        let region = HistoryRegion {
+            monitor: 1,
+            scale_factor: 2.0,
+            x: -40,
+            y: 10,
+            w: 300,
+            h: 120,
+        };
+        let img = image::RgbaImage::from_pixel(600, 300, image::Rgba([200, 30, 30, 255]));
+        history
+            .add("first", OutputMode::Text, None, None, DEFAULT_LIMIT)
+            .unwrap();
+        history
+            .add(
+                "a\tb",
+                OutputMode::Tsv,
+                Some(region),
+                Some(&img),
+                DEFAULT_LIMIT,
+            )
+            .unwrap();
+
+        let reopened = HistoryStore::new(dir.clone());
+        let entries = reopened.list(0, 10);
+        assert_eq!(texts(&entries), ["a\tb", "first"]);
+        assert_eq!(entries[0].mode, OutputMode::Tsv);
+        let saved = entries[0].region.unwrap();
+        assert_eq!((saved.monitor, saved.scale_factor), (1, 2.0));
+        assert_eq!((saved.x, saved.y, saved.w, saved.h), (-40, 10, 300, 120));
+        assert!(entries[0]
+            .thumbnail
+            .as_deref()
+            .unwrap()
+            .starts_with("data:image/jpeg;base64,"));
+        assert!(entries[1].region.is_none() && entries[1].thumbnail.is_none());
+
+        // A corrupt line loses only itself
+        let path = dir.join(HISTORY_FILE);
+        let mut content = std::fs::read_to_string(&path).unwrap();
+        content.insert_str(0, "{not json\n");
+        std::fs::write(&path, content).unwrap();
+        assert_eq!(texts(&reopened.list(0, 10)), ["a\tb", "first"]);
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn caps_at_the_limit() {
+        let (history, dir) = store("cap");
+        for text in ["one", "two", "three", "four", "five"] {
+            history.add(text, OutputMode::Text, None, None, 3).unwrap();
+        }
+        assert_eq!(texts(&history.list(0, 10)), ["five", "four", "three"]);
+        assert_eq!(texts(&history.list(1, 1)), ["four"]);
+
+        history.prune(1).unwrap();
+        assert_eq!(texts(&history.list(0, 10)), ["five"]);
+
+        let id = history.list(0, 1)[0].id.clone();
+        assert_eq!(history.delete(&["missing".to_string()]).unwrap(), 0);
+        assert_eq!(history.delete(&[id]).unwrap(), 1);
+        assert!(history.list(0, 10).is_empty());
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn searches_every_word_ignoring_case_and_accents() {
+        let (history, dir) = store("search");
+        for text in [
+            "Canción de cuna",
+            "Invoice 2024 total",
+            "CANCION final",
+            "Straße",
+        ] {
+            history
+                .add(text, OutputMode::Text, None, None, DEFAULT_LIMIT)
+                .unwrap();
+        }
+
+        assert_eq!(
+            texts(&history.search("cancion", 10)),
+            ["CANCION final", "Canción de cuna"]
+        );
+        assert_eq!(texts(&history.search("canción", 1)), ["CANCION final"]);
+        assert_eq!(
+            texts(&history.search("cuna  CANCIÓN", 10)),
+            ["Canción de cuna"]
+        );
+        assert_eq!(texts(&history.search("total 2025", 10)), Vec::<&str>::new());
+        assert_eq!(history.search("  ", 10).len(), 4);
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn write_errors_are_returned() {
+        let (_, dir) = store("error");
+        // The data dir is a file, so nothing can be created inside it
+        std::fs::write(&dir, b"").unwrap();
+        let history = HistoryStore::new(dir.clone());
+        let err = history
+            .add("text", OutputMode::Text, None, None, DEFAULT_LIMIT)
+            .unwrap_err();
+        assert!(err.starts_with("Failed to"), "{}", err);
+        std::fs::remove_file(dir).unwrap();
+    }
+}
@@ -0,0 +1,402 @@
+//! Rebuilds columns, paragraphs and reading order from OCR line geometry so every
+//! backend produces the same text for the same screenshot.
+
+use super::{BoundingBox, OcrLine, OcrResult};
+use serde::Serialize;
+
+/// A vertical gap larger than this fraction of the average line height starts a new paragraph.
+const PARAGRAPH_GAP_RATIO: f32 = 0.7;
+/// Horizontal whitespace wider than this many line heights separates two columns.
+const COLUMN_GUTTER_RATIO: f32 = 1.5;
+/// Lines whose vertical extents overlap by more than this fraction sit on the same row.
+const SAME_ROW_OVERLAP: f32 = 0.5;
+
+#[derive(Serialize, Debug, Clone, Default)]
+pub struct Paragraph {
+    pub text: String,
+    pub bbox: BoundingBox,
+    /// Lines in reading order
+    pub lines: Vec<OcrLine>,
+}
+
+#[derive(Serialize, Debug, Clone, Default)]
+pub struct Column {
+    pub bbox: BoundingBox,
+    pub paragraphs: Vec<Paragraph>,
+}
+
+#[derive(Serialize, Debug, Clone, Default)]
+pub struct Layout {
+    /// Columns left to right. A line spanning several columns (a heading over both
+    /// halves of a page) is a column of its own, between the columns above and below it
+    pub columns: Vec<Column>,
+    pub language: Option<String>,
+}
+
+impl Layout {
+    /// Plain text in reading order: lines joined by `\n`, paragraphs and columns by a blank line.
+    pub fn text(&self) -> String {
+        self.columns
+            .iter()
+            .flat_map(|c| c.paragraphs.iter())
+            .map(|p| p.text.as_str())
+            .collect::<Vec<_>>()
+            .join("\n\n")
+    }
+}
+
+pub fn analyze(result: &OcrResult) -> Layout {
+    let lines: Vec<&OcrLine> = result
+        .lines
+        .iter()
+        .filter(|l| !l.text.trim().is_empty())
+        .collect();
+
+    let line_height = average_height(&lines);
+
+    let columns = split_columns(lines, line_height)
+        .into_iter()
+        .map(|mut col_lines| {
+            sort_reading_order(&mut col_lines);
+            let paragraphs = split_paragraphs(&col_lines, line_height);
+            let bbox = paragraphs
+                .iter()
+                .fold(BoundingBox::default(), |acc, p| acc.union(&p.bbox));
+            Column { bbox, paragraphs }
+        })
+        .collect();
+
+    Layout {
+        columns,
+        language: result.language.clone(),
+    }
+}
+
+fn average_height(lines: &[&OcrLine]) -> f32 {
+    let boxed: Vec<f32> = lines
+        .iter()
+        .filter(|l| !l.bbox.is_empty())
+        .map(|l| l.bbox.height)
+        .collect();
+    if boxed.is_empty() {
+        return 0.0;
+    }
+    boxed.iter().sum::<f32>() / boxed.len() as f32
+}
+
+/// Groups lines into columns by projecting them onto the x axis: lines whose
+/// horizontal extents overlap (or nearly touch) belong to the same column, and a
+/// gutter wider than [`COLUMN_GUTTER_RATIO`] line heights separates columns.
+///
+/// Engines without geometry (all boxes empty) yield a single column in engine order.
+fn split_columns(lines: Vec<&OcrLine>, line_height: f32) -> Vec<Vec<&OcrLine>> {
+    if lines.iter().all(|l| l.bbox.is_empty()) || line_height <= 0.0 {
+        return if lines.is_empty() {
+            Vec::new()
+        } else {
+            vec![lines]
+        };
+    }
+
+    let gutter = line_height * COLUMN_GUTTER_RATIO;
+
+    let bridging = bridging_lines(&lines, gutter);
+    if bridging.contains(&true) {
+        return split_bands(lines, &bridging, line_height);
+    }
+
+    let merged = column_spans(&lines, gutter);
+    let mut columns: Vec<Vec<&OcrLine>> = vec![Vec::new(); merged.len()];
+    for line in lines {
+        let center = line.bbox.x + line.bbox.width / 2.0;
+        let idx = merged
+            .iter()
+            .position(|(start, end, _)| center >= *start && center <= *end)
+            .unwrap_or(0);
+        columns[idx].push(line);
+    }
+    columns.retain(|c| !c.is_empty());
+    columns
+}
+
+/// Horizontal extents of the columns formed by `lines`, and how many lines each has.
+fn column_spans(lines: &[&OcrLine], gutter: f32) -> Vec<(f32, f32, usize)> {
+    let mut spans: Vec<(f32, f32)> = lines
+        .iter()
+        .filter(|l| !l.bbox.is_empty())
+        .map(|l| (l.bbox.x, l.bbox.right()))
+        .collect();
+    spans.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+    let mut merged: Vec<(f32, f32, usize)> = Vec::new();
+    for (start, end) in spans {
+        match merged.last_mut() {
+            Some(last) if start <= last.1 + gutter => {
+                last.1 = last.1.max(end);
+                last.2 += 1;
+            }
+            _ => merged.push((start, end, 1)),
+        }
+    }
+    merged
+}
+
+/// Lines that cross the gutter between columns of narrower lines, like a heading
+/// over both halves of a page, which would otherwise glue the columns into one.
+/// Only two or more lines make a column, so a lone short line off to the side (a
+/// page number) doesn't turn every wider line into a heading.
+fn bridging_lines(lines: &[&OcrLine], gutter: f32) -> Vec<bool> {
+    let mut by_width: Vec<usize> = (0..lines.len())
+        .filter(|&i| !lines[i].bbox.is_empty())
+        .collect();
+    by_width.sort_by(|&a, &b| lines[a].bbox.width.total_cmp(&lines[b].bbox.width));
+
+    let mut bridging = vec![false; lines.len()];
+    let mut narrower: Vec<&OcrLine> = Vec::new();
+    for i in by_width {
+        let bbox = &lines[i].bbox;
+        let crossed = column_spans(&narrower, gutter)
+            .iter()
+            .filter(|(start, end, count)| *count > 1 && bbox.x < *end && bbox.right() > *start)
+            .count();
+        if crossed > 1 {
+            bridging[i] = true;
+        } else {
+            narrower.push(lines[i]);
+        }
+    }
+    bridging
+}
+
+/// Cuts the page at every run of `bridging` lines, top to bottom: the lines above
+/// are split into columns on their own, then the run is one column, and so on.
+fn split_bands<'a>(
+    lines: Vec<&'a OcrLine>,
+    bridging: &[bool],
+    line_height: f32,
+) -> Vec<Vec<&'a OcrLine>> {
+    let mut lines: Vec<(&OcrLine, bool)> =
+        lines.into_iter().zip(bridging.iter().copied()).collect();
+    lines.sort_by(|a, b| a.0.bbox.y.total_cmp(&b.0.bbox.y));
+
+    let mut columns = Vec::new();
+    let mut band: Vec<&OcrLine> = Vec::new();
+    let mut run: Vec<&OcrLine> = Vec::new();
+    for (line, bridges) in lines {
+        if bridges {
+            columns.extend(split_columns(std::mem::take(&mut band), line_height));
+            run.push(line);
+        } else {
+            if !run.is_empty() {
+                columns.push(std::mem::take(&mut run));
+            }
+            band.push(line);
+        }
+    }
+    columns.extend(split_columns(band, line_height));
+    if !run.is_empty() {
+        columns.push(run);
+    }
+    columns
+}
+
+/// Top-to-bottom, then left-to-right for lines sharing a row. A skewed line read as
+/// several fragments stays one row as long as each fragment overlaps another one.
+fn sort_reading_order(lines: &mut Vec<&OcrLine>) {
+    if lines.iter().any(|l| l.bbox.is_empty()) {
+        return;
+    }
+    lines.sort_by(|a, b| a.bbox.y.total_cmp(&b.bbox.y));
+
+    let mut rows: Vec<Vec<&OcrLine>> = Vec::new();
+    for line in lines.drain(..) {
+        match rows.last_mut() {
+            Some(row) if row.iter().any(|l| same_row(&l.bbox, &line.bbox)) => row.push(line),
+            _ => rows.push(vec![line]),
+        }
+    }
+    for mut row in rows {
+        row.sort_by(|a, b| a.bbox.x.total_cmp(&b.bbox.x));
+        lines.extend(row);
+    }
+}
+
+fn same_row(a: &BoundingBox, b: &BoundingBox) -> bool {
+    let overlap = a.bottom().min(b.bottom()) - a.y.max(b.y);
+    overlap > a.height.min(b.height) * SAME_ROW_OVERLAP
+}
+
+fn split_paragraphs(lines: &[&OcrLine], line_height: f32) -> Vec<Paragraph> {
+    let mut paragraphs: Vec<Paragraph> = Vec::new();
+    // Lowest box of the current row, and the line before
+    let mut prev: Option<&BoundingBox> = None;
+    let mut last: Option<&BoundingBox> = None;
+
+    for line in lines {
+        let starts_paragraph = match prev {
+            None => true,
+            Some(prev_box) if line_height > 0.0 && !line.bbox.is_empty() => {
+                let gap = line.bbox.y - prev_box.bottom();
+                let avg = (prev_box.height + line.bbox.height) / 2.0;
+                gap > avg * PARAGRAPH_GAP_RATIO
+            }
+            Some(_) => false,
+        };
+
+        if starts_paragraph {
+            paragraphs.push(Paragraph::default());
+        }
+        if let Some(p) = paragraphs.last_mut() {
+            p.bbox = p.bbox.union(&line.bbox);
+            p.lines.push((*line).clone());
+        }
+
+        // Keep the lowest box of the row so a side-by-side fragment doesn't shrink the gap
+        prev = match (prev, last) {
+            (Some(p), Some(l)) if same_row(l, &line.bbox) && p.bottom() > line.bbox.bottom() => {
+                Some(p)
+            }
+            _ => Some(&line.bbox),
+        };
+        last = Some(&line.bbox);
+    }
+
+    for p in &mut paragraphs {
+        p.text = join_lines(&p.lines);
+    }
+    paragraphs
+}
+
+/// Joins lines with `\n`, except fragments on the same row which are joined with a space.
+fn join_lines(lines: &[OcrLine]) -> String {
+    let mut text = String::new();
+    for (i, line) in lines.iter().enumerate() {
+        if i > 0 {
+            let prev = &lines[i - 1];
+            let same = !line.bbox.is_empty() && same_row(&prev.bbox, &line.bbox);
+            text.push(if same { ' ' } else { '\n' });
+        }
+        text.push_str(line.text.trim());
+    }
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn line(text: &str, x: f32, y: f32, width: f32) -> OcrLine {
+        OcrLine {
+            text: text.to_string(),
+            bbox: BoundingBox::new(x, y, width, 16.0),
+            confidence: 1.0,
+            words: Vec::new(),
+        }
+    }
+
+    fn layout(lines: Vec<OcrLine>) -> Layout {
+        analyze(&OcrResult {
+            lines,
+            language: None,
+        })
+    }
+
+    fn column_texts(layout: &Layout) -> Vec<String> {
+        layout
+            .columns
+            .iter()
+            .map(|c| {
+                c.paragraphs
+                    .iter()
+                    .map(|p| p.text.as_str())
+                    .collect::<Vec<_>>()
+                    .join("\n\n")
+            })
+            .collect()
+    }
+
+    /// A left column at x 0..200 and a right one at 300..500, starting at `top`,
+    /// in an engine order that zigzags between them.
+    fn two_columns(top: f32) -> Vec<OcrLine> {
+        let mut lines = Vec::new();
+        for (i, y) in [0.0, 20.0, 40.0, 80.0].into_iter().enumerate() {
+            lines.push(line(&format!("R{}", i + 1), 300.0, top + y, 180.0));
+            lines.push(line(&format!("L{}", i + 1), 0.0, top + y, 200.0));
+        }
+        lines
+    }
+
+    #[test]
+    fn two_columns_read_one_after_the_other() {
+        let layout = layout(two_columns(0.0));
+        assert_eq!(
+            column_texts(&layout),
+            ["L1\nL2\nL3\n\nL4", "R1\nR2\nR3\n\nR4"]
+        );
+        assert_eq!(layout.columns[0].paragraphs.len(), 2);
+        assert_eq!(layout.columns[1].bbox.x, 300.0);
+        assert_eq!(layout.text(), "L1\nL2\nL3\n\nL4\n\nR1\nR2\nR3\n\nR4");
+    }
+
+    #[test]
+    fn heading_over_both_columns_comes_first() {
+        let mut lines = two_columns(60.0);
+        lines.push(line("Subtitle across the page", 40.0, 20.0, 420.0));
+        lines.push(line("Title across the page", 0.0, 0.0, 500.0));
+        lines.push(line("Footer across the page", 0.0, 200.0, 500.0));
+        let layout = layout(lines);
+        assert_eq!(
+            column_texts(&layout),
+            [
+                "Title across the page\nSubtitle across the page",
+                "L1\nL2\nL3\n\nL4",
+                "R1\nR2\nR3\n\nR4",
+                "Footer across the page",
+            ]
+        );
+    }
+
+    #[test]
+    fn long_line_in_a_single_column_is_not_a_heading() {
+        let layout = layout(vec![
+            line("Short", 0.0, 0.0, 60.0),
+            line("A much longer line of text", 0.0, 20.0, 400.0),
+            line("Short again", 0.0, 40.0, 120.0),
+        ]);
+        assert_eq!(layout.columns.len(), 1);
+        assert_eq!(
+            layout.text(),
+            "Short\nA much longer line of text\nShort again"
+        );
+    }
+
+    #[test]
+    fn skewed_line_keeps_its_reading_order() {
+        // One line read as fragments stepping up or down to the right, then the next line
+        for step in [-5.0, 5.0] {
+            let fragment = |text: &str, i: f32| line(text, i * 100.0, 40.0 + i * step, 90.0);
+            let layout = layout(vec![
+                fragment("gamma", 2.0),
+                // Just below the lowest fragment
+                line("next line", 0.0, 60.0 + (2.0 * step).max(0.0), 290.0),
+                fragment("alpha", 0.0),
+                fragment("beta", 1.0),
+            ]);
+            assert_eq!(
+                layout.text(),
+                "alpha beta gamma\nnext line",
+                "step {}",
+                step
+            );
+        }
+    }
+
+    #[test]
+    fn lines_without_boxes_keep_engine_order() {
+        let mut lines = vec![line("first", 0.0, 0.0, 0.0), line("second", 0.0, 0.0, 0.0)];
+        for l in &mut lines {
+            l.bbox = BoundingBox::default();
+        }
+        assert_eq!(layout(lines).text(), "first\nsecond");
+    }
+}
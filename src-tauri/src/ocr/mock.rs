@@ -0,0 +1,90 @@
+use super::{BoundingBox, OcrEngine, OcrLine, OcrResult, OcrWord};
+
+/// Engine returning a canned result. Lets the capture → OCR → clipboard flow and
+/// its error paths run on machines (and CI runners) without a native OCR engine.
+pub struct MockEngine {
+    result: Result<OcrResult, String>,
+}
+
+impl MockEngine {
+    /// Lays `lines` out top-to-bottom at a fixed 20px line height, words split on spaces.
+    pub fn with_lines(lines: &[&str]) -> Self {
+        let lines = lines
+            .iter()
+            .enumerate()
+            .map(|(i, text)| {
+                let y = i as f32 * 20.0;
+                let mut x = 0.0;
+                let words: Vec<OcrWord> = text
+                    .split_whitespace()
+                    .map(|w| {
+                        let width = w.chars().count() as f32 * 8.0;
+                        let word = OcrWord {
+                            text: w.to_string(),
+                            bbox: BoundingBox::new(x, y, width, 16.0),
+                            confidence: 1.0,
+                        };
+                        x += width + 8.0;
+                        word
+                    })
+                    .collect();
+                OcrLine {
+                    text: text.to_string(),
+                    bbox: BoundingBox::new(0.0, y, (x - 8.0).max(0.0), 16.0),
+                    confidence: 1.0,
+                    words,
+                }
+            })
+            .collect();
+
+        Self {
+            result: Ok(OcrResult {
+                lines,
+                language: Some("en-US".to_string()),
+            }),
+        }
+    }
+
+    pub fn failing(message: &str) -> Self {
+        Self {
+            result: Err(message.to_string()),
+        }
+    }
+
+    /// `TASKGOBLIN_OCR_MOCK_ERROR` makes every recognition fail with that message,
+    /// otherwise the lines of `TASKGOBLIN_OCR_MOCK_TEXT` are returned.
+    pub fn from_env() -> Self {
+        if let Ok(err) = std::env::var("TASKGOBLIN_OCR_MOCK_ERROR") {
+            return Self::failing(&err);
+        }
+        let text = std::env::var("TASKGOBLIN_OCR_MOCK_TEXT").unwrap_or_default();
+        Self::with_lines(&text.lines().collect::<Vec<_>>())
+    }
+}
+
+impl OcrEngine for MockEngine {
+    fn name(&self) -> &'static str {
+        "mock"
+    }
+
+    fn supported_languages(&self) -> Result<Vec<String>, String> {
+        Ok(vec![
+            "en-US".to_string(),
+            "es-ES".to_string(),
+            "de-DE".to_string(),
+            "pt-BR".to_string(),
+        ])
+    }
+
+    fn recognize(
+        &self,
+        _image: &image::RgbaImage,
+        languages: &[String],
+    ) -> Result<OcrResult, String> {
+        let mut result = self.result.clone()?;
+        if let Some(lang) = languages.first() {
+            result.language = Some(lang.clone());
+        }
+        Ok(result)
+    }
+}
@@ -0,0 +1,462 @@
+//! Screen OCR engines.
+//!
+//! Every backend takes an already-captured image and returns the same structured
+//! [`OcrResult`], so capture, post-processing and the island/clipboard flow live in
+//! one place instead of being duplicated per OS.
+
+use serde::{Deserialize, Serialize};
+
+pub mod history;
+pub mod layout;
+mod mock;
+pub mod table;
+#[cfg(target_os = "linux")]
+mod tesseract;
+#[cfg(target_os = "macos")]
+mod vision;
+#[cfg(target_os = "windows")]
+mod windows;
+
+pub use mock::MockEngine;
+#[cfg(target_os = "linux")]
+pub use tesseract::TesseractEngine;
+#[cfg(target_os = "macos")]
+pub use vision::VisionEngine;
+#[cfg(target_os = "windows")]
+pub use windows::WindowsOcrEngine;
+
+/// Prefix used by the Swift and PowerShell backends to hand back their JSON payload.
+/// The payload is Base64-encoded to bypass all console encoding issues.
+#[cfg(any(target_os = "macos", target_os = "windows", test))]
+const JSON_PREFIX: &str = "OCR_JSON:";
+
+/// Axis-aligned box in pixels of the recognized image, origin at the top-left.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq)]
+pub struct BoundingBox {
+    pub x: f32,
+    pub y: f32,
+    pub width: f32,
+    pub height: f32,
+}
+
+impl BoundingBox {
+    pub fn new(x: f32, y: f32, width: f32, height: f32) -> Self {
+        Self {
+            x,
+            y,
+            width,
+            height,
+        }
+    }
+
+    pub fn right(&self) -> f32 {
+        self.x + self.width
+    }
+
+    pub fn bottom(&self) -> f32 {
+        self.y + self.height
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.width <= 0.0 || self.height <= 0.0
+    }
+
+    /// Smallest box containing both `self` and `other`.
+    pub fn union(&self, other: &BoundingBox) -> BoundingBox {
+        if self.is_empty() {
+            return *other;
+        }
+        if other.is_empty() {
+            return *self;
+        }
+        let x = self.x.min(other.x);
+        let y = self.y.min(other.y);
+        BoundingBox::new(
+            x,
+            y,
+            self.right().max(other.right()) - x,
+            self.bottom().max(other.bottom()) - y,
+        )
+    }
+
+    #[cfg(any(target_os = "windows", target_os = "linux"))]
+    fn scaled(&self, factor: f32) -> BoundingBox {
+        BoundingBox::new(
+            self.x * factor,
+            self.y * factor,
+            self.width * factor,
+            self.height * factor,
+        )
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct OcrWord {
+    pub text: String,
+    #[serde(default)]
+    pub bbox: BoundingBox,
+    /// 0.0..=1.0; engines that don't report one use 1.0
+    #[serde(default = "full_confidence")]
+    pub confidence: f32,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct OcrLine {
+    pub text: String,
+    #[serde(default)]
+    pub bbox: BoundingBox,
+    #[serde(default = "full_confidence")]
+    pub confidence: f32,
+    #[serde(default)]
+    pub words: Vec<OcrWord>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct OcrResult {
+    pub lines: Vec<OcrLine>,
+    /// Language the engine actually recognized with (BCP-47 or Tesseract code)
+    #[serde(default)]
+    pub language: Option<String>,
+}
+
+fn full_confidence() -> f32 {
+    1.0
+}
+
+impl OcrResult {
+    /// Fills in line boxes that the engine didn't report (Windows.Media.Ocr only
+    /// gives word rectangles) and drops empty lines.
+    fn normalized(mut self) -> Self {
+        self.lines.retain(|l| !l.text.trim().is_empty());
+        for line in &mut self.lines {
+            if line.bbox.is_empty() {
+                line.bbox = line
+                    .words
+                    .iter()
+                    .fold(BoundingBox::default(), |acc, w| acc.union(&w.bbox));
+            }
+        }
+        if self.language.as_deref() == Some("") {
+            self.language = None;
+        }
+        self
+    }
+
+    /// Maps coordinates from an upscaled copy back onto the original image.
+    #[cfg(any(target_os = "windows", target_os = "linux"))]
+    fn scaled(mut self, factor: f32) -> Self {
+        for line in &mut self.lines {
+            line.bbox = line.bbox.scaled(factor);
+            for word in &mut line.words {
+                word.bbox = word.bbox.scaled(factor);
+            }
+        }
+        self
+    }
+}
+
+pub trait OcrEngine: Send + Sync {
+    /// Short identifier used in logs and error messages
+    fn name(&self) -> &'static str;
+
+    /// Language tags this engine can recognize on this machine.
+    fn supported_languages(&self) -> Result<Vec<String>, String>;
+
+    /// Recognizes `image`, preferring `languages` in order (BCP-47 tags).
+    /// Unsupported entries are skipped; an empty list uses the engine's defaults.
+    fn recognize(
+        &self,
+        image: &image::RgbaImage,
+        languages: &[String],
+    ) -> Result<OcrResult, String>;
+}
+
+/// Languages used until the user picks their own with `set_ocr_languages`.
+pub fn default_languages() -> Vec<String> {
+    vec!["es-ES".to_string(), "en-US".to_string()]
+}
+
+/// Primary language subtag, e.g. "pt" for "pt-BR".
+fn primary_subtag(tag: &str) -> String {
+    tag.split(['-', '_'])
+        .next()
+        .unwrap_or_default()
+        .to_lowercase()
+}
+
+/// Resolves each requested tag to an entry of `supported`, preferring an exact
+/// (case-insensitive) match and falling back to the same primary language
+/// ("de" → "de-DE"). Fails listing every tag the engine can't handle.
+pub fn validate_languages(
+    requested: &[String],
+    supported: &[String],
+) -> Result<Vec<String>, String> {
+    if requested.is_empty() {
+        return Err("Select at least one OCR language".to_string());
+    }
+
+    let mut resolved: Vec<String> = Vec::new();
+    let mut unsupported: Vec<&str> = Vec::new();
+    for tag in requested {
+        let tag = tag.trim();
+        let found = supported
+            .iter()
+            .find(|s| s.eq_ignore_ascii_case(tag))
+            .or_else(|| {
+                supported
+                    .iter()
+                    .find(|s| primary_subtag(s) == primary_subtag(tag))
+            });
+        match found {
+            Some(s) if !resolved.contains(s) => resolved.push(s.clone()),
+            Some(_) => {}
+            None => unsupported.push(tag),
+        }
+    }
+
+    if !unsupported.is_empty() {
+        return Err(format!(
+            "Unsupported OCR language(s): {}. Available: {}",
+            unsupported.join(", "),
+            supported.join(", ")
+        ));
+    }
+    Ok(resolved)
+}
+
+/// Engine for the current OS. `TASKGOBLIN_OCR_ENGINE=mock` swaps in [`MockEngine`]
+/// so the capture flow can be exercised without a native OCR install.
+pub fn default_engine() -> Box<dyn OcrEngine> {
+    if std::env::var("TASKGOBLIN_OCR_ENGINE").as_deref() == Ok("mock") {
+        return Box::new(MockEngine::from_env());
+    }
+
+    #[cfg(target_os = "macos")]
+    {
+        Box::new(VisionEngine)
+    }
+    #[cfg(target_os = "windows")]
+    {
+        Box::new(WindowsOcrEngine)
+    }
+    #[cfg(target_os = "linux")]
+    {
+        Box::new(TesseractEngine)
+    }
+    #[cfg(not(any(target_os = "macos", target_os = "windows", target_os = "linux")))]
+    {
+        Box::new(MockEngine::failing(
+            "OCR is only supported on macOS, Windows and Linux",
+        ))
+    }
+}
+
+/// How recognized text is turned into what lands on the clipboard.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum OutputMode {
+    /// Paragraphs in reading order
+    #[default]
+    Text,
+    /// Table cells separated by tabs, pastes straight into spreadsheets
+    Tsv,
+    Csv,
+    Markdown,
+}
+
+/// What the OCR commands hand back to the frontend: the text that goes to the
+/// clipboard plus the layout (and table, in table modes) it was rebuilt from.
+#[derive(Serialize, Debug, Clone, Default)]
+pub struct OcrOutput {
+    pub text: String,
+    pub mode: OutputMode,
+    pub layout: layout::Layout,
+    pub table: Option<table::Table>,
+}
+
+impl OcrOutput {
+    pub fn new(result: OcrResult, mode: OutputMode) -> Self {
+        let layout = layout::analyze(&result);
+        let table = match mode {
+            OutputMode::Text => None,
+            _ => Some(table::extract(&result)),
+        };
+        let text = match (&table, mode) {
+            (Some(t), OutputMode::Tsv) => t.to_tsv(),
+            (Some(t), OutputMode::Csv) => t.to_csv(),
+            (Some(t), OutputMode::Markdown) => t.to_markdown(),
+            _ => layout.text(),
+        };
+        Self {
+            text,
+            mode,
+            layout,
+            table,
+        }
+    }
+}
+
+/// Status shown by the OCR island for a finished recognition.
+pub fn island_status(res: &Result<OcrOutput, String>) -> &'static str {
+    match res {
+        Ok(output) if output.text.trim().is_empty() => "no_text",
+        Ok(_) => "success",
+        Err(_) => "error",
+    }
+}
+
+/// Upscales small captures; OCR engines recognize 2x images noticeably better.
+/// Returns the image to feed the engine and the factor to map boxes back.
+#[cfg(any(target_os = "windows", target_os = "linux"))]
+fn upscale_for_ocr(img: &image::RgbaImage) -> (image::RgbaImage, f32) {
+    let scaled = image::imageops::resize(
+        img,
+        img.width() * 2,
+        img.height() * 2,
+        image::imageops::FilterType::CatmullRom,
+    );
+    (scaled, 0.5)
+}
+
+/// Parses the `OCR_JSON:<base64>` line printed by the script backends.
+#[cfg(any(target_os = "macos", target_os = "windows", test))]
+fn parse_json_output(stdout: &str) -> Result<OcrResult, String> {
+    use base64::{engine::general_purpose, Engine as _};
+
+    let line = stdout
+        .lines()
+        .map(str::trim)
+        .find(|l| l.starts_with(JSON_PREFIX));
+
+    let Some(line) = line else {
+        if stdout.trim().is_empty() {
+            return Ok(OcrResult::default());
+        }
+        return Err(format!("Unexpected OCR output: {}", stdout.trim()));
+    };
+
+    let bytes = general_purpose::STANDARD
+        .decode(line.trim_start_matches(JSON_PREFIX))
+        .map_err(|e| format!("Invalid OCR payload: {}", e))?;
+    let result: OcrResult =
+        serde_json::from_slice(&bytes).map_err(|e| format!("Invalid OCR payload: {}", e))?;
+    Ok(result.normalized())
+}
+
+/// Writes `img` as a PNG in the temp dir for the external OCR process to read.
+fn write_temp_png(img: &image::RgbaImage, name: &str) -> Result<std::path::PathBuf, String> {
+    let path = std::env::temp_dir().join(name);
+    img.save(&path)
+        .map_err(|e| format!("Failed to write OCR image: {}", e))?;
+    Ok(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use base64::{engine::general_purpose, Engine as _};
+
+    fn tags(tags: &[&str]) -> Vec<String> {
+        tags.iter().map(|t| t.to_string()).collect()
+    }
+
+    fn recognize(engine: &MockEngine, languages: &[&str]) -> Result<OcrOutput, String> {
+        let image = image::RgbaImage::new(8, 8);
+        let result = engine.recognize(&image, &tags(languages))?;
+        Ok(OcrOutput::new(result, OutputMode::Text))
+    }
+
+    #[test]
+    fn mock_engine_feeds_text_output() {
+        let engine = MockEngine::with_lines(&["Hello world", "Second line"]);
+        let image = image::RgbaImage::new(8, 8);
+        let result = engine.recognize(&image, &tags(&["es-ES"])).unwrap();
+        assert_eq!(result.language.as_deref(), Some("es-ES"));
+        assert_eq!(result.lines[0].words.len(), 2);
+
+        let output = OcrOutput::new(result, OutputMode::Text);
+        assert_eq!(output.text, "Hello world\nSecond line");
+        assert!(output.table.is_none());
+        assert_eq!(island_status(&Ok(output)), "success");
+    }
+
+    #[test]
+    fn mock_engine_feeds_table_output() {
+        let engine = MockEngine::with_lines(&["a b", "c d"]);
+        let result = engine.recognize(&image::RgbaImage::new(8, 8), &[]).unwrap();
+        assert_eq!(result.language.as_deref(), Some("en-US"));
+
+        let output = OcrOutput::new(result, OutputMode::Tsv);
+        let table = output.table.as_ref().unwrap();
+        assert_eq!(output.text, table.to_tsv());
+        assert!(output.text.contains('a') && output.text.contains('d'));
+    }
+
+    #[test]
+    fn mock_engine_without_lines_has_no_text() {
+        let output = recognize(&MockEngine::with_lines(&[]), &["en-US"]);
+        assert_eq!(island_status(&output), "no_text");
+    }
+
+    #[test]
+    fn mock_engine_error_reaches_the_island() {
+        let output = recognize(&MockEngine::failing("engine exploded"), &["en-US"]);
+        assert_eq!(output.as_ref().unwrap_err(), "engine exploded");
+        assert_eq!(island_status(&output), "error");
+    }
+
+    #[test]
+    fn validate_languages_resolves_against_the_engine() {
+        let supported = MockEngine::with_lines(&[]).supported_languages().unwrap();
+        assert_eq!(
+            validate_languages(&tags(&["EN-us", "de", "pt_BR", "en-US"]), &supported).unwrap(),
+            tags(&["en-US", "de-DE", "pt-BR"])
+        );
+
+        let err = validate_languages(&tags(&["en-US", "ja-JP", "xx"]), &supported).unwrap_err();
+        assert!(err.starts_with("Unsupported OCR language(s): ja-JP, xx."));
+        assert!(err.ends_with("Available: en-US, es-ES, de-DE, pt-BR"));
+
+        assert_eq!(
+            validate_languages(&[], &supported).unwrap_err(),
+            "Select at least one OCR language"
+        );
+    }
+
+    #[test]
+    fn parse_json_output_normalizes_the_payload() {
+        let json = r#"{"language": "", "lines": [
+            {"text": "Hello world", "words": [
+                {"text": "Hello", "bbox": {"x": 10, "y": 5, "width": 40, "height": 12}},
+                {"text": "world", "bbox": {"x": 56, "y": 6, "width": 44, "height": 12}, "confidence": 0.5}
+            ]},
+            {"text": "  "}
+        ]}"#;
+        let stdout = format!(
+            "warming up\n  {}{}  \n",
+            JSON_PREFIX,
+            general_purpose::STANDARD.encode(json)
+        );
+        let result = parse_json_output(&stdout).unwrap();
+        assert_eq!(result.lines.len(), 1);
+        assert_eq!(result.language, None);
+        let line = &result.lines[0];
+        assert_eq!(line.bbox, BoundingBox::new(10.0, 5.0, 90.0, 13.0));
+        assert_eq!(line.confidence, 1.0);
+        assert_eq!(line.words[1].confidence, 0.5);
+    }
+
+    #[test]
+    fn parse_json_output_rejects_bad_output() {
+        assert!(parse_json_output(" \n").unwrap().lines.is_empty());
+        assert_eq!(
+            parse_json_output("Swift crashed\n").unwrap_err(),
+            "Unexpected OCR output: Swift crashed"
+        );
+        let err = parse_json_output(&format!("{}not base64!", JSON_PREFIX)).unwrap_err();
+        assert!(err.starts_with("Invalid OCR payload"));
+        let payload = general_purpose::STANDARD.encode("{\"lines\": 3}");
+        let err = parse_json_output(&format!("{}{}", JSON_PREFIX, payload)).unwrap_err();
+        assert!(err.starts_with("Invalid OCR payload"));
+    }
+}
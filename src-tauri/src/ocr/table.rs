@@ -0,0 +1,361 @@
+//! Table extraction: clusters OCR word boxes into rows and columns so a
+//! screenshot of a spreadsheet or report pastes back as cells.
+
+use super::{BoundingBox, OcrResult, OcrWord};
+use serde::Serialize;
+
+/// Words closer than this many row heights are part of the same cell.
+const CELL_GAP_RATIO: f32 = 0.9;
+/// A word belongs to a row when its vertical center falls within the row band,
+/// widened by this fraction of the row height.
+const ROW_TOLERANCE: f32 = 0.25;
+
+#[derive(Serialize, Debug, Clone, Default)]
+pub struct Table {
+    /// Cells row by row; every row has the same number of columns
+    pub rows: Vec<Vec<String>>,
+}
+
+struct Row<'a> {
+    bbox: BoundingBox,
+    words: Vec<&'a OcrWord>,
+}
+
+struct Cell {
+    bbox: BoundingBox,
+    text: String,
+}
+
+impl Table {
+    pub fn to_tsv(&self) -> String {
+        self.rows
+            .iter()
+            .map(|row| {
+                row.iter()
+                    .map(|c| c.replace(['\t', '\n', '\r'], " "))
+                    .collect::<Vec<_>>()
+                    .join("\t")
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    pub fn to_csv(&self) -> String {
+        self.rows
+            .iter()
+            .map(|row| {
+                row.iter()
+                    .map(|c| csv_field(c))
+                    .collect::<Vec<_>>()
+                    .join(",")
+            })
+            .collect::<Vec<_>>()
+            .join("\r\n")
+    }
+
+    /// GitHub-flavored Markdown table; the first row becomes the header.
+    pub fn to_markdown(&self) -> String {
+        let Some((header, body)) = self.rows.split_first() else {
+            return String::new();
+        };
+        let line = |row: &Vec<String>| {
+            let cells: Vec<String> = row
+                .iter()
+                .map(|c| c.replace('|', "\\|").replace(['\n', '\r'], " "))
+                .collect();
+            format!("| {} |", cells.join(" | "))
+        };
+
+        let mut out = vec![line(header)];
+        out.push(format!("|{}", " --- |".repeat(header.len())));
+        out.extend(body.iter().map(line));
+        out.join("\n")
+    }
+
+    /// HTML `<table>` for rich-text clipboards; spreadsheets and word processors
+    /// paste it as real cells.
+    pub fn to_html(&self) -> String {
+        let mut html = String::from("<table>");
+        for row in &self.rows {
+            html.push_str("<tr>");
+            for cell in row {
+                html.push_str("<td>");
+                html.push_str(&html_escape(cell));
+                html.push_str("</td>");
+            }
+            html.push_str("</tr>");
+        }
+        html.push_str("</table>");
+        html
+    }
+}
+
+fn html_escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+fn csv_field(cell: &str) -> String {
+    if cell.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", cell.replace('"', "\"\""))
+    } else {
+        cell.to_string()
+    }
+}
+
+pub fn extract(result: &OcrResult) -> Table {
+    // Lines without word geometry count as a single word
+    let line_words: Vec<OcrWord> = result
+        .lines
+        .iter()
+        .filter(|l| l.words.is_empty())
+        .map(|l| OcrWord {
+            text: l.text.clone(),
+            bbox: l.bbox,
+            confidence: l.confidence,
+        })
+        .collect();
+    let words: Vec<&OcrWord> = result
+        .lines
+        .iter()
+        .flat_map(|l| l.words.iter())
+        .chain(line_words.iter())
+        .filter(|w| !w.text.trim().is_empty() && !w.bbox.is_empty())
+        .collect();
+
+    if words.is_empty() {
+        return Table::default();
+    }
+
+    let rows = group_rows(words);
+    let row_cells: Vec<Vec<Cell>> = rows.iter().map(split_cells).collect();
+    let columns = column_bands(&row_cells);
+
+    let rows = row_cells
+        .into_iter()
+        .map(|cells| {
+            let mut row = vec![String::new(); columns.len()];
+            for cell in cells {
+                let center = cell.bbox.x + cell.bbox.width / 2.0;
+                // Merged cells go in the first column they cover
+                let idx = columns
+                    .iter()
+                    .position(|(start, end)| center >= *start && center <= *end)
+                    .or_else(|| columns.iter().position(|band| overlaps(&cell.bbox, band)))
+                    .unwrap_or(0);
+                if !row[idx].is_empty() {
+                    row[idx].push(' ');
+                }
+                row[idx].push_str(&cell.text);
+            }
+            row
+        })
+        .collect();
+
+    Table { rows }
+}
+
+/// Sweeps words top to bottom, starting a new row when a word's vertical center
+/// leaves the current row band.
+fn group_rows(mut words: Vec<&OcrWord>) -> Vec<Row<'_>> {
+    words.sort_by(|a, b| center_y(&a.bbox).total_cmp(&center_y(&b.bbox)));
+
+    let mut rows: Vec<Row> = Vec::new();
+    for word in words {
+        let cy = center_y(&word.bbox);
+        match rows.last_mut() {
+            Some(row)
+                if cy >= row.bbox.y - row.bbox.height * ROW_TOLERANCE
+                    && cy <= row.bbox.bottom() + row.bbox.height * ROW_TOLERANCE =>
+            {
+                row.bbox = row.bbox.union(&word.bbox);
+                row.words.push(word);
+            }
+            _ => rows.push(Row {
+                bbox: word.bbox,
+                words: vec![word],
+            }),
+        }
+    }
+
+    for row in &mut rows {
+        row.words.sort_by(|a, b| a.bbox.x.total_cmp(&b.bbox.x));
+    }
+    rows
+}
+
+/// Merges neighbouring words of a row into cells, splitting on wide gaps.
+fn split_cells(row: &Row) -> Vec<Cell> {
+    let height = row
+        .words
+        .iter()
+        .map(|w| w.bbox.height)
+        .fold(0.0_f32, f32::max);
+    let max_gap = height * CELL_GAP_RATIO;
+
+    let mut cells: Vec<Cell> = Vec::new();
+    for word in &row.words {
+        match cells.last_mut() {
+            Some(cell) if word.bbox.x - cell.bbox.right() <= max_gap => {
+                cell.bbox = cell.bbox.union(&word.bbox);
+                cell.text.push(' ');
+                cell.text.push_str(word.text.trim());
+            }
+            _ => cells.push(Cell {
+                bbox: word.bbox,
+                text: word.text.trim().to_string(),
+            }),
+        }
+    }
+    cells
+}
+
+/// Column x-ranges: the union of overlapping cell extents across all rows. A cell
+/// covering two columns of narrower cells is a merged cell and doesn't join them.
+fn column_bands(rows: &[Vec<Cell>]) -> Vec<(f32, f32)> {
+    let mut cells: Vec<&Cell> = rows.iter().flatten().collect();
+    cells.sort_by(|a, b| a.bbox.width.total_cmp(&b.bbox.width));
+
+    let mut spans: Vec<(f32, f32)> = Vec::new();
+    for cell in cells {
+        let covered = merge_spans(spans.clone())
+            .iter()
+            .filter(|band| overlaps(&cell.bbox, band))
+            .count();
+        if covered <= 1 {
+            spans.push((cell.bbox.x, cell.bbox.right()));
+        }
+    }
+    merge_spans(spans)
+}
+
+fn merge_spans(mut spans: Vec<(f32, f32)>) -> Vec<(f32, f32)> {
+    spans.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+    let mut bands: Vec<(f32, f32)> = Vec::new();
+    for (start, end) in spans {
+        match bands.last_mut() {
+            Some(last) if start <= last.1 => last.1 = last.1.max(end),
+            _ => bands.push((start, end)),
+        }
+    }
+    bands
+}
+
+fn overlaps(b: &BoundingBox, (start, end): &(f32, f32)) -> bool {
+    b.x <= *end && b.right() >= *start
+}
+
+fn center_y(b: &BoundingBox) -> f32 {
+    b.y + b.height / 2.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ocr::OcrLine;
+
+    /// One OCR line per table row; `cells` are (x, text) and every word is 8px per
+    /// character with 8px between words, 16px high.
+    fn row(y: f32, cells: &[(f32, &str)]) -> OcrLine {
+        let mut words = Vec::new();
+        for &(x, text) in cells {
+            let mut x = x;
+            for word in text.split(' ') {
+                let width = word.chars().count() as f32 * 8.0;
+                words.push(OcrWord {
+                    text: word.to_string(),
+                    bbox: BoundingBox::new(x, y, width, 16.0),
+                    confidence: 1.0,
+                });
+                x += width + 8.0;
+            }
+        }
+        OcrLine {
+            text: cells.iter().map(|(_, t)| *t).collect::<Vec<_>>().join(" "),
+            words,
+            ..Default::default()
+        }
+    }
+
+    fn table(rows: Vec<OcrLine>) -> Table {
+        extract(&OcrResult {
+            lines: rows,
+            language: None,
+        })
+    }
+
+    #[test]
+    fn regular_grid() {
+        let table = table(vec![
+            row(0.0, &[(0.0, "City"), (150.0, "Visits"), (300.0, "Share")]),
+            row(24.0, &[(0.0, "New York"), (150.0, "1,200"), (300.0, "40%")]),
+            row(48.0, &[(0.0, "Lima"), (150.0, "900"), (300.0, "30%")]),
+        ]);
+        assert_eq!(
+            table.to_tsv(),
+            "City\tVisits\tShare\nNew York\t1,200\t40%\nLima\t900\t30%"
+        );
+        assert_eq!(
+            table.to_csv(),
+            "City,Visits,Share\r\nNew York,\"1,200\",40%\r\nLima,900,30%"
+        );
+        assert_eq!(
+            table.to_markdown(),
+            "| City | Visits | Share |\n| --- | --- | --- |\n| New York | 1,200 | 40% |\n| Lima | 900 | 30% |"
+        );
+    }
+
+    #[test]
+    fn merged_cell_goes_in_its_first_column() {
+        let table = table(vec![
+            row(0.0, &[(0.0, "Region"), (150.0, "First half of the year")]),
+            row(24.0, &[(150.0, "Jan"), (300.0, "Feb")]),
+            row(48.0, &[(0.0, "North"), (150.0, "10"), (300.0, "12")]),
+        ]);
+        assert_eq!(
+            table.rows,
+            [
+                ["Region", "First half of the year", ""],
+                ["", "Jan", "Feb"],
+                ["North", "10", "12"],
+            ]
+        );
+        assert_eq!(
+            table.to_tsv(),
+            "Region\tFirst half of the year\t\n\tJan\tFeb\nNorth\t10\t12"
+        );
+    }
+
+    #[test]
+    fn empty_cells_stay_in_place() {
+        let table = table(vec![
+            row(0.0, &[(0.0, "a"), (150.0, "b"), (300.0, "c")]),
+            row(24.0, &[(0.0, "d"), (300.0, "f")]),
+            row(48.0, &[(150.0, "h")]),
+        ]);
+        assert_eq!(table.to_csv(), "a,b,c\r\nd,,f\r\n,h,");
+        assert_eq!(table.to_tsv(), "a\tb\tc\nd\t\tf\n\th\t");
+    }
+
+    #[test]
+    fn escapes_cells() {
+        let table = Table {
+            rows: vec![vec![
+                "say \"hi\"".to_string(),
+                "a|b".to_string(),
+                "<b>".to_string(),
+            ]],
+        };
+        assert_eq!(table.to_csv(), "\"say \"\"hi\"\"\",a|b,<b>");
+        assert_eq!(
+            table.to_html(),
+            "<table><tr><td>say &quot;hi&quot;</td><td>a|b</td><td>&lt;b&gt;</td></tr></table>"
+        );
+        assert!(table
+            .to_markdown()
+            .starts_with("| say \"hi\" | a\\|b | <b> |"));
+    }
+}
@@ -0,0 +1,264 @@
+use super::{upscale_for_ocr, write_temp_png, BoundingBox, OcrEngine, OcrLine, OcrResult, OcrWord};
+use std::process::Command;
+
+/// Local Tesseract install driven through its CLI. Works fully offline; language
+/// packs come from the distro (`tesseract-ocr-spa`, ...).
+pub struct TesseractEngine;
+
+/// BCP-47 tag ↔ Tesseract traineddata name for the packs distros ship most often.
+const LANGUAGE_CODES: &[(&str, &str)] = &[
+    ("en-US", "eng"),
+    ("es-ES", "spa"),
+    ("de-DE", "deu"),
+    ("pt-BR", "por"),
+    ("fr-FR", "fra"),
+    ("it-IT", "ita"),
+    ("nl-NL", "nld"),
+    ("pl-PL", "pol"),
+    ("ru-RU", "rus"),
+    ("uk-UA", "ukr"),
+    ("ja-JP", "jpn"),
+    ("ko-KR", "kor"),
+    ("zh-Hans", "chi_sim"),
+    ("zh-Hant", "chi_tra"),
+];
+
+/// Tesseract pack for a tag: exact tag, same primary language, or a raw pack name.
+fn tesseract_code(tag: &str) -> String {
+    LANGUAGE_CODES
+        .iter()
+        .find(|(t, _)| t.eq_ignore_ascii_case(tag))
+        .or_else(|| {
+            LANGUAGE_CODES
+                .iter()
+                .find(|(t, _)| super::primary_subtag(t) == super::primary_subtag(tag))
+        })
+        .map(|(_, code)| code.to_string())
+        .unwrap_or_else(|| tag.to_string())
+}
+
+impl TesseractEngine {
+    fn installed_languages() -> Vec<String> {
+        Command::new("tesseract")
+            .arg("--list-langs")
+            .output()
+            .map(|out| {
+                // First line is the "List of available languages ..." header
+                String::from_utf8_lossy(&out.stdout)
+                    .lines()
+                    .skip(1)
+                    .map(|l| l.trim().to_string())
+                    .filter(|l| !l.is_empty())
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+}
+
+impl OcrEngine for TesseractEngine {
+    fn name(&self) -> &'static str {
+        "tesseract"
+    }
+
+    fn supported_languages(&self) -> Result<Vec<String>, String> {
+        let installed = Self::installed_languages();
+        if installed.is_empty() {
+            return Err("Tesseract is not installed or has no language packs".to_string());
+        }
+        // "osd" is orientation detection, not a language
+        Ok(installed
+            .iter()
+            .filter(|code| code.as_str() != "osd")
+            .map(|code| {
+                LANGUAGE_CODES
+                    .iter()
+                    .find(|(_, c)| c == code)
+                    .map(|(tag, _)| tag.to_string())
+                    .unwrap_or_else(|| code.clone())
+            })
+            .collect())
+    }
+
+    fn recognize(
+        &self,
+        image: &image::RgbaImage,
+        languages: &[String],
+    ) -> Result<OcrResult, String> {
+        let tesseract_check = Command::new("which").arg("tesseract").output();
+        if !matches!(tesseract_check, Ok(ref out) if out.status.success()) {
+            return Err("Tesseract is not installed. Install it with your package manager (e.g. 'sudo apt install tesseract-ocr tesseract-ocr-spa').".to_string());
+        }
+
+        // Only ask for installed packs; Tesseract aborts on a missing one
+        let installed = Self::installed_languages();
+        let mut codes: Vec<String> = Vec::new();
+        for code in languages.iter().map(|tag| tesseract_code(tag)) {
+            if installed.contains(&code) && !codes.contains(&code) {
+                codes.push(code);
+            }
+        }
+        let langs = if codes.is_empty() {
+            "eng".to_string()
+        } else {
+            codes.join("+")
+        };
+
+        let (scaled, factor) = upscale_for_ocr(image);
+        let temp_image_path = write_temp_png(&scaled, "mouse_crazy_ocr_capture.png")?;
+
+        let output = Command::new("tesseract")
+            .arg(&temp_image_path)
+            .arg("stdout")
+            .arg("-l")
+            .arg(&langs)
+            .arg("tsv")
+            .output()
+            .map_err(|e| format!("Failed to run tesseract: {}", e));
+
+        let _ = std::fs::remove_file(&temp_image_path);
+        let output = output?;
+
+        if !output.status.success() {
+            return Err(format!(
+                "tesseract failed: {}",
+                String::from_utf8_lossy(&output.stderr).trim()
+            ));
+        }
+
+        let mut result = parse_tsv(&String::from_utf8_lossy(&output.stdout)).scaled(factor);
+        result.language = Some(langs);
+        Ok(result)
+    }
+}
+
+/// Parses Tesseract's `tsv` output into lines of words.
+///
+/// Columns: level page_num block_num par_num line_num word_num left top width height conf text.
+/// Only level 5 (word) rows carry text; they are grouped by their page/block/par/line ids.
+fn parse_tsv(tsv: &str) -> OcrResult {
+    let mut lines: Vec<OcrLine> = Vec::new();
+    let mut current_key: Option<(u32, u32, u32, u32)> = None;
+
+    for row in tsv.lines().skip(1) {
+        let cols: Vec<&str> = row.splitn(12, '\t').collect();
+        if cols.len() < 12 || cols[0] != "5" {
+            continue;
+        }
+        let text = cols[11].trim();
+        if text.is_empty() {
+            continue;
+        }
+
+        let num = |i: usize| cols[i].trim().parse::<f32>().unwrap_or(0.0);
+        let key = (num(1) as u32, num(2) as u32, num(3) as u32, num(4) as u32);
+        let word = OcrWord {
+            text: text.to_string(),
+            bbox: BoundingBox::new(num(6), num(7), num(8), num(9)),
+            confidence: (num(10) / 100.0).clamp(0.0, 1.0),
+        };
+
+        if current_key != Some(key) {
+            current_key = Some(key);
+            lines.push(OcrLine::default());
+        }
+        if let Some(line) = lines.last_mut() {
+            line.words.push(word);
+        }
+    }
+
+    for line in &mut lines {
+        line.text = line
+            .words
+            .iter()
+            .map(|w| w.text.as_str())
+            .collect::<Vec<_>>()
+            .join(" ");
+        line.confidence =
+            line.words.iter().map(|w| w.confidence).sum::<f32>() / line.words.len() as f32;
+    }
+
+    OcrResult {
+        lines,
+        language: None,
+    }
+    .normalized()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Rendered at 28px: "Invoice 2024" over "Total due 1250 EUR"
+    const FIXTURE: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/ocr_text.png");
+
+    /// `tesseract ocr_text.png stdout tsv` shape: page/block/par/line rows with
+    /// conf -1, then one row per word; blank words show up for stray marks.
+    const TSV: &str = "level\tpage_num\tblock_num\tpar_num\tline_num\tword_num\tleft\ttop\twidth\theight\tconf\ttext
+1\t1\t0\t0\t0\t0\t0\t0\t552\t192\t-1\t
+2\t1\t1\t0\t0\t0\t40\t42\t468\t110\t-1\t
+3\t1\t1\t1\t0\t0\t40\t42\t468\t110\t-1\t
+4\t1\t1\t1\t1\t0\t40\t42\t260\t42\t-1\t
+5\t1\t1\t1\t1\t1\t40\t42\t130\t42\t96.5\tInvoice
+5\t1\t1\t1\t1\t2\t190\t44\t110\t40\t91.5\t2024
+4\t1\t1\t1\t2\t0\t40\t110\t468\t42\t-1\t
+5\t1\t1\t1\t2\t1\t40\t110\t90\t42\t95\tTotal
+5\t1\t1\t1\t2\t2\t146\t110\t60\t42\t93\tdue
+5\t1\t1\t1\t2\t3\t222\t112\t100\t40\t90\t1250
+5\t1\t1\t1\t2\t4\t338\t112\t80\t40\t94\tEUR
+5\t1\t1\t1\t2\t5\t430\t112\t4\t4\t12\t 
+2\t1\t2\t0\t0\t0\t500\t170\t6\t6\t-1\t
+5\t1\t2\t1\t1\t1\t500\t170\t6\t6\t0\t 
+";
+
+    #[test]
+    fn parse_tsv_groups_words_into_lines() {
+        let result = parse_tsv(TSV);
+        let texts: Vec<&str> = result.lines.iter().map(|l| l.text.as_str()).collect();
+        assert_eq!(texts, ["Invoice 2024", "Total due 1250 EUR"]);
+        assert_eq!(result.language, None);
+
+        let first = &result.lines[0];
+        assert_eq!(first.words.len(), 2);
+        assert_eq!(
+            first.words[1].bbox,
+            BoundingBox::new(190.0, 44.0, 110.0, 40.0)
+        );
+        assert!((first.confidence - 0.94).abs() < 1e-6);
+        // Filled in from the words by `normalized`
+        assert_eq!(first.bbox, BoundingBox::new(40.0, 42.0, 260.0, 42.0));
+
+        let second = &result.lines[1];
+        assert_eq!(second.words.len(), 4);
+        assert_eq!(second.bbox, BoundingBox::new(40.0, 110.0, 378.0, 42.0));
+    }
+
+    #[test]
+    fn parse_tsv_skips_malformed_rows() {
+        assert!(parse_tsv("").lines.is_empty());
+        let tsv = "level\ttext\n5\t1\t1\t1\t1\n5\t1\t1\t1\t1\t1\t0\t0\t10\t10\t250\tword\n";
+        let result = parse_tsv(tsv);
+        assert_eq!(result.lines.len(), 1);
+        assert_eq!(result.lines[0].confidence, 1.0);
+    }
+
+    #[test]
+    fn tesseract_code_maps_tags_to_packs() {
+        assert_eq!(tesseract_code("en-US"), "eng");
+        assert_eq!(tesseract_code("pt-PT"), "por");
+        assert_eq!(tesseract_code("chi_sim"), "chi_sim");
+    }
+
+    #[test]
+    #[ignore = "needs the tesseract binary with the eng pack"]
+    fn recognizes_fixture_with_tesseract() {
+        let img = image::open(FIXTURE).unwrap().to_rgba8();
+        let result = TesseractEngine
+            .recognize(&img, &["en-US".to_string()])
+            .unwrap();
+        let texts: Vec<&str> = result.lines.iter().map(|l| l.text.as_str()).collect();
+        assert_eq!(texts, ["Invoice 2024", "Total due 1250 EUR"]);
+        assert_eq!(result.language.as_deref(), Some("eng"));
+        // Boxes are mapped back from the 2x upscaled copy
+        assert!(result.lines[1].bbox.right() <= img.width() as f32);
+    }
+}
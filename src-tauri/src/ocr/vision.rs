@@ -0,0 +1,158 @@
+use super::{parse_json_output, write_temp_png, OcrEngine, OcrResult};
+use std::process::Command;
+
+/// Apple Vision (`VNRecognizeTextRequest`) driven through a Swift script.
+pub struct VisionEngine;
+
+const SWIFT_SCRIPT: &str = r#"
+import Vision
+import Cocoa
+
+if CommandLine.arguments[1] == "--list-languages" {
+    let langs = (try? VNRecognizeTextRequest.supportedRecognitionLanguages(for: .accurate, revision: VNRecognizeTextRequestRevision3)) ?? []
+    print(langs.joined(separator: "\n"))
+    exit(0)
+}
+
+let imagePath = CommandLine.arguments[1]
+guard let image = NSImage(contentsOfFile: imagePath),
+      let tiffData = image.tiffRepresentation,
+      let bitmap = NSBitmapImageRep(data: tiffData),
+      let cgImage = bitmap.cgImage else {
+    print("ERROR: Failed to load image. If this happens consistently, check Screen Recording permissions.")
+    exit(1)
+}
+
+let imageWidth = Double(cgImage.width)
+let imageHeight = Double(cgImage.height)
+
+// Vision uses normalized coordinates with the origin at the bottom-left
+func pixelRect(_ r: CGRect) -> [String: Double] {
+    return [
+        "x": Double(r.minX) * imageWidth,
+        "y": (1.0 - Double(r.maxY)) * imageHeight,
+        "width": Double(r.width) * imageWidth,
+        "height": Double(r.height) * imageHeight,
+    ]
+}
+
+var lines: [[String: Any]] = []
+let request = VNRecognizeTextRequest { (request, error) in
+    guard let observations = request.results as? [VNRecognizedTextObservation] else { return }
+    for observation in observations {
+        guard let candidate = observation.topCandidates(1).first else { continue }
+        let text = candidate.string
+        var words: [[String: Any]] = []
+        text.enumerateSubstrings(in: text.startIndex..<text.endIndex, options: .byWords) { (word, range, _, _) in
+            guard let word = word, let box = try? candidate.boundingBox(for: range) else { return }
+            words.append(["text": word, "bbox": pixelRect(box.boundingBox), "confidence": candidate.confidence])
+        }
+        lines.append([
+            "text": text,
+            "bbox": pixelRect(observation.boundingBox),
+            "confidence": candidate.confidence,
+            "words": words,
+        ])
+    }
+}
+request.recognitionLevel = .accurate
+request.usesLanguageCorrection = true
+
+// Preferred languages arrive comma-separated in OCR_LANGS (from AppConfig)
+let envLangs = ProcessInfo.processInfo.environment["OCR_LANGS"] ?? ""
+let requestedLangs = envLangs.split(separator: ",").map { String($0) }.filter { !$0.isEmpty }
+let desiredLangs = requestedLangs.isEmpty ? ["es-ES", "en-US"] : requestedLangs
+if let supportedLangs = try? VNRecognizeTextRequest.supportedRecognitionLanguages(for: .accurate, revision: request.revision) {
+    let validLangs = desiredLangs.filter { supportedLangs.contains($0) }
+    request.recognitionLanguages = validLangs.isEmpty ? supportedLangs : validLangs
+} else {
+    request.recognitionLanguages = desiredLangs
+}
+
+let handler = VNImageRequestHandler(cgImage: cgImage, options: [:])
+do {
+    try handler.perform([request])
+} catch {
+    print("ERROR: \(error)")
+    exit(1)
+}
+
+let payload: [String: Any] = ["lines": lines, "language": request.recognitionLanguages.first ?? ""]
+guard let data = try? JSONSerialization.data(withJSONObject: payload) else {
+    print("ERROR: Failed to serialize OCR result")
+    exit(1)
+}
+print("OCR_JSON:\(data.base64EncodedString())")
+"#;
+
+impl VisionEngine {
+    /// Runs the Swift script with `arg` (an image path or `--list-languages`).
+    fn run_script(
+        arg: &std::path::Path,
+        languages: &[String],
+    ) -> Result<std::process::Output, String> {
+        // Preliminary check: Is 'swift' available?
+        let swift_check = Command::new("which")
+            .arg("swift")
+            .output()
+            .map_err(|e| format!("Failed to check for swift: {}", e))?;
+
+        if !swift_check.status.success() {
+            return Err("El comando 'swift' no está disponible. Por favor, instala Xcode Command Line Tools (ejecuta 'xcode-select --install' en la Terminal).".to_string());
+        }
+
+        // Write swift script to a temporary file instead of using -e (fixes error on M1 macs without swift-driver)
+        let swift_script_path = std::env::temp_dir().join("mouse_crazy_ocr_script.swift");
+        std::fs::write(&swift_script_path, SWIFT_SCRIPT)
+            .map_err(|e| format!("Failed to write swift script: {}", e))?;
+
+        let output = Command::new("swift")
+            .arg(&swift_script_path)
+            .arg(arg)
+            .env("OCR_LANGS", languages.join(","))
+            .output();
+
+        let _ = std::fs::remove_file(&swift_script_path);
+
+        output.map_err(|e| format!("No se pudo ejecutar el script de OCR: {}", e))
+    }
+}
+
+impl OcrEngine for VisionEngine {
+    fn name(&self) -> &'static str {
+        "vision"
+    }
+
+    fn supported_languages(&self) -> Result<Vec<String>, String> {
+        let out = Self::run_script(std::path::Path::new("--list-languages"), &[])?;
+        if !out.status.success() {
+            return Err(String::from_utf8_lossy(&out.stderr).trim().to_string());
+        }
+        Ok(String::from_utf8_lossy(&out.stdout)
+            .lines()
+            .map(|l| l.trim().to_string())
+            .filter(|l| !l.is_empty())
+            .collect())
+    }
+
+    fn recognize(
+        &self,
+        image: &image::RgbaImage,
+        languages: &[String],
+    ) -> Result<OcrResult, String> {
+        let temp_image_path = write_temp_png(image, "mouse_crazy_ocr_capture.png")?;
+        let output = Self::run_script(&temp_image_path, languages);
+        let _ = std::fs::remove_file(&temp_image_path);
+
+        let out = output?;
+        let stdout = String::from_utf8_lossy(&out.stdout).trim().to_string();
+        let stderr = String::from_utf8_lossy(&out.stderr).trim().to_string();
+
+        if !out.status.success() || stdout.starts_with("ERROR:") {
+            let err_msg = if !stdout.is_empty() { stdout } else { stderr };
+            return Err(err_msg);
+        }
+
+        parse_json_output(&stdout)
+    }
+}
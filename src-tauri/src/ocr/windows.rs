@@ -0,0 +1,146 @@
+use super::{parse_json_output, upscale_for_ocr, write_temp_png, OcrEngine, OcrResult};
+use std::process::Command;
+
+/// `Windows.Media.Ocr` driven through PowerShell's WinRT bridge.
+pub struct WindowsOcrEngine;
+
+const OCR_PS: &str = r#"
+$ErrorActionPreference = 'Stop'
+Add-Type -AssemblyName System.Runtime.WindowsRuntime
+
+try {
+    $null = [Windows.Media.Ocr.OcrEngine,             Windows.Foundation, ContentType=WindowsRuntime]
+    $null = [Windows.Graphics.Imaging.BitmapDecoder,  Windows.Graphics,   ContentType=WindowsRuntime]
+    $null = [Windows.Graphics.Imaging.SoftwareBitmap, Windows.Graphics,   ContentType=WindowsRuntime]
+    $null = [Windows.Globalization.Language,          Windows.Globalization, ContentType=WindowsRuntime]
+
+    $asTaskGM = [System.WindowsRuntimeSystemExtensions].GetMethods() |
+        Where-Object { $_.Name -eq 'AsTask' -and $_.IsGenericMethodDefinition -and $_.GetParameters().Count -eq 1 } |
+        Select-Object -First 1
+
+    function Await { param($op, $type)
+        $asTaskGM.MakeGenericMethod($type).Invoke($null, @($op)).GetAwaiter().GetResult()
+    }
+
+    $fileStream = [System.IO.File]::OpenRead($env:OCR_IMG_PATH)
+    $ras        = [System.IO.WindowsRuntimeStreamExtensions]::AsRandomAccessStream($fileStream)
+    $decoder    = Await ([Windows.Graphics.Imaging.BitmapDecoder]::CreateAsync($ras)) ([Windows.Graphics.Imaging.BitmapDecoder])
+    $softBmp    = Await ($decoder.GetSoftwareBitmapAsync())                            ([Windows.Graphics.Imaging.SoftwareBitmap])
+    $fileStream.Dispose()
+
+    # Preferred languages arrive comma-separated in OCR_LANGS (from AppConfig).
+    # Windows.Media.Ocr recognizes one language at a time, so take the first installed one.
+    $engine = $null
+    foreach ($tag in ($env:OCR_LANGS -split ',' | Where-Object { $_ })) {
+        try {
+            $lang = [Windows.Globalization.Language]::new($tag)
+            $engine = [Windows.Media.Ocr.OcrEngine]::TryCreateFromLanguage($lang)
+        } catch { }
+        if ($null -ne $engine) { break }
+    }
+    if ($null -eq $engine) {
+        $engine = [Windows.Media.Ocr.OcrEngine]::TryCreateFromUserProfileLanguages()
+    }
+    if ($null -eq $engine) {
+        try {
+            $langEn = [Windows.Globalization.Language]::new('en-US')
+            $engine = [Windows.Media.Ocr.OcrEngine]::TryCreateFromLanguage($langEn)
+        } catch { }
+    }
+    if ($null -eq $engine) {
+        throw 'No OCR language pack is installed'
+    }
+
+    $result = Await ($engine.RecognizeAsync($softBmp)) ([Windows.Media.Ocr.OcrResult])
+
+    # OcrLine has no rectangle of its own; the Rust side derives it from the words
+    $lines = @()
+    foreach ($line in @($result.Lines)) {
+        $words = @()
+        foreach ($word in @($line.Words)) {
+            $r = $word.BoundingRect
+            $words += [PSCustomObject]@{
+                text = $word.Text
+                bbox = [PSCustomObject]@{ x = [double]$r.X; y = [double]$r.Y; width = [double]$r.Width; height = [double]$r.Height }
+            }
+        }
+        $lines += [PSCustomObject]@{ text = $line.Text; words = $words }
+    }
+
+    $payload = [PSCustomObject]@{ lines = $lines; language = $engine.RecognizerLanguage.LanguageTag }
+    $json = ConvertTo-Json $payload -Depth 6 -Compress
+    $bytes = [System.Text.Encoding]::UTF8.GetBytes($json)
+    Write-Output ("OCR_JSON:" + [System.Convert]::ToBase64String($bytes))
+} catch {
+    Write-Error $_.Exception.Message
+    exit 1
+}
+"#;
+
+const LIST_LANGUAGES_PS: &str = r#"
+$ErrorActionPreference = 'Stop'
+$null = [Windows.Media.Ocr.OcrEngine, Windows.Foundation, ContentType=WindowsRuntime]
+[Windows.Media.Ocr.OcrEngine]::AvailableRecognizerLanguages | ForEach-Object { $_.LanguageTag }
+"#;
+
+fn powershell(script: &str) -> Command {
+    use std::os::windows::process::CommandExt;
+
+    let mut cmd = Command::new("powershell");
+    cmd.arg("-NoProfile")
+        .arg("-NonInteractive")
+        .arg("-WindowStyle")
+        .arg("Hidden")
+        .arg("-Command")
+        .arg(script)
+        .creation_flags(0x08000000); // CREATE_NO_WINDOW
+    cmd
+}
+
+impl OcrEngine for WindowsOcrEngine {
+    fn name(&self) -> &'static str {
+        "windows-media-ocr"
+    }
+
+    fn supported_languages(&self) -> Result<Vec<String>, String> {
+        let out = powershell(LIST_LANGUAGES_PS)
+            .output()
+            .map_err(|e| format!("Failed to run Windows OCR: {}", e))?;
+        if !out.status.success() {
+            return Err(String::from_utf8_lossy(&out.stderr).trim().to_string());
+        }
+        Ok(String::from_utf8_lossy(&out.stdout)
+            .lines()
+            .map(|l| l.trim().to_string())
+            .filter(|l| !l.is_empty())
+            .collect())
+    }
+
+    fn recognize(
+        &self,
+        image: &image::RgbaImage,
+        languages: &[String],
+    ) -> Result<OcrResult, String> {
+        let (scaled, factor) = upscale_for_ocr(image);
+        let temp_image_path = write_temp_png(&scaled, "mouse_crazy_ocr_capture.png")?;
+
+        let output = powershell(OCR_PS)
+            .env("OCR_IMG_PATH", &temp_image_path)
+            .env("OCR_LANGS", languages.join(","))
+            .output();
+
+        let _ = std::fs::remove_file(&temp_image_path);
+
+        let out = output.map_err(|e| format!("Failed to run Windows OCR: {}", e))?;
+        if !out.status.success() {
+            let stderr = String::from_utf8_lossy(&out.stderr).trim().to_string();
+            return Err(if !stderr.is_empty() {
+                stderr
+            } else {
+                "Unknown PowerShell error".to_string()
+            });
+        }
+
+        Ok(parse_json_output(&String::from_utf8_lossy(&out.stdout))?.scaled(factor))
+    }
+}
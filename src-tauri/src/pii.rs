@@ -0,0 +1,452 @@
+//! Detection of personal data in OCR results, for redacting screenshots before they
+//! are shared: email addresses, phone numbers, payment card numbers and IBANs.
+//!
+//! Matching works on OCR words so every finding maps back to pixels. Card numbers
+//! and IBANs are checksum-validated (Luhn, ISO 7064 mod 97) to keep false positives
+//! down; phone numbers are sanitized like the WhatsApp scheduler does.
+
+use crate::ocr::{BoundingBox, OcrResult};
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum PiiKind {
+    Email,
+    Phone,
+    CreditCard,
+    Iban,
+}
+
+impl PiiKind {
+    pub const ALL: [PiiKind; 4] = [
+        PiiKind::Email,
+        PiiKind::Phone,
+        PiiKind::CreditCard,
+        PiiKind::Iban,
+    ];
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct PiiFinding {
+    pub kind: PiiKind,
+    /// Masked value for the report, e.g. `j•••@example.com` or `•••• 4242`
+    pub masked: String,
+    /// Pixels of the recognized image covering the match
+    pub bbox: BoundingBox,
+}
+
+/// Keeps only digits and `+`, the form phone numbers are dialed in.
+pub fn sanitize_phone(phone: &str) -> String {
+    phone
+        .chars()
+        .filter(|c| c.is_ascii_digit() || *c == '+')
+        .collect()
+}
+
+struct Word<'a> {
+    text: &'a str,
+    bbox: BoundingBox,
+}
+
+/// Words of every line. Lines without word boxes are split on whitespace with
+/// boxes estimated from character counts.
+fn words(result: &OcrResult) -> Vec<Vec<Word<'_>>> {
+    result
+        .lines
+        .iter()
+        .map(|line| {
+            if !line.words.is_empty() {
+                return line
+                    .words
+                    .iter()
+                    .map(|w| Word {
+                        text: w.text.as_str(),
+                        bbox: w.bbox,
+                    })
+                    .collect();
+            }
+            let total = line.text.chars().count().max(1) as f32;
+            let char_width = line.bbox.width / total;
+            let mut words = Vec::new();
+            let mut offset = 0;
+            for token in line.text.split(' ') {
+                let len = token.chars().count();
+                if !token.trim().is_empty() {
+                    words.push(Word {
+                        text: token,
+                        bbox: BoundingBox::new(
+                            line.bbox.x + offset as f32 * char_width,
+                            line.bbox.y,
+                            len as f32 * char_width,
+                            line.bbox.height,
+                        ),
+                    });
+                }
+                offset += len + 1;
+            }
+            words
+        })
+        .collect()
+}
+
+/// Punctuation OCR glues onto the ends of words ("(mail: a@b.com)," etc.).
+fn trim_punctuation(word: &str) -> &str {
+    word.trim_matches(|c: char| matches!(c, ',' | ';' | ':' | '"' | '\'' | '<' | '>' | '[' | ']'))
+        .trim_start_matches('(')
+        .trim_end_matches(['.', ')'])
+}
+
+/// Finds the enabled kinds of personal data in `result`.
+pub fn detect(result: &OcrResult, kinds: &[PiiKind]) -> Vec<PiiFinding> {
+    let mut findings = Vec::new();
+
+    for line in words(result) {
+        let mut i = 0;
+        while i < line.len() {
+            let word = trim_punctuation(line[i].text);
+
+            if kinds.contains(&PiiKind::Email) {
+                // "Email:john@example.com" still counts; the whole word is covered
+                let candidate = word.rsplit(':').next().unwrap_or(word);
+                if is_email(candidate) {
+                    findings.push(PiiFinding {
+                        kind: PiiKind::Email,
+                        masked: mask_email(candidate),
+                        bbox: line[i].bbox,
+                    });
+                    i += 1;
+                    continue;
+                }
+            }
+
+            if kinds.contains(&PiiKind::Iban) {
+                if let Some(len) = match_iban(&line[i..]) {
+                    let text: String = line[i..i + len].iter().map(|w| w.text).collect();
+                    findings.push(PiiFinding {
+                        kind: PiiKind::Iban,
+                        masked: mask_tail(&text, 4),
+                        bbox: union(&line[i..i + len]),
+                    });
+                    i += len;
+                    continue;
+                }
+            }
+
+            // Numbers are often split into several OCR words ("4242 4242 ...")
+            let run = line[i..]
+                .iter()
+                .take_while(|w| is_numeric_word(trim_punctuation(w.text)))
+                .count();
+            if run > 0 {
+                let text = line[i..i + run]
+                    .iter()
+                    .map(|w| trim_punctuation(w.text))
+                    .collect::<Vec<_>>()
+                    .join(" ");
+                let kind = if is_card_number(&text) {
+                    Some(PiiKind::CreditCard)
+                } else if is_phone_number(&text) {
+                    Some(PiiKind::Phone)
+                } else {
+                    None
+                };
+                if let Some(kind) = kind.filter(|k| kinds.contains(k)) {
+                    findings.push(PiiFinding {
+                        kind,
+                        masked: mask_tail(&text, if kind == PiiKind::Phone { 2 } else { 4 }),
+                        bbox: union(&line[i..i + run]),
+                    });
+                }
+                i += run;
+                continue;
+            }
+
+            i += 1;
+        }
+    }
+
+    findings
+}
+
+fn union(words: &[Word]) -> BoundingBox {
+    words
+        .iter()
+        .fold(BoundingBox::default(), |acc, w| acc.union(&w.bbox))
+}
+
+fn is_email(word: &str) -> bool {
+    let Some((local, domain)) = word.split_once('@') else {
+        return false;
+    };
+    let local_ok = !local.is_empty()
+        && local
+            .chars()
+            .all(|c| c.is_alphanumeric() || "._%+-".contains(c));
+    let labels: Vec<&str> = domain.split('.').collect();
+    let domain_ok = labels.len() >= 2
+        && labels
+            .iter()
+            .all(|l| !l.is_empty() && l.chars().all(|c| c.is_alphanumeric() || c == '-'))
+        && labels
+            .last()
+            .is_some_and(|tld| tld.len() >= 2 && tld.chars().all(char::is_alphabetic));
+    local_ok && domain_ok
+}
+
+/// Digits with the separators phone and card numbers are written with.
+fn is_numeric_word(word: &str) -> bool {
+    word.chars().any(|c| c.is_ascii_digit())
+        && word
+            .chars()
+            .all(|c| c.is_ascii_digit() || matches!(c, '+' | '(' | ')' | '-' | '.'))
+}
+
+fn digits(text: &str) -> Vec<u32> {
+    text.chars().filter_map(|c| c.to_digit(10)).collect()
+}
+
+fn luhn_valid(digits: &[u32]) -> bool {
+    let sum: u32 = digits
+        .iter()
+        .rev()
+        .enumerate()
+        .map(|(i, &d)| match (i % 2, d * 2) {
+            (1, doubled) if doubled > 9 => doubled - 9,
+            (1, doubled) => doubled,
+            _ => d,
+        })
+        .sum();
+    sum.is_multiple_of(10)
+}
+
+fn is_card_number(text: &str) -> bool {
+    if text.contains(['+', '(', ')', '.']) {
+        return false;
+    }
+    let digits = digits(text);
+    (13..=19).contains(&digits.len()) && luhn_valid(&digits)
+}
+
+/// 7 to 15 digits (E.164), with a leading `+`, separators or at least ten digits so
+/// plain amounts and years aren't taken for phone numbers. Dates, dotted amounts and
+/// IP addresses are rejected.
+fn is_phone_number(text: &str) -> bool {
+    let sanitized = sanitize_phone(text);
+    let digit_count = sanitized.chars().filter(char::is_ascii_digit).count();
+    if !(7..=15).contains(&digit_count) || sanitized[1..].contains('+') {
+        return false;
+    }
+    if looks_like_date(text) || looks_like_dotted_number(text) {
+        return false;
+    }
+    let has_separators = text.contains([' ', '-', '(', '.']);
+    sanitized.starts_with('+') || has_separators || digit_count >= 10
+}
+
+/// `2024-01-31`, `31.01.2024`, `31-01-24`...
+fn looks_like_date(text: &str) -> bool {
+    let groups: Vec<&str> = text.split(['-', '.']).collect();
+    groups.len() == 3
+        && groups
+            .iter()
+            .all(|g| !g.is_empty() && g.chars().all(|c| c.is_ascii_digit()))
+        && groups.iter().filter(|g| g.len() <= 2).count() >= 2
+}
+
+/// `1.234.567` (thousands) or `192.168.1.20` (IPv4).
+fn looks_like_dotted_number(text: &str) -> bool {
+    let groups: Vec<&str> = text.split('.').collect();
+    if groups.len() < 2
+        || !groups
+            .iter()
+            .all(|g| !g.is_empty() && g.len() <= 3 && g.chars().all(|c| c.is_ascii_digit()))
+    {
+        return false;
+    }
+    let thousands = groups[1..].iter().all(|g| g.len() == 3);
+    let ipv4 = groups.len() == 4
+        && groups
+            .iter()
+            .all(|g| g.parse::<u16>().is_ok_and(|n| n <= 255));
+    thousands || ipv4
+}
+
+/// Number of words starting at `words[0]` that form a valid IBAN, longest match.
+fn match_iban(words: &[Word]) -> Option<usize> {
+    let first = trim_punctuation(words.first()?.text);
+    let bytes = first.as_bytes();
+    let starts_like_iban = bytes.len() >= 4
+        && bytes[..2].iter().all(u8::is_ascii_uppercase)
+        && bytes[2..4].iter().all(u8::is_ascii_digit);
+    if !starts_like_iban {
+        return None;
+    }
+
+    let mut compact = String::new();
+    let mut best = None;
+    for (n, word) in words.iter().enumerate() {
+        let part = trim_punctuation(word.text);
+        if part.is_empty() || !part.chars().all(|c| c.is_ascii_alphanumeric()) {
+            break;
+        }
+        compact.push_str(&part.to_ascii_uppercase());
+        if compact.len() > 34 {
+            break;
+        }
+        if compact.len() >= 15 && iban_valid(&compact) {
+            best = Some(n + 1);
+        }
+    }
+    best
+}
+
+/// ISO 13616: move the first four characters to the end, letters become 10..35, and
+/// the resulting number mod 97 must be 1.
+fn iban_valid(iban: &str) -> bool {
+    let (head, tail) = iban.split_at(4);
+    let mut remainder = 0u32;
+    for c in tail.chars().chain(head.chars()) {
+        let Some(value) = c.to_digit(36) else {
+            return false;
+        };
+        remainder = if value < 10 {
+            (remainder * 10 + value) % 97
+        } else {
+            (remainder * 100 + value) % 97
+        };
+    }
+    remainder == 1
+}
+
+fn mask_email(email: &str) -> String {
+    let (local, domain) = email.split_once('@').unwrap_or((email, ""));
+    let first: String = local.chars().take(1).collect();
+    format!("{}•••@{}", first, domain)
+}
+
+/// Keeps the last `keep` alphanumerics: `•••• 4242`.
+fn mask_tail(text: &str, keep: usize) -> String {
+    let chars: Vec<char> = text.chars().filter(char::is_ascii_alphanumeric).collect();
+    let tail: String = chars[chars.len().saturating_sub(keep)..].iter().collect();
+    format!("•••• {}", tail)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ocr::{MockEngine, OcrEngine};
+
+    fn findings(lines: &[&str]) -> Vec<(PiiKind, String)> {
+        let result = MockEngine::with_lines(lines)
+            .recognize(&image::RgbaImage::new(1, 1), &[])
+            .unwrap();
+        detect(&result, &PiiKind::ALL)
+            .into_iter()
+            .map(|f| (f.kind, f.masked))
+            .collect()
+    }
+
+    #[test]
+    fn luhn() {
+        assert!(luhn_valid(&digits("4242 4242 4242 4242")));
+        assert!(luhn_valid(&digits("79927398713")));
+        assert!(!luhn_valid(&digits("4242 4242 4242 4241")));
+        assert!(!luhn_valid(&digits("79927398710")));
+
+        assert!(is_card_number("4242-4242-4242-4242"));
+        assert!(is_card_number("378282246310005"));
+        // Too short to be a card, even with a valid checksum
+        assert!(!is_card_number("79927398713"));
+        assert!(!is_card_number("+4242 4242 4242 4242"));
+    }
+
+    #[test]
+    fn iban_mod_97() {
+        assert!(iban_valid("DE89370400440532013000"));
+        assert!(iban_valid("GB82WEST12345698765432"));
+        assert!(iban_valid("ES9121000418450200051332"));
+        assert!(!iban_valid("DE89370400440532013001"));
+        assert!(!iban_valid("GB82WEST12345698765423"));
+        assert!(!iban_valid("DE89-370400440532013000"));
+    }
+
+    #[test]
+    fn phone_numbers() {
+        assert!(is_phone_number("+52 55 1234 5678"));
+        assert!(is_phone_number("(555) 123-4567"));
+        assert!(is_phone_number("555.123.4567"));
+        assert!(is_phone_number("5551234567"));
+        // Plain numbers need ten digits, a `+` or separators
+        assert!(!is_phone_number("1234567"));
+        assert!(!is_phone_number("12345"));
+        assert!(!is_phone_number("+1 555 123+4567"));
+        assert!(!is_phone_number("1234567890123456"));
+    }
+
+    #[test]
+    fn dates_and_dotted_numbers() {
+        for date in ["2024-01-31", "31.01.2024", "31-01-24"] {
+            assert!(looks_like_date(date), "{}", date);
+            assert!(!is_phone_number(date), "{}", date);
+        }
+        assert!(!looks_like_date("555-123-4567"));
+        assert!(looks_like_dotted_number("1.234.567"));
+        assert!(looks_like_dotted_number("192.168.1.20"));
+        assert!(!looks_like_dotted_number("555.123.4567"));
+        assert!(!is_phone_number("192.168.100.200"));
+    }
+
+    #[test]
+    fn emails() {
+        assert!(is_email("juan.perez@example.com"));
+        assert!(is_email("a+tag@mail.example.es"));
+        assert!(!is_email("@example.com"));
+        assert!(!is_email("juan@localhost"));
+        assert!(!is_email("juan@example.c0m"));
+        assert!(!is_email("juan@@example.com"));
+        assert_eq!(mask_email("juan@example.com"), "j•••@example.com");
+    }
+
+    #[test]
+    fn detects_mixed_text() {
+        let found = findings(&[
+            "Contacto: (juan.perez@example.com), tel +52 55 1234 5678.",
+            "Card 4242 4242 4242 4242 exp 12/27",
+            "IBAN DE89 3704 0044 0532 0130 00 thanks",
+            "Email:ana@mail.es",
+        ]);
+        assert_eq!(
+            found,
+            [
+                (PiiKind::Email, "j•••@example.com".to_string()),
+                (PiiKind::Phone, "•••• 78".to_string()),
+                (PiiKind::CreditCard, "•••• 4242".to_string()),
+                (PiiKind::Iban, "•••• 3000".to_string()),
+                (PiiKind::Email, "a•••@mail.es".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn ignores_look_alikes() {
+        let found = findings(&[
+            "Order 1234567 shipped 2024-01-31, invoice 4242 4242 4242 4241",
+            "Total 1.234.567 from 192.168.100.200 in 2024",
+            "Ref DE88 3704 0044 0532 0130 00 at 12:30",
+            "user@host, see page 12",
+        ]);
+        assert!(found.is_empty(), "{:?}", found);
+    }
+
+    #[test]
+    fn only_enabled_kinds() {
+        let result = MockEngine::with_lines(&["mail a@b.com card 4242 4242 4242 4242"])
+            .recognize(&image::RgbaImage::new(1, 1), &[])
+            .unwrap();
+        let found = detect(&result, &[PiiKind::CreditCard]);
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].kind, PiiKind::CreditCard);
+        // The box covers all four words
+        assert_eq!(found[0].bbox.width, 4.0 * 32.0 + 3.0 * 8.0);
+    }
+}
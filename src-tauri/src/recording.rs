@@ -0,0 +1,431 @@
+//! Region recording: a capture thread grabs frames at a fixed rate and hands them
+//! to an encoder thread writing an animated GIF (image crate) or WebM (ffmpeg).
+
+use crate::capture::{self, CaptureTarget};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Arc};
+use std::time::{Duration, Instant};
+
+pub const DEFAULT_FPS: u32 = 10;
+pub const MAX_FPS: u32 = 30;
+pub const DEFAULT_MAX_SECS: u64 = 120;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum RecordingFormat {
+    #[default]
+    Gif,
+    Webm,
+}
+
+impl RecordingFormat {
+    pub fn extension(self) -> &'static str {
+        match self {
+            RecordingFormat::Gif => "gif",
+            RecordingFormat::Webm => "webm",
+        }
+    }
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct RecordingResult {
+    pub path: String,
+    pub format: RecordingFormat,
+    pub frames: u32,
+    /// Frames skipped because capture or encoding couldn't keep up
+    pub dropped: u32,
+    pub duration_ms: u64,
+}
+
+struct Frame {
+    image: image::RgbaImage,
+    at: Duration,
+}
+
+/// A running recording. Dropping it without [`Recorder::stop`] leaves the threads
+/// running until `max_secs`.
+pub struct Recorder {
+    stop: Arc<AtomicBool>,
+    started_at: Instant,
+    capture_thread: std::thread::JoinHandle<u32>,
+    encoder_thread: std::thread::JoinHandle<Result<u32, String>>,
+    path: PathBuf,
+    format: RecordingFormat,
+}
+
+impl Recorder {
+    /// Starts grabbing `target` at `fps` into `path`. Stops on its own after `max_secs`.
+    pub fn start(
+        target: CaptureTarget,
+        fps: u32,
+        max_secs: u64,
+        format: RecordingFormat,
+        path: PathBuf,
+    ) -> Result<Self, String> {
+        let fps = fps.clamp(1, MAX_FPS);
+        let capturer = capture::default_capturer();
+
+        // Grab one frame up front: it fails fast on permission problems and fixes the size
+        let first = capturer.capture(target)?;
+        let (width, height) = match format {
+            // VP9 with yuv420p needs even dimensions
+            RecordingFormat::Webm => (first.width() & !1, first.height() & !1),
+            RecordingFormat::Gif => first.dimensions(),
+        };
+        if width == 0 || height == 0 {
+            return Err("Recording region is too small".to_string());
+        }
+
+        let sink: Box<dyn FrameSink> = match format {
+            RecordingFormat::Gif => Box::new(GifSink::new(&path)?),
+            RecordingFormat::Webm => Box::new(WebmSink::new(&path, width, height, fps)?),
+        };
+
+        let stop = Arc::new(AtomicBool::new(false));
+        // A couple of seconds of buffer; beyond that frames are dropped rather than piling up
+        let (tx, rx) = mpsc::sync_channel::<Frame>((fps * 2) as usize);
+        let started_at = Instant::now();
+
+        let _ = tx.try_send(Frame {
+            image: fit(first, width, height),
+            at: Duration::ZERO,
+        });
+
+        let capture_stop = stop.clone();
+        let capture_thread = std::thread::spawn(move || {
+            let interval = Duration::from_secs_f64(1.0 / fps as f64);
+            let max = Duration::from_secs(max_secs);
+            let mut next = started_at + interval;
+            let mut dropped = 0;
+
+            while !capture_stop.load(Ordering::Relaxed) && started_at.elapsed() < max {
+                let now = Instant::now();
+                if next > now {
+                    std::thread::sleep(next - now);
+                }
+                next += interval;
+
+                let frame = match capturer.capture(target) {
+                    Ok(img) => Frame {
+                        image: fit(img, width, height),
+                        at: started_at.elapsed(),
+                    },
+                    Err(_) => {
+                        dropped += 1;
+                        continue;
+                    }
+                };
+                if tx.try_send(frame).is_err() {
+                    dropped += 1;
+                }
+            }
+            dropped
+        });
+
+        let encoder_thread = std::thread::spawn(move || encode(rx, sink, fps));
+
+        Ok(Self {
+            stop,
+            started_at,
+            capture_thread,
+            encoder_thread,
+            path,
+            format,
+        })
+    }
+
+    pub fn elapsed(&self) -> Duration {
+        self.started_at.elapsed()
+    }
+
+    /// Stops capturing and waits for the encoder to finish the file. Blocking.
+    pub fn stop(self) -> Result<RecordingResult, String> {
+        self.stop.store(true, Ordering::Relaxed);
+        let duration = self.started_at.elapsed();
+        let dropped = self
+            .capture_thread
+            .join()
+            .map_err(|_| "Recording capture thread panicked".to_string())?;
+        let frames = self
+            .encoder_thread
+            .join()
+            .map_err(|_| "Recording encoder thread panicked".to_string())??;
+
+        Ok(RecordingResult {
+            path: self.path.to_string_lossy().to_string(),
+            format: self.format,
+            frames,
+            dropped,
+            duration_ms: duration.as_millis() as u64,
+        })
+    }
+}
+
+/// Crops frames to the recording size (the screen can change under us, e.g. a
+/// resolution switch mid-recording).
+fn fit(img: image::RgbaImage, width: u32, height: u32) -> image::RgbaImage {
+    if img.dimensions() == (width, height) {
+        return img;
+    }
+    let mut out = image::RgbaImage::new(width, height);
+    image::imageops::replace(&mut out, &img, 0, 0);
+    out
+}
+
+trait FrameSink: Send {
+    /// Writes `image`, shown for `duration`.
+    fn write(&mut self, image: &image::RgbaImage, duration: Duration) -> Result<(), String>;
+    fn finish(self: Box<Self>) -> Result<(), String>;
+}
+
+/// Each frame is held until the next one arrives so its on-screen time matches the
+/// real capture interval, even when frames were dropped.
+fn encode(
+    rx: mpsc::Receiver<Frame>,
+    mut sink: Box<dyn FrameSink>,
+    fps: u32,
+) -> Result<u32, String> {
+    let nominal = Duration::from_secs_f64(1.0 / fps as f64);
+    let mut pending: Option<Frame> = None;
+    let mut written = 0;
+
+    for frame in rx {
+        if let Some(prev) = pending.take() {
+            sink.write(
+                &prev.image,
+                frame.at.saturating_sub(prev.at).max(nominal / 2),
+            )?;
+            written += 1;
+        }
+        pending = Some(frame);
+    }
+    if let Some(last) = pending {
+        sink.write(&last.image, nominal)?;
+        written += 1;
+    }
+
+    sink.finish()?;
+    Ok(written)
+}
+
+struct GifSink {
+    encoder: image::codecs::gif::GifEncoder<std::io::BufWriter<std::fs::File>>,
+}
+
+impl GifSink {
+    fn new(path: &Path) -> Result<Self, String> {
+        let file = std::fs::File::create(path)
+            .map_err(|e| format!("Failed to create recording file: {}", e))?;
+        // Speed 10 trades a little palette quality for real-time encoding
+        let mut encoder =
+            image::codecs::gif::GifEncoder::new_with_speed(std::io::BufWriter::new(file), 10);
+        encoder
+            .set_repeat(image::codecs::gif::Repeat::Infinite)
+            .map_err(|e| format!("Failed to start GIF: {}", e))?;
+        Ok(Self { encoder })
+    }
+}
+
+impl FrameSink for GifSink {
+    fn write(&mut self, image: &image::RgbaImage, duration: Duration) -> Result<(), String> {
+        let delay = image::Delay::from_saturating_duration(duration);
+        self.encoder
+            .encode_frame(image::Frame::from_parts(image.clone(), 0, 0, delay))
+            .map_err(|e| format!("Failed to encode GIF frame: {}", e))
+    }
+
+    fn finish(self: Box<Self>) -> Result<(), String> {
+        // The trailer is written when the encoder is dropped
+        drop(self.encoder);
+        Ok(())
+    }
+}
+
+/// Pipes raw RGBA frames into `ffmpeg` (VP9). ffmpeg expects a constant frame rate,
+/// so long frames are repeated to fill their duration.
+struct WebmSink {
+    child: std::process::Child,
+    stdin: Option<std::process::ChildStdin>,
+    /// Reads ffmpeg's stderr as it comes so a chatty ffmpeg never blocks on a full pipe
+    stderr: Option<std::thread::JoinHandle<String>>,
+    width: u32,
+    height: u32,
+    frame_time: Duration,
+    /// Time covered by frames written so far vs. time the frames should cover
+    written: Duration,
+    expected: Duration,
+}
+
+impl WebmSink {
+    fn new(path: &Path, width: u32, height: u32, fps: u32) -> Result<Self, String> {
+        use std::process::{Command, Stdio};
+
+        let mut cmd = Command::new("ffmpeg");
+        cmd.args([
+            "-y",
+            "-loglevel",
+            "error",
+            "-f",
+            "rawvideo",
+            "-pix_fmt",
+            "rgba",
+        ])
+        .arg("-s")
+        .arg(format!("{}x{}", width, height))
+        .arg("-r")
+        .arg(fps.to_string())
+        .args(["-i", "-", "-c:v", "libvpx-vp9", "-b:v", "0", "-crf", "32"])
+        .args(["-deadline", "realtime", "-pix_fmt", "yuv420p"])
+        .arg(path)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped());
+
+        #[cfg(target_os = "windows")]
+        {
+            use std::os::windows::process::CommandExt;
+            cmd.creation_flags(0x08000000); // CREATE_NO_WINDOW
+        }
+
+        let mut child = cmd.spawn().map_err(|e| {
+            if e.kind() == std::io::ErrorKind::NotFound {
+                "WebM recording needs ffmpeg. Install it and make sure it is on PATH, or record as GIF.".to_string()
+            } else {
+                format!("Failed to start ffmpeg: {}", e)
+            }
+        })?;
+        let stdin = child.stdin.take();
+        let stderr = child.stderr.take().map(|mut pipe| {
+            std::thread::spawn(move || {
+                use std::io::Read;
+
+                let mut text = String::new();
+                let _ = pipe.read_to_string(&mut text);
+                text
+            })
+        });
+
+        Ok(Self {
+            child,
+            stdin,
+            stderr,
+            width,
+            height,
+            frame_time: Duration::from_secs_f64(1.0 / fps as f64),
+            written: Duration::ZERO,
+            expected: Duration::ZERO,
+        })
+    }
+}
+
+impl FrameSink for WebmSink {
+    fn write(&mut self, image: &image::RgbaImage, duration: Duration) -> Result<(), String> {
+        use std::io::Write;
+
+        let stdin = self.stdin.as_mut().ok_or("ffmpeg input is closed")?;
+        let frame = fit(image.clone(), self.width, self.height);
+
+        self.expected += duration;
+        // Always write at least one copy so no captured frame is lost
+        loop {
+            stdin
+                .write_all(frame.as_raw())
+                .map_err(|e| format!("Failed to write frame to ffmpeg: {}", e))?;
+            self.written += self.frame_time;
+            if self.written + self.frame_time / 2 >= self.expected {
+                break;
+            }
+        }
+        Ok(())
+    }
+
+    fn finish(mut self: Box<Self>) -> Result<(), String> {
+        // Closing stdin tells ffmpeg the stream is over
+        drop(self.stdin.take());
+        let status = self
+            .child
+            .wait()
+            .map_err(|e| format!("Failed to wait for ffmpeg: {}", e))?;
+        let stderr = self
+            .stderr
+            .take()
+            .and_then(|reader| reader.join().ok())
+            .unwrap_or_default();
+        if !status.success() {
+            return Err(format!("ffmpeg failed: {}", stderr.trim()));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::AnimationDecoder;
+
+    /// Records what [`encode`] hands the sink.
+    struct Durations(Arc<std::sync::Mutex<Vec<Duration>>>);
+
+    impl FrameSink for Durations {
+        fn write(&mut self, _image: &image::RgbaImage, duration: Duration) -> Result<(), String> {
+            self.0.lock().unwrap().push(duration);
+            Ok(())
+        }
+
+        fn finish(self: Box<Self>) -> Result<(), String> {
+            Ok(())
+        }
+    }
+
+    /// Frames captured at these times, each a different shade.
+    fn send(times_ms: &[u64]) -> mpsc::Receiver<Frame> {
+        let (tx, rx) = mpsc::channel();
+        for (i, &ms) in times_ms.iter().enumerate() {
+            let shade = (i * 60) as u8;
+            tx.send(Frame {
+                image: image::RgbaImage::from_pixel(8, 6, image::Rgba([shade, 0, 0, 255])),
+                at: Duration::from_millis(ms),
+            })
+            .unwrap();
+        }
+        rx
+    }
+
+    #[test]
+    fn frames_last_until_the_next_one() {
+        let durations = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let sink = Box::new(Durations(durations.clone()));
+        // At 10 fps: on time, two dropped, then one only 10ms later
+        let written = encode(send(&[0, 100, 350, 360]), sink, 10).unwrap();
+        assert_eq!(written, 4);
+        let ms: Vec<u128> = durations
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|d| d.as_millis())
+            .collect();
+        // Half a frame at least, and the last one gets the nominal frame time
+        assert_eq!(ms, [100, 250, 50, 100]);
+    }
+
+    #[test]
+    fn gif_delays_match_the_capture_times() {
+        let path =
+            std::env::temp_dir().join(format!("taskgoblin-recording-{}.gif", std::process::id()));
+        let sink = Box::new(GifSink::new(&path).unwrap());
+        assert_eq!(encode(send(&[0, 100, 350, 360]), sink, 10).unwrap(), 4);
+
+        let file = std::io::BufReader::new(std::fs::File::open(&path).unwrap());
+        let frames = image::codecs::gif::GifDecoder::new(file)
+            .unwrap()
+            .into_frames()
+            .collect_frames()
+            .unwrap();
+        std::fs::remove_file(&path).unwrap();
+        let delays: Vec<(u32, u32)> = frames.iter().map(|f| f.delay().numer_denom_ms()).collect();
+        assert_eq!(delays, [(100, 1), (250, 1), (50, 1), (100, 1)]);
+        assert_eq!(frames[1].buffer().get_pixel(0, 0).0, [60, 0, 0, 255]);
+    }
+}
@@ -655,8 +655,8 @@ function App() {
   const handleExtractText = async () => {
     setHoveredItem(null); // Clear tooltip when starting OCR
     try {
-      const result = await invoke("process_screenshot_ocr") as string;
-      if (result === "NO_TEXT") {
+      const result = await invoke("process_screenshot_ocr") as { text: string };
+      if (!result.text.trim()) {
         showToast(t('ocr.no_text'));
       } else {
         showToast(t('ocr.success'));
@@ -1,15 +1,30 @@
 import React, { useEffect, useRef, useState } from "react";
 import ReactDOM from "react-dom/client";
 import { invoke } from "@tauri-apps/api/core";
-import { getCurrentWindow } from "@tauri-apps/api/window";
 
 interface Rect { x: number; y: number; w: number; h: number }
 
+type OcrMode = "text" | "tsv" | "csv" | "markdown";
+
+const MODES: { id: OcrMode; label: string }[] = [
+    { id: "text", label: "Texto" },
+    { id: "tsv", label: "Tabla (TSV)" },
+    { id: "csv", label: "CSV" },
+    { id: "markdown", label: "Markdown" },
+];
+
+// Initial OCR mode comes from the backend (capture.html?mode=...). Without it the
+// capture isn't for OCR (e.g. QR decoding) and the mode toolbar is hidden.
+const initialMode = new URLSearchParams(window.location.search).get("mode") as OcrMode | null;
+
 function CaptureOverlay() {
     const canvasRef = useRef<HTMLCanvasElement>(null);
     const startRef = useRef<{ x: number; y: number } | null>(null);
     const [rect, setRect] = useState<Rect | null>(null);
     const dragging = useRef(false);
+    const [mode, setMode] = useState<OcrMode>(
+        MODES.some((m) => m.id === initialMode) ? initialMode! : "text"
+    );
 
     // Draw selection rect on canvas
     useEffect(() => {
@@ -88,17 +103,17 @@ function CaptureOverlay() {
         const h = Math.abs(e.clientY - startRef.current.y);
 
         if (w > 5 && h > 5) {
-            // Get device pixel ratio for hi-DPI screens
-            const dpr = window.devicePixelRatio || 1;
+            // Logical (CSS) pixels; the backend knows this overlay's monitor and DPI scale
             await invoke("finalize_capture", {
-                x: Math.round(x * dpr),
-                y: Math.round(y * dpr),
-                w: Math.round(w * dpr),
-                h: Math.round(h * dpr),
+                x,
+                y,
+                w,
+                h,
+                mode: initialMode ? mode : null,
             });
         } else {
-            // Tiny selection = cancel
-            await getCurrentWindow().close();
+            // Tiny selection = cancel (closes the overlays on every monitor)
+            await invoke("cancel_capture");
         }
     };
 
@@ -109,22 +124,60 @@ function CaptureOverlay() {
     };
 
     return (
-        <canvas
-            ref={canvasRef}
-            style={{
-                position: "fixed",
-                inset: 0,
-                width: "100vw",
-                height: "100vh",
-                cursor: "crosshair",
-                display: "block",
-            }}
-            onMouseDown={onMouseDown}
-            onMouseMove={onMouseMove}
-            onMouseUp={onMouseUp}
-            onKeyDown={onKeyDown}
-            tabIndex={0}
-        />
+        <>
+            <canvas
+                ref={canvasRef}
+                style={{
+                    position: "fixed",
+                    inset: 0,
+                    width: "100vw",
+                    height: "100vh",
+                    cursor: "crosshair",
+                    display: "block",
+                }}
+                onMouseDown={onMouseDown}
+                onMouseMove={onMouseMove}
+                onMouseUp={onMouseUp}
+                onKeyDown={onKeyDown}
+                tabIndex={0}
+            />
+            {/* Output mode toolbar; hidden while dragging so it never covers the selection */}
+            {initialMode && !dragging.current && (
+                <div
+                    style={{
+                        position: "fixed",
+                        top: 16,
+                        left: "50%",
+                        transform: "translateX(-50%)",
+                        display: "flex",
+                        gap: 4,
+                        padding: 4,
+                        borderRadius: 999,
+                        background: "rgba(0,0,0,0.75)",
+                    }}
+                    onMouseDown={(e) => e.stopPropagation()}
+                    onMouseUp={(e) => e.stopPropagation()}
+                >
+                    {MODES.map((m) => (
+                        <button
+                            key={m.id}
+                            onClick={() => setMode(m.id)}
+                            style={{
+                                border: "none",
+                                borderRadius: 999,
+                                padding: "6px 12px",
+                                font: "12px sans-serif",
+                                cursor: "pointer",
+                                color: mode === m.id ? "#000" : "#fff",
+                                background: mode === m.id ? "#fff" : "transparent",
+                            }}
+                        >
+                            {m.label}
+                        </button>
+                    ))}
+                </div>
+            )}
+        </>
     );
 }
 
@@ -145,11 +145,13 @@ const ImageConverter: React.FC<{ showToast: (msg: string) => void, t: (key: stri
             await invoke("process_image", {
                 inputPath,
                 outputPath,
-                format,
-                width: width ? Number.parseInt(width) : null,
-                height: height ? Number.parseInt(height) : null,
-                quality: (format === 'jpg' || format === 'jpeg') ? quality : null,
-                optimize: optimize
+                request: {
+                    format,
+                    width: width ? Number.parseInt(width) : null,
+                    height: height ? Number.parseInt(height) : null,
+                    quality: (format === 'jpg' || format === 'jpeg') ? quality : null,
+                    optimize: optimize
+                }
             });
 
             showToast(t('image.toast_success'));
@@ -251,6 +251,10 @@ export const translations = {
             failed: "OCR Failed",
             shortcut_hint: "Press Ctrl x3",
         },
+        recording: {
+            starting: "Recording in",
+            stop: "Stop recording",
+        },
         tooltips: {
             move_mouse: "Automatically moves the mouse to keep you active in apps that track activity.",
             whatsapp: "Schedule WhatsApp messages to be sent automatically at a specific date and time.",
@@ -508,6 +512,10 @@ export const translations = {
             failed: "Fallo en OCR",
             shortcut_hint: "Presiona Ctrl x3",
         },
+        recording: {
+            starting: "Grabando en",
+            stop: "Detener grabación",
+        },
         tooltips: {
             move_mouse: "Mueve el mouse automáticamente para mantenerte activo en apps que detectan actividad.",
             whatsapp: "Programa mensajes de WhatsApp para enviarse automáticamente en una fecha y hora específicas.",
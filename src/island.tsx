@@ -26,12 +26,109 @@ const pillStyle = {
     position: 'relative' as const
 };
 
+type RecordingStatus = { state: 'idle' | 'starting' | 'countdown' | 'recording'; countdown_secs: number; elapsed_secs: number };
+
+function recordingLabel(key: 'starting' | 'stop'): string {
+    const lang = (localStorage.getItem("app-language") as Language) || "es";
+    return translations[lang]?.recording?.[key] ?? translations.es.recording[key];
+}
+
+// Countdown, then a red dot with the elapsed time and a stop button
+const RecordingIsland = () => {
+    const [status, setStatus] = useState<RecordingStatus | null>(null);
+
+    useEffect(() => {
+        const tick = async () => {
+            try {
+                const next = await invoke<RecordingStatus>("get_recording_status");
+                if (next.state === 'idle') {
+                    await getCurrentWebviewWindow().close();
+                    return;
+                }
+                setStatus(next);
+            } catch (err) {
+                console.error("Failed to get recording status:", err);
+            }
+        };
+        tick();
+        const interval = setInterval(tick, 250);
+        return () => clearInterval(interval);
+    }, []);
+
+    const handleStop = async () => {
+        try {
+            await invoke("stop_region_recording");
+        } catch (e) {
+            console.error(e);
+        }
+    };
+
+    const pad = (n: number) => n.toString().padStart(2, '0');
+    const elapsed = status ? `${Math.floor(status.elapsed_secs / 60)}:${pad(status.elapsed_secs % 60)}` : "...";
+    const isCountdown = status?.state === 'countdown';
+
+    return (
+        <div style={{
+            width: '100vw',
+            height: '100vh',
+            display: 'flex',
+            alignItems: 'flex-start',
+            justifyContent: 'center',
+            paddingTop: '8px',
+            userSelect: 'none',
+            overflow: 'hidden'
+        }}>
+            <div style={pillStyle} data-tauri-drag-region>
+                <div style={{ display: 'flex', alignItems: 'center', gap: '8px', zIndex: 1 }} data-tauri-drag-region>
+                    <span style={{
+                        width: '10px',
+                        height: '10px',
+                        borderRadius: '5px',
+                        backgroundColor: isCountdown ? '#8e8e93' : '#ff3b30'
+                    }} data-tauri-drag-region />
+                    <span style={{
+                        fontSize: '15px',
+                        fontWeight: 600,
+                        fontFamily: '-apple-system, BlinkMacSystemFont, "Segoe UI", Roboto, sans-serif',
+                        color: '#ffffff',
+                        letterSpacing: '0.3px',
+                        whiteSpace: 'nowrap'
+                    }} data-tauri-drag-region>
+                        {isCountdown ? `${recordingLabel('starting')} ${status?.countdown_secs}` : elapsed}
+                    </span>
+                </div>
+                <button
+                    onClick={handleStop}
+                    title={recordingLabel('stop')}
+                    style={{
+                        background: 'rgba(255, 59, 48, 0.9)',
+                        border: 'none',
+                        width: '22px',
+                        height: '22px',
+                        borderRadius: '11px',
+                        display: 'flex',
+                        alignItems: 'center',
+                        justifyContent: 'center',
+                        cursor: 'pointer',
+                        zIndex: 1,
+                        outline: 'none',
+                        marginLeft: '16px'
+                    }}
+                >
+                    <span style={{ width: '8px', height: '8px', backgroundColor: '#ffffff', borderRadius: '1px' }} />
+                </button>
+            </div>
+        </div>
+    );
+};
+
 const Island = () => {
     const [timeLeftStr, setTimeLeftStr] = useState("...");
     const [ocrStatus, setOcrStatus] = useState<'loading' | 'success' | 'no_text' | 'error'>('loading');
 
     const params = typeof globalThis.window !== "undefined" ? new URLSearchParams(globalThis.window.location.search) : new URLSearchParams();
     const isOcrMode = params.get("mode") === "ocr";
+    const isRecordingMode = params.get("mode") === "recording";
 
     const getStatusLabel = () => {
         const lang = (localStorage.getItem("app-language") as Language) || "es";
@@ -81,7 +178,7 @@ const Island = () => {
     }, [isOcrMode]);
 
     useEffect(() => {
-        if (isOcrMode) return;
+        if (isOcrMode || isRecordingMode) return;
         let interval: ReturnType<typeof setInterval>;
 
         const tick = async () => {
@@ -129,6 +226,10 @@ const Island = () => {
         }
     };
 
+    if (isRecordingMode) {
+        return <RecordingIsland />;
+    }
+
     if (isOcrMode) {
         return (
             <div style={{